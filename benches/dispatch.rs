@@ -0,0 +1,112 @@
+//! ## Dispatch
+//!
+//! Measures the dispatch path of the pool with criterion: how fast jobs are
+//! submitted, how long an idle pool takes to run a job, and how the
+//! throughput scales from 1 to 64 workers. Criterion keeps the results of
+//! the previous run, so a regression is reported as a change between two
+//! commits. Run it with `cargo bench --bench dispatch`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
+    time::{Duration, Instant},
+};
+use workerpool_rs::pool::WorkerPool;
+
+const JOBS: usize = 10_000;
+const WORKERS: [usize; 7] = [1, 2, 4, 8, 16, 32, 64];
+
+// Times the submission of JOBS counter increments, without waiting for them
+// to run.
+fn submit(workers: usize) -> Duration {
+    let counter = Arc::new(AtomicUsize::new(0));
+    let pool = WorkerPool::new(workers);
+
+    let start = Instant::now();
+    for _ in 0..JOBS {
+        let counter = Arc::clone(&counter);
+        pool.execute(move || {
+            counter.fetch_add(1, Ordering::Relaxed);
+        })
+        .unwrap();
+    }
+    let elapsed = start.elapsed();
+    pool.join();
+    assert_eq!(JOBS, counter.load(Ordering::Relaxed));
+    elapsed
+}
+
+// Times JOBS small computations from submission to completion.
+fn compute(workers: usize) -> Duration {
+    let counter = Arc::new(AtomicUsize::new(0));
+    let pool = WorkerPool::new(workers);
+
+    let start = Instant::now();
+    for i in 0..JOBS {
+        let counter = Arc::clone(&counter);
+        pool.execute(move || {
+            let sum = (0..64).fold(i, |sum, x| sum.wrapping_mul(31).wrapping_add(x));
+            counter.fetch_add(sum & 1, Ordering::Relaxed);
+        })
+        .unwrap();
+    }
+    pool.join();
+    start.elapsed()
+}
+
+// Submission throughput, in jobs per second.
+fn submission(c: &mut Criterion) {
+    let mut group = c.benchmark_group("submission");
+    group.throughput(Throughput::Elements(JOBS as u64));
+    group.sample_size(10);
+    for &workers in &WORKERS {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(workers),
+            &workers,
+            |b, &workers| b.iter_custom(|iters| (0..iters).map(|_| submit(workers)).sum()),
+        );
+    }
+    group.finish();
+}
+
+// The round trip of a job submitted to an idle pool and waited for.
+fn latency(c: &mut Criterion) {
+    let mut group = c.benchmark_group("latency");
+    for &workers in &WORKERS {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(workers),
+            &workers,
+            |b, &workers| {
+                let pool = WorkerPool::new(workers);
+                let (tx, rx) = mpsc::channel();
+                b.iter(|| {
+                    let tx = tx.clone();
+                    pool.execute(move || tx.send(()).unwrap()).unwrap();
+                    rx.recv().unwrap();
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+// End to end throughput, in jobs per second.
+fn scalability(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scalability");
+    group.throughput(Throughput::Elements(JOBS as u64));
+    group.sample_size(10);
+    for &workers in &WORKERS {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(workers),
+            &workers,
+            |b, &workers| b.iter_custom(|iters| (0..iters).map(|_| compute(workers)).sum()),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, submission, latency, scalability);
+criterion_main!(benches);
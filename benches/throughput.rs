@@ -0,0 +1,101 @@
+//! ## Throughput
+//!
+//! Measures how many tiny jobs per second the pool dispatches, next to a
+//! baseline pool whose workers share a `Mutex<Receiver>`, as the pool did
+//! before its queue became lock-free. Run it with `cargo bench`.
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use workerpool_rs::pool::{Job, WorkerPool};
+
+const JOBS: usize = 200_000;
+
+// Runs JOBS counter increments on a baseline pool of the given size.
+fn mutex_receiver(workers: usize) -> Duration {
+    let counter = Arc::new(AtomicUsize::new(0));
+    let (tx, rx) = mpsc::channel::<Job>();
+    let rx = Arc::new(Mutex::new(rx));
+    let handles: Vec<_> = (0..workers)
+        .map(|_| {
+            let rx = Arc::clone(&rx);
+            thread::spawn(move || loop {
+                let job = rx.lock().unwrap().recv();
+                match job {
+                    Ok(job) => job(),
+                    Err(_) => break,
+                }
+            })
+        })
+        .collect();
+
+    let start = Instant::now();
+    for _ in 0..JOBS {
+        let counter = Arc::clone(&counter);
+        tx.send(Box::new(move || {
+            counter.fetch_add(1, Ordering::Relaxed);
+        }))
+        .unwrap();
+    }
+    drop(tx);
+    handles.into_iter().for_each(|h| h.join().unwrap());
+    assert_eq!(JOBS, counter.load(Ordering::Relaxed));
+    start.elapsed()
+}
+
+// Runs JOBS counter increments on a WorkerPool of the given size.
+fn worker_pool(workers: usize) -> Duration {
+    let counter = Arc::new(AtomicUsize::new(0));
+    let pool = WorkerPool::new(workers);
+
+    let start = Instant::now();
+    for _ in 0..JOBS {
+        let counter = Arc::clone(&counter);
+        pool.execute(move || {
+            counter.fetch_add(1, Ordering::Relaxed);
+        })
+        .unwrap();
+    }
+    pool.join();
+    assert_eq!(JOBS, counter.load(Ordering::Relaxed));
+    start.elapsed()
+}
+
+// Runs JOBS counter increments on a WorkerPool, submitted as one batch.
+fn worker_pool_batch(workers: usize) -> Duration {
+    let counter = Arc::new(AtomicUsize::new(0));
+    let pool = WorkerPool::new(workers);
+
+    let start = Instant::now();
+    let jobs = (0..JOBS)
+        .map(|_| {
+            let counter = Arc::clone(&counter);
+            Box::new(move || {
+                counter.fetch_add(1, Ordering::Relaxed);
+            }) as Job
+        })
+        .collect();
+    pool.execute_batch(jobs).unwrap();
+    pool.join();
+    assert_eq!(JOBS, counter.load(Ordering::Relaxed));
+    start.elapsed()
+}
+
+// Prints the throughput of a run in jobs per second.
+fn report(name: &str, workers: usize, elapsed: Duration) {
+    let rate = JOBS as f64 / elapsed.as_secs_f64();
+    println!("{:<16} workers: {:<3} {:>12.0} jobs/s", name, workers, rate);
+}
+
+fn main() {
+    for &workers in &[1, 2, 4, 8, 16] {
+        report("mutex-receiver", workers, mutex_receiver(workers));
+        report("workerpool", workers, worker_pool(workers));
+        report("workerpool-batch", workers, worker_pool_batch(workers));
+    }
+}
@@ -0,0 +1,160 @@
+//! ## Affinity
+//!
+//! This module contains the core affinity of the workers, set with
+//! [`WorkerPoolBuilder::affinity`](crate::pool::WorkerPoolBuilder::affinity).
+//! Pinning a worker to a core keeps its caches warm, and pinning workers
+//! to the cores of one node avoids remote memory on NUMA machines.
+//!
+//! Workers are pinned with `sched_setaffinity` on Linux, within the cores
+//! the process is allowed to run on. On other platforms the workers can't
+//! be pinned. A worker that can't be pinned is reported to
+//! [`PoolObserver::on_pin_failed`](crate::observer::PoolObserver::on_pin_failed).
+
+use std::thread;
+
+/// The cores the workers are pinned to.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Affinity {
+    /// Each worker is pinned to one of the cores the process is allowed
+    /// to run on, cycling through them by worker id.
+    #[default]
+    RoundRobin,
+    /// Each worker is pinned to a set of cores, worker `id` to the set at
+    /// `id` modulo the number of sets. Workers are not pinned if there
+    /// are no sets.
+    Cores(Vec<Vec<usize>>),
+}
+
+// An affinity with the cores the process was allowed to run on when the
+// pool was built. They are read once, as a worker spawned by a pinned one
+// inherits its single core.
+//
+// affinity: Affinity - the cores of each worker.\
+// allowed: Vec<usize> - the cores the process may run on, in order.
+#[derive(Debug, Clone)]
+pub(crate) struct Pinning {
+    affinity: Affinity,
+    allowed: Vec<usize>,
+}
+
+impl Pinning {
+    // Constructs a new Pinning, reading the cores the calling thread may
+    // run on.
+    pub(crate) fn new(affinity: Affinity) -> Pinning {
+        Pinning {
+            affinity,
+            allowed: allowed_cores(),
+        }
+    }
+
+    // Returns the cores the given worker is pinned to.
+    pub(crate) fn cores(&self, worker: usize) -> Vec<usize> {
+        match &self.affinity {
+            Affinity::RoundRobin if self.allowed.is_empty() => Vec::new(),
+            Affinity::RoundRobin => vec![self.allowed[worker % self.allowed.len()]],
+            Affinity::Cores(sets) if sets.is_empty() => Vec::new(),
+            Affinity::Cores(sets) => sets[worker % sets.len()].clone(),
+        }
+    }
+}
+
+// The size of the kernel cpu_set_t, one bit per core.
+#[cfg(target_os = "linux")]
+const SET_WORDS: usize = 1024 / 64;
+
+// Returns the cores the calling thread may run on, or every core if the
+// mask can't be read.
+#[cfg(target_os = "linux")]
+fn allowed_cores() -> Vec<usize> {
+    extern "C" {
+        fn sched_getaffinity(pid: i32, size: usize, mask: *mut u64) -> i32;
+    }
+
+    let mut mask = [0u64; SET_WORDS];
+    // The pid 0 is the calling thread, and the mask outlives the call.
+    let read = unsafe { sched_getaffinity(0, std::mem::size_of_val(&mask), mask.as_mut_ptr()) };
+    if read != 0 {
+        return every_core();
+    }
+    (0..SET_WORDS * 64)
+        .filter(|&core| mask[core / 64] & (1 << (core % 64)) != 0)
+        .collect()
+}
+
+// Returns the cores the calling thread may run on, which are every core
+// on this platform.
+#[cfg(not(target_os = "linux"))]
+fn allowed_cores() -> Vec<usize> {
+    every_core()
+}
+
+// Returns the ids of every core of the machine.
+fn every_core() -> Vec<usize> {
+    (0..thread::available_parallelism().map_or(1, |count| count.get())).collect()
+}
+
+// Pins the calling thread to the given cores.
+//
+// **returns**: true if the thread was pinned.
+#[cfg(target_os = "linux")]
+pub(crate) fn pin(cores: &[usize]) -> bool {
+    extern "C" {
+        fn sched_setaffinity(pid: i32, size: usize, mask: *const u64) -> i32;
+    }
+
+    let mut mask = [0u64; SET_WORDS];
+    for &core in cores.iter().filter(|&&core| core < SET_WORDS * 64) {
+        mask[core / 64] |= 1 << (core % 64);
+    }
+    if mask.iter().all(|&word| word == 0) {
+        return false;
+    }
+    // The pid 0 is the calling thread, and the mask outlives the call.
+    unsafe { sched_setaffinity(0, std::mem::size_of_val(&mask), mask.as_ptr()) == 0 }
+}
+
+// Pins the calling thread to the given cores, which is not supported on
+// this platform.
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn pin(_cores: &[usize]) -> bool {
+    false
+}
+
+// This sections are the beginning of affinity module unit tests.
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    #[test]
+    fn affinity_should_map_workers_to_core_sets() {
+        let pinning = Pinning::new(Affinity::Cores(vec![vec![0, 1], vec![2, 3]]));
+        assert_eq!(vec![0, 1], pinning.cores(0));
+        assert_eq!(vec![2, 3], pinning.cores(1));
+        assert_eq!(vec![0, 1], pinning.cores(2));
+        assert!(Pinning::new(Affinity::Cores(Vec::new()))
+            .cores(0)
+            .is_empty());
+        assert_eq!(1, Pinning::new(Affinity::RoundRobin).cores(0).len());
+    }
+
+    #[test]
+    fn round_robin_should_cycle_through_the_allowed_cores() {
+        let pinning = Pinning {
+            affinity: Affinity::RoundRobin,
+            allowed: vec![2, 5, 7],
+        };
+        let cores: Vec<_> = (0..4).flat_map(|worker| pinning.cores(worker)).collect();
+        assert_eq!(vec![2, 5, 7, 2], cores);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn pin_should_reject_an_empty_set() {
+        assert!(!pin(&[]));
+        let allowed = allowed_cores();
+        assert!(!allowed.is_empty());
+        let core = Pinning::new(Affinity::RoundRobin).cores(0);
+        assert!(allowed.contains(&core[0]));
+        assert!(thread::spawn(move || pin(&core)).join().unwrap());
+    }
+}
@@ -0,0 +1,229 @@
+//! ## Autoscale
+//!
+//! This module contains the autoscaler enabled with
+//! [`WorkerPoolBuilder::autoscale`](crate::pool::WorkerPoolBuilder::autoscale).
+//! A supervisor thread samples the pool at a fixed interval, adding a
+//! worker while the queue is longer than a threshold and retiring one
+//! after workers were left idle for a while.
+
+use std::{
+    io,
+    sync::{Arc, Condvar, Mutex, PoisonError},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// The settings of the autoscaler. The fields not given to
+/// [`new`](AutoScale::new) can be overridden with the struct update syntax.
+///
+/// # Examples
+///
+/// ```
+/// use workerpool_rs::autoscale::AutoScale;
+/// use workerpool_rs::pool::WorkerPoolBuilder;
+/// use std::time::Duration;
+///
+/// let pool = WorkerPoolBuilder::new()
+///     .autoscale(AutoScale {
+///         scale_up_queue_len: 8,
+///         scale_down_idle: Duration::from_secs(5),
+///         ..AutoScale::new(1, 8)
+///     })
+///     .build();
+///
+/// assert_eq!(1, pool.num_workers());
+/// assert_eq!(Ok(2), pool.submit(|| 1 + 1).unwrap().wait());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AutoScale {
+    /// The fewest workers the pool keeps.
+    pub min_workers: usize,
+    /// The most workers the pool spawns.
+    pub max_workers: usize,
+    /// A worker is added while more jobs than this are queued.
+    pub scale_up_queue_len: usize,
+    /// A worker is retired once some workers were idle for this long.
+    pub scale_down_idle: Duration,
+    /// How often the pool is sampled.
+    pub interval: Duration,
+}
+
+impl AutoScale {
+    /// Constructs the settings of an autoscaler keeping between min and
+    /// max workers. A worker is added whenever a job is waiting, and one is
+    /// retired after a minute of idleness, sampling every 10 milliseconds.
+    ///
+    /// **min**: usize - The fewest workers, at least one. \
+    /// **max**: usize - The most workers, at least min.
+    ///
+    /// # Panics
+    ///
+    /// Panics if min is zero or greater than max.
+    pub fn new(min: usize, max: usize) -> AutoScale {
+        assert!(min > 0, "a pool needs at least one worker");
+        assert!(min <= max, "min workers can't exceed max workers");
+        AutoScale {
+            min_workers: min,
+            max_workers: max,
+            scale_up_queue_len: 0,
+            scale_down_idle: Duration::from_secs(60),
+            interval: Duration::from_millis(10),
+        }
+    }
+
+    // Clamps a number of workers to the configured range.
+    pub(crate) fn clamp(&self, workers: usize) -> usize {
+        workers.clamp(self.min_workers, self.max_workers)
+    }
+}
+
+// A sample of the pool load.
+//
+// workers: usize - the number of workers.\
+// queued: usize - the number of jobs waiting for a worker.\
+// busy: usize - the number of jobs being executed.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Load {
+    pub(crate) workers: usize,
+    pub(crate) queued: usize,
+    pub(crate) busy: usize,
+}
+
+// Decides the number of workers from successive load samples.
+struct Scaler {
+    config: AutoScale,
+    idle_since: Option<Instant>,
+}
+
+impl Scaler {
+    fn new(config: AutoScale) -> Scaler {
+        Scaler {
+            config,
+            idle_since: None,
+        }
+    }
+
+    // Returns the number of workers the pool should have.
+    fn next_size(&mut self, now: Instant, load: Load) -> usize {
+        let config = &self.config;
+        if load.queued > config.scale_up_queue_len && load.workers < config.max_workers {
+            self.idle_since = None;
+            return load.workers + 1;
+        }
+        if load.queued > 0 || load.busy >= load.workers || load.workers <= config.min_workers {
+            self.idle_since = None;
+            return config.clamp(load.workers);
+        }
+        let since = *self.idle_since.get_or_insert(now);
+        if now.duration_since(since) >= config.scale_down_idle {
+            self.idle_since = Some(now);
+            return load.workers - 1;
+        }
+        load.workers
+    }
+}
+
+// Owns the supervisor thread, which samples the pool and resizes it.
+pub(crate) struct Supervisor {
+    stopped: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Supervisor {
+    pub(crate) fn new<P, R>(config: AutoScale, probe: P, resize: R) -> io::Result<Supervisor>
+    where
+        P: Fn() -> Load + Send + 'static,
+        R: Fn(usize) + Send + 'static,
+    {
+        let stopped = Arc::new((Mutex::new(false), Condvar::new()));
+        let thread_stopped = Arc::clone(&stopped);
+        let thread = thread::Builder::new()
+            .name(String::from("workerpool-autoscale"))
+            .spawn(move || {
+                let mut scaler = Scaler::new(config);
+                let (lock, wakeup) = &*thread_stopped;
+                let mut stopped = lock.lock().unwrap_or_else(PoisonError::into_inner);
+                while !*stopped {
+                    stopped = wakeup
+                        .wait_timeout(stopped, config.interval)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0;
+                    let load = probe();
+                    let size = scaler.next_size(Instant::now(), load);
+                    if size != load.workers && !*stopped {
+                        resize(size);
+                    }
+                }
+            })?;
+
+        Ok(Supervisor {
+            stopped,
+            thread: Some(thread),
+        })
+    }
+
+    // Stops the supervisor thread.
+    pub(crate) fn stop(&mut self) {
+        let (lock, wakeup) = &*self.stopped;
+        *lock.lock().unwrap_or_else(PoisonError::into_inner) = true;
+        wakeup.notify_one();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Supervisor {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+// This sections are the beginning of autoscale module unit tests.
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    fn load(workers: usize, queued: usize, busy: usize) -> Load {
+        Load {
+            workers,
+            queued,
+            busy,
+        }
+    }
+
+    #[test]
+    fn scaler_should_grow_up_to_max_while_jobs_wait() {
+        let mut scaler = Scaler::new(AutoScale::new(1, 2));
+        let now = Instant::now();
+        assert_eq!(2, scaler.next_size(now, load(1, 5, 1)));
+        assert_eq!(2, scaler.next_size(now, load(2, 5, 2)));
+    }
+
+    #[test]
+    fn scaler_should_shrink_after_the_idle_period() {
+        let config = AutoScale {
+            scale_down_idle: Duration::from_secs(1),
+            ..AutoScale::new(1, 4)
+        };
+        let mut scaler = Scaler::new(config);
+        let now = Instant::now();
+        assert_eq!(3, scaler.next_size(now, load(3, 0, 1)));
+        assert_eq!(
+            3,
+            scaler.next_size(now + Duration::from_millis(500), load(3, 0, 0))
+        );
+        assert_eq!(
+            2,
+            scaler.next_size(now + Duration::from_secs(1), load(3, 0, 0))
+        );
+        assert_eq!(
+            2,
+            scaler.next_size(now + Duration::from_secs(2), load(2, 0, 2))
+        );
+        assert_eq!(
+            1,
+            scaler.next_size(now + Duration::from_secs(1), load(1, 0, 0))
+        );
+    }
+}
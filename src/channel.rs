@@ -0,0 +1,137 @@
+//! ## Channel
+//!
+//! This module contains the ring of the job queue built on a bounded
+//! `crossbeam-channel`, used in place of the lock-free ring of the crate
+//! with the `crossbeam` feature. A channel can't show its oldest item, so
+//! reading its stamp moves the item into a slot that is popped first. That
+//! slot is the only lock taken, and only when the queue compares the
+//! stamps of several priority levels.
+
+use crossbeam_channel::{Receiver, Sender};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex, MutexGuard, PoisonError,
+};
+
+// sender: Sender<(u64, T)> - the end items are pushed to.\
+// receiver: Receiver<(u64, T)> - the end items are popped from.\
+// peeked: Mutex<Option<(u64, T)>> - the oldest item, once its stamp was read.\
+// has_peeked: AtomicBool - whether peeked holds an item.
+pub(crate) struct Ring<T> {
+    sender: Sender<(u64, T)>,
+    receiver: Receiver<(u64, T)>,
+    peeked: Mutex<Option<(u64, T)>>,
+    has_peeked: AtomicBool,
+}
+
+impl<T> Ring<T> {
+    // Constructs a ring holding at least capacity items.
+    pub(crate) fn new(capacity: usize) -> Ring<T> {
+        let (sender, receiver) = crossbeam_channel::bounded(capacity.max(2));
+        Ring {
+            sender,
+            receiver,
+            peeked: Mutex::new(None),
+            has_peeked: AtomicBool::new(false),
+        }
+    }
+
+    fn peeked(&self) -> MutexGuard<'_, Option<(u64, T)>> {
+        self.peeked.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Pushes an item with its stamp, giving it back if the ring is full.
+    pub(crate) fn push(&self, stamp: u64, value: T) -> Result<(), T> {
+        self.sender
+            .try_send((stamp, value))
+            .map_err(|error| error.into_inner().1)
+    }
+
+    // Pops the oldest item with its stamp, or returns None if the ring is
+    // empty.
+    pub(crate) fn pop(&self) -> Option<(u64, T)> {
+        if self.has_peeked.load(Ordering::SeqCst) {
+            let mut peeked = self.peeked();
+            if let Some(entry) = peeked.take() {
+                self.has_peeked.store(false, Ordering::SeqCst);
+                return Some(entry);
+            }
+        }
+        self.receiver.try_recv().ok()
+    }
+
+    // Returns whether the ring is empty. Items may be pushed or taken
+    // concurrently, so it is only a hint.
+    pub(crate) fn is_empty(&self) -> bool {
+        !self.has_peeked.load(Ordering::SeqCst) && self.receiver.is_empty()
+    }
+
+    // Returns the stamp of the oldest item without popping it, moving the
+    // item out of the channel if needed. The item may be taken
+    // concurrently, so the stamp is only a hint.
+    pub(crate) fn front_stamp(&self) -> Option<u64> {
+        let mut peeked = self.peeked();
+        if peeked.is_none() {
+            *peeked = self.receiver.try_recv().ok();
+            self.has_peeked.store(peeked.is_some(), Ordering::SeqCst);
+        }
+        peeked.as_ref().map(|(stamp, _)| *stamp)
+    }
+}
+
+// This sections are the beginning of channel module unit tests.
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use std::{sync::Arc, thread};
+
+    #[test]
+    fn channel_ring_should_pop_in_fifo_order_until_empty() {
+        let ring = Ring::new(4);
+        for i in 0..4 {
+            ring.push(i, i).unwrap();
+        }
+        assert_eq!(Err(4), ring.push(4, 4));
+        assert_eq!(Some(0), ring.front_stamp());
+        assert!(!ring.is_empty());
+        assert_eq!(
+            vec![(0, 0), (1, 1), (2, 2), (3, 3)],
+            (0..4).filter_map(|_| ring.pop()).collect::<Vec<_>>()
+        );
+        assert_eq!(None, ring.pop());
+        assert_eq!(None, ring.front_stamp());
+        assert!(ring.is_empty());
+    }
+
+    #[test]
+    fn channel_ring_should_deliver_every_item_across_threads() {
+        let ring = Arc::new(Ring::new(8));
+        let producers: Vec<_> = (0..4u64)
+            .map(|p| {
+                let ring = Arc::clone(&ring);
+                thread::spawn(move || {
+                    for i in 0..1000 {
+                        let mut item = p * 1000 + i;
+                        while let Err(back) = ring.push(0, item) {
+                            item = back;
+                            thread::yield_now();
+                        }
+                    }
+                })
+            })
+            .collect();
+        let mut seen = Vec::new();
+        while seen.len() < 4000 {
+            if seen.len() % 3 == 0 {
+                ring.front_stamp();
+            }
+            match ring.pop() {
+                Some((_, item)) => seen.push(item),
+                None => thread::yield_now(),
+            }
+        }
+        producers.into_iter().for_each(|p| p.join().unwrap());
+        seen.sort_unstable();
+        assert_eq!((0..4000).collect::<Vec<_>>(), seen);
+    }
+}
@@ -0,0 +1,508 @@
+//! ## Config
+//!
+//! This module contains [`PoolConfig`], the settings of a pool as plain
+//! data, so services can keep them next to the rest of their
+//! configuration instead of in code. A config is built field by field,
+//! parsed from flat `key = value` lines, or, with the `serde` feature,
+//! deserialized from any format serde supports, and turned into a pool
+//! with [`WorkerPool::from_config`](crate::pool::WorkerPool::from_config).
+//! Operators can override the settings of a config with the `WORKERPOOL_`
+//! environment variables read by [`PoolConfig::from_env`], without
+//! recompiling.
+//!
+//! ## Examples
+//!
+//! ```
+//! use workerpool_rs::config::PoolConfig;
+//! use workerpool_rs::pool::WorkerPool;
+//!
+//! let config: PoolConfig = r#"
+//!     [pool]
+//!     workers = 2
+//!     queue_size = 64
+//!     thread_name = "ingest"
+//!     panic_policy = "restart"
+//! "#
+//! .parse()
+//! .unwrap();
+//!
+//! let pool = WorkerPool::from_config(&config);
+//! assert_eq!(2, pool.num_workers());
+//! assert_eq!(Ok(4), pool.submit(|| 2 + 2).unwrap().wait());
+//! ```
+
+use crate::{
+    autoscale::AutoScale,
+    pool::{default_num_workers, PanicPolicy, WorkerPoolBuilder},
+};
+use std::{env, fmt::Display, str::FromStr, time::Duration};
+
+// The environment variables read by PoolConfig::from_env, with the
+// settings they override. Later variables win over earlier ones.
+const ENV_VARS: [(&str, &str); 8] = [
+    ("WORKERPOOL_NUM_WORKERS", "workers"),
+    ("WORKERPOOL_THREADS", "workers"),
+    ("WORKERPOOL_QUEUE", "queue_size"),
+    ("WORKERPOOL_THREAD_NAME", "thread_name"),
+    ("WORKERPOOL_IDLE_TIMEOUT_MS", "idle_timeout_ms"),
+    ("WORKERPOOL_PANIC_POLICY", "panic_policy"),
+    ("WORKERPOOL_MIN_WORKERS", "min_workers"),
+    ("WORKERPOOL_MAX_WORKERS", "max_workers"),
+];
+
+/// The settings of a pool. Every field left to None keeps the default of
+/// [`WorkerPoolBuilder`].
+///
+/// With the `serde` feature, it implements `serde::Deserialize`, reading
+/// the same keys as [`set`](PoolConfig::set). Unknown keys are rejected,
+/// and the bounds of the autoscaler should be checked with
+/// [`validate`](PoolConfig::validate) before the config is applied.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "serde")]
+/// # {
+/// use workerpool_rs::config::PoolConfig;
+/// use std::time::Duration;
+///
+/// let config: PoolConfig = toml::from_str(
+///     r#"
+///     workers = 4
+///     idle_timeout_ms = 500
+///     panic_policy = "restart"
+///     "#,
+/// )
+/// .unwrap();
+/// config.validate().unwrap();
+/// assert_eq!(Some(4), config.workers);
+/// assert_eq!(Some(Duration::from_millis(500)), config.idle_timeout);
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct PoolConfig {
+    /// The number of workers, `workers`.
+    pub workers: Option<usize>,
+    /// The capacity of the bounded job queue, `queue_size`.
+    pub queue_size: Option<usize>,
+    /// The prefix of the worker thread names, `thread_name`.
+    pub thread_name: Option<String>,
+    /// How long a worker stays idle before its thread stops,
+    /// `idle_timeout_ms`, in milliseconds.
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "idle_timeout_ms", deserialize_with = "de::millis")
+    )]
+    pub idle_timeout: Option<Duration>,
+    /// What a worker does once a job panicked, `panic_policy`, one of
+    /// `ignore`, `restart` or `abort_pool`.
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "de::panic_policy"))]
+    pub panic_policy: Option<PanicPolicy>,
+    /// The fewest workers of the autoscaler, `min_workers`. Setting
+    /// either bound enables the autoscaler.
+    pub min_workers: Option<usize>,
+    /// The most workers of the autoscaler, `max_workers`.
+    pub max_workers: Option<usize>,
+}
+
+/// The errors of parsing a [`PoolConfig`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    /// The key isn't a setting of the pool.
+    UnknownKey(String),
+    /// The value can't be parsed for its key, or the bounds of the
+    /// autoscaler are inconsistent.
+    InvalidValue {
+        /// The key of the setting.
+        key: String,
+        /// The rejected value.
+        value: String,
+    },
+    /// The line isn't a `key = value` pair.
+    Syntax(String),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::UnknownKey(key) => write!(f, "unknown pool setting {}", key),
+            ConfigError::InvalidValue { key, value } => {
+                write!(f, "invalid value {} for pool setting {}", value, key)
+            }
+            ConfigError::Syntax(line) => write!(f, "expected key = value, found {}", line),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl PoolConfig {
+    /// Sets the setting named key from its textual value. Quotes around
+    /// the value are ignored.
+    ///
+    /// **key**: &str - The name of the setting, as in the field docs. \
+    /// **value**: &str - The value of the setting. \
+    /// **returns**: `Err(ConfigError)` if the key is unknown or the value
+    /// can't be parsed.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        let value = value.trim().trim_matches('"');
+        let invalid = || ConfigError::InvalidValue {
+            key: key.to_string(),
+            value: value.to_string(),
+        };
+        let number = || value.parse::<usize>().map_err(|_| invalid());
+        match key {
+            "workers" => self.workers = Some(number()?),
+            "queue_size" => self.queue_size = Some(number()?),
+            "thread_name" => self.thread_name = Some(value.to_string()),
+            "idle_timeout_ms" => {
+                let millis = value.parse::<u64>().map_err(|_| invalid())?;
+                self.idle_timeout = Some(Duration::from_millis(millis));
+            }
+            "panic_policy" => self.panic_policy = Some(panic_policy(value).ok_or_else(invalid)?),
+            "min_workers" => self.min_workers = Some(number()?),
+            "max_workers" => self.max_workers = Some(number()?),
+            _ => return Err(ConfigError::UnknownKey(key.to_string())),
+        }
+        Ok(())
+    }
+
+    /// Reads a config from the environment. `WORKERPOOL_THREADS`, or the
+    /// older `WORKERPOOL_NUM_WORKERS`, sets the number of workers, and
+    /// `WORKERPOOL_QUEUE` the queue size. The other settings are read from
+    /// their key upper cased and prefixed with `WORKERPOOL_`, as
+    /// `WORKERPOOL_IDLE_TIMEOUT_MS`. The settings without a variable are
+    /// None.
+    ///
+    /// **returns**: `Err(ConfigError)` naming the variable if a value can't
+    /// be parsed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use workerpool_rs::config::PoolConfig;
+    /// use workerpool_rs::pool::WorkerPoolBuilder;
+    ///
+    /// // WORKERPOOL_THREADS=8 overrides the two workers set in code.
+    /// let builder = WorkerPoolBuilder::new().num_workers(2);
+    /// let pool = PoolConfig::from_env().unwrap().apply(builder).build();
+    /// assert!(pool.num_workers() > 0);
+    /// ```
+    pub fn from_env() -> Result<PoolConfig, ConfigError> {
+        PoolConfig::from_vars(|var| env::var(var).ok())
+    }
+
+    // Reads a config from the environment as from_env, but skips the
+    // variables that can't be parsed instead of failing, and the bounds of
+    // the autoscaler if they are invalid.
+    pub(crate) fn from_env_lenient() -> PoolConfig {
+        PoolConfig::read_vars(|var| env::var(var).ok()).0
+    }
+
+    // Reads a config from the variables returned by lookup.
+    fn from_vars<L>(lookup: L) -> Result<PoolConfig, ConfigError>
+    where
+        L: Fn(&str) -> Option<String>,
+    {
+        let (config, errors) = PoolConfig::read_vars(lookup);
+        match errors.into_iter().next() {
+            Some(error) => Err(error),
+            None => Ok(config),
+        }
+    }
+
+    // Reads a config from the variables returned by lookup, skipping the
+    // ones that can't be parsed and the invalid bounds of the autoscaler.
+    // Returns the config with the errors of the skipped settings.
+    fn read_vars<L>(lookup: L) -> (PoolConfig, Vec<ConfigError>)
+    where
+        L: Fn(&str) -> Option<String>,
+    {
+        let mut config = PoolConfig::default();
+        let mut errors = Vec::new();
+        for (var, key) in ENV_VARS.iter() {
+            if let Some(value) = lookup(var) {
+                if config.set(key, &value).is_err() {
+                    errors.push(ConfigError::InvalidValue {
+                        key: var.to_string(),
+                        value,
+                    });
+                }
+            }
+        }
+        if let Err(error) = config.validate() {
+            config.min_workers = None;
+            config.max_workers = None;
+            errors.push(error);
+        }
+        (config, errors)
+    }
+
+    /// Checks that the bounds of the autoscaler can be applied.
+    ///
+    /// **returns**: `Err(ConfigError)` if min_workers is zero or greater
+    /// than max_workers.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let (min, max) = match self.autoscale() {
+            Some(bounds) => bounds,
+            None => return Ok(()),
+        };
+        if min == 0 || min > max {
+            return Err(ConfigError::InvalidValue {
+                key: "min_workers".to_string(),
+                value: min.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns a builder with the settings of the config, to set the
+    /// options that can't be expressed as data.
+    ///
+    /// **returns**: a WorkerPoolBuilder object.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bounds of the autoscaler fail [`validate`](PoolConfig::validate).
+    pub fn builder(&self) -> WorkerPoolBuilder {
+        self.apply(WorkerPoolBuilder::new())
+    }
+
+    /// Overrides the settings of a builder with the settings of the config
+    /// that are set, keeping the others.
+    ///
+    /// **builder**: WorkerPoolBuilder - The builder to override. \
+    /// **returns**: the WorkerPoolBuilder object.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bounds of the autoscaler fail [`validate`](PoolConfig::validate).
+    pub fn apply(&self, mut builder: WorkerPoolBuilder) -> WorkerPoolBuilder {
+        if let Some(policy) = &self.panic_policy {
+            builder = builder.panic_policy(policy.clone());
+        }
+        if let Some(workers) = self.workers {
+            builder = builder.num_workers(workers);
+        }
+        if let Some(capacity) = self.queue_size {
+            builder = builder.queue_capacity(capacity);
+        }
+        if let Some(name) = &self.thread_name {
+            builder = builder.thread_name_prefix(name);
+        }
+        if let Some(timeout) = self.idle_timeout {
+            builder = builder.idle_timeout(timeout);
+        }
+        if let Some((min, max)) = self.autoscale() {
+            builder = builder.autoscale(AutoScale::new(min, max));
+        }
+        builder
+    }
+
+    // Returns the bounds of the autoscaler, if either is set. A missing
+    // min is one worker, and a missing max is the number of workers.
+    fn autoscale(&self) -> Option<(usize, usize)> {
+        if self.min_workers.is_none() && self.max_workers.is_none() {
+            return None;
+        }
+        let min = self.min_workers.unwrap_or(1);
+        let max = self.max_workers.unwrap_or_else(|| {
+            let workers = self.workers.unwrap_or_else(default_num_workers);
+            workers.max(min)
+        });
+        Some((min, max))
+    }
+}
+
+// Returns the panic policy with the given name, if there is one.
+fn panic_policy(name: &str) -> Option<PanicPolicy> {
+    match name {
+        "ignore" => Some(PanicPolicy::Ignore),
+        "restart" => Some(PanicPolicy::Restart),
+        "abort_pool" => Some(PanicPolicy::AbortPool),
+        _ => None,
+    }
+}
+
+// The deserializers of the fields whose value isn't stored as is.
+#[cfg(feature = "serde")]
+mod de {
+    use crate::pool::PanicPolicy;
+    use serde::{de::Error, Deserialize, Deserializer};
+    use std::time::Duration;
+
+    // Deserializes a number of milliseconds into a duration.
+    pub(super) fn millis<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let millis = Option::<u64>::deserialize(deserializer)?;
+        Ok(millis.map(Duration::from_millis))
+    }
+
+    // Deserializes a panic policy from its name.
+    pub(super) fn panic_policy<'de, D>(deserializer: D) -> Result<Option<PanicPolicy>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match Option::<String>::deserialize(deserializer)? {
+            Some(name) => super::panic_policy(&name)
+                .map(Some)
+                .ok_or_else(|| D::Error::custom(format!("unknown panic policy {}", name))),
+            None => Ok(None),
+        }
+    }
+}
+
+impl FromStr for PoolConfig {
+    type Err = ConfigError;
+
+    /// Parses a config from flat `key = value` lines. Blank lines, `#`
+    /// comments and `[section]` headers are skipped. Nested tables, arrays
+    /// and multi-line values aren't read; a full TOML document is read
+    /// with the `serde` feature instead.
+    fn from_str(text: &str) -> Result<PoolConfig, ConfigError> {
+        let mut config = PoolConfig::default();
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') || line.starts_with('[') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| ConfigError::Syntax(line.to_string()))?;
+            config.set(key.trim(), value)?;
+        }
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+// This sections are the beginning of config module unit tests.
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    #[test]
+    fn config_should_parse_every_setting() {
+        let config: PoolConfig = "
+            # the ingest pool
+            workers = 3
+            queue_size = 16
+            thread_name = \"ingest\"
+            idle_timeout_ms = 250
+            panic_policy = \"abort_pool\"
+            min_workers = 2
+            max_workers = 6
+        "
+        .parse()
+        .unwrap();
+        assert_eq!(Some(3), config.workers);
+        assert_eq!(Some(16), config.queue_size);
+        assert_eq!(Some("ingest"), config.thread_name.as_deref());
+        assert_eq!(Some(Duration::from_millis(250)), config.idle_timeout);
+        assert!(matches!(config.panic_policy, Some(PanicPolicy::AbortPool)));
+        assert_eq!(Some((2, 6)), config.autoscale());
+    }
+
+    #[test]
+    fn config_should_reject_invalid_settings() {
+        assert_eq!(
+            Err(ConfigError::UnknownKey("threads".to_string())),
+            "threads = 2".parse::<PoolConfig>().map(|_| ())
+        );
+        assert_eq!(
+            Err(ConfigError::InvalidValue {
+                key: "workers".to_string(),
+                value: "many".to_string(),
+            }),
+            "workers = many".parse::<PoolConfig>().map(|_| ())
+        );
+        assert_eq!(
+            Err(ConfigError::Syntax("workers".to_string())),
+            "workers".parse::<PoolConfig>().map(|_| ())
+        );
+        assert!("min_workers = 4\nmax_workers = 2"
+            .parse::<PoolConfig>()
+            .is_err());
+    }
+
+    #[test]
+    fn config_should_read_the_environment_overrides() {
+        let vars = |var: &str| match var {
+            "WORKERPOOL_NUM_WORKERS" => Some("2".to_string()),
+            "WORKERPOOL_THREADS" => Some("5".to_string()),
+            "WORKERPOOL_QUEUE" => Some("32".to_string()),
+            "WORKERPOOL_PANIC_POLICY" => Some("restart".to_string()),
+            _ => None,
+        };
+        let config = PoolConfig::from_vars(vars).unwrap();
+        assert_eq!(Some(5), config.workers);
+        assert_eq!(Some(32), config.queue_size);
+        assert_eq!(None, config.thread_name);
+        assert!(matches!(config.panic_policy, Some(PanicPolicy::Restart)));
+
+        let pool = config
+            .apply(WorkerPoolBuilder::new().num_workers(1))
+            .build();
+        assert_eq!(5, pool.num_workers());
+
+        let invalid = |var: &str| match var {
+            "WORKERPOOL_QUEUE" => Some("unbounded".to_string()),
+            _ => None,
+        };
+        assert_eq!(
+            Err(ConfigError::InvalidValue {
+                key: "WORKERPOOL_QUEUE".to_string(),
+                value: "unbounded".to_string(),
+            }),
+            PoolConfig::from_vars(invalid).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn config_should_skip_only_the_invalid_environment_overrides() {
+        let vars = |var: &str| match var {
+            "WORKERPOOL_NUM_WORKERS" => Some("3".to_string()),
+            "WORKERPOOL_THREADS" => Some("many".to_string()),
+            "WORKERPOOL_QUEUE" => Some("16".to_string()),
+            "WORKERPOOL_MIN_WORKERS" => Some("4".to_string()),
+            "WORKERPOOL_MAX_WORKERS" => Some("2".to_string()),
+            _ => None,
+        };
+        let (config, errors) = PoolConfig::read_vars(vars);
+        assert_eq!(Some(3), config.workers);
+        assert_eq!(Some(16), config.queue_size);
+        assert_eq!((None, None), (config.min_workers, config.max_workers));
+        assert_eq!(2, errors.len());
+        assert!(PoolConfig::from_vars(vars).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn config_should_deserialize_every_setting() {
+        let config: PoolConfig = toml::from_str(
+            r#"
+            workers = 3
+            queue_size = 16
+            thread_name = "ingest"
+            idle_timeout_ms = 250
+            panic_policy = "abort_pool"
+            min_workers = 2
+            max_workers = 6
+            "#,
+        )
+        .unwrap();
+        assert_eq!(Some(3), config.workers);
+        assert_eq!(Some(16), config.queue_size);
+        assert_eq!(Some("ingest"), config.thread_name.as_deref());
+        assert_eq!(Some(Duration::from_millis(250)), config.idle_timeout);
+        assert!(matches!(config.panic_policy, Some(PanicPolicy::AbortPool)));
+        assert_eq!((Some(2), Some(6)), (config.min_workers, config.max_workers));
+
+        assert!(toml::from_str::<PoolConfig>("panic_policy = \"retry\"").is_err());
+        assert!(toml::from_str::<PoolConfig>("threads = 4").is_err());
+        assert!(toml::from_str::<PoolConfig>("").unwrap().workers.is_none());
+    }
+}
@@ -0,0 +1,228 @@
+//! ## Context
+//!
+//! This module contains the [`JobContext`] given to the jobs executed with
+//! [`WorkerPool::execute_ctx`](crate::pool::WorkerPool::execute_ctx). It
+//! tells a job which worker runs it, its id, how much time is left before
+//! its deadline, and whether it was cancelled, and lets it report its
+//! progress.
+
+use crate::{handle::CancellationToken, progress::ProgressUpdate};
+use std::{
+    fmt::Display,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc,
+    },
+    time::{Duration, Instant},
+};
+
+// The bits of the fraction of a job that didn't report any.
+const NO_FRACTION: u64 = u64::MAX;
+
+/// The id of a job, unique within the pool that assigned it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct JobId(u64);
+
+impl JobId {
+    // Constructs a JobId from the pool counter.
+    pub(crate) fn new(id: u64) -> JobId {
+        JobId(id)
+    }
+
+    /// Returns the id as a number.
+    pub fn as_u64(self) -> u64 {
+        self.0
+    }
+}
+
+impl Display for JobId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "job-{}", self.0)
+    }
+}
+
+/// The state of a job submitted with
+/// [`WorkerPool::submit_tracked`](crate::pool::WorkerPool::submit_tracked).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobStatus {
+    /// The job is waiting for a worker.
+    Queued,
+    /// The job is being executed.
+    Running,
+    /// The job finished normally.
+    Completed,
+    /// The job panicked.
+    Panicked,
+    /// The job was cancelled before it started.
+    Cancelled,
+}
+
+impl JobStatus {
+    /// Checks whether the job is done, whatever the outcome.
+    pub fn is_finished(self) -> bool {
+        !matches!(self, JobStatus::Queued | JobStatus::Running)
+    }
+}
+
+/// What a running job knows about itself.
+///
+/// ## Examples
+///
+/// ```
+/// use workerpool_rs::pool::WorkerPool;
+/// use std::time::{Duration, Instant};
+///
+/// let pool = WorkerPool::new(2);
+/// let deadline = Instant::now() + Duration::from_secs(60);
+/// let handle = pool
+///     .execute_ctx_with_deadline(deadline, |ctx| {
+///         assert!(ctx.worker_id() < 2);
+///         assert!(!ctx.is_cancelled());
+///         ctx.remaining().unwrap() > Duration::ZERO
+///     })
+///     .unwrap();
+///
+/// assert_eq!(Ok(true), handle.wait());
+/// ```
+#[derive(Debug)]
+pub struct JobContext {
+    worker: usize,
+    job: JobId,
+    deadline: Option<Instant>,
+    token: CancellationToken,
+    started: Instant,
+    fraction: AtomicU64,
+    progress: Option<mpsc::Sender<ProgressUpdate>>,
+}
+
+impl JobContext {
+    // Constructs the context of a job about to run, reporting its progress
+    // to the given channel, if any.
+    pub(crate) fn new(
+        worker: usize,
+        job: JobId,
+        deadline: Option<Instant>,
+        token: CancellationToken,
+        progress: Option<mpsc::Sender<ProgressUpdate>>,
+    ) -> JobContext {
+        JobContext {
+            worker,
+            job,
+            deadline,
+            token,
+            started: Instant::now(),
+            fraction: AtomicU64::new(NO_FRACTION),
+            progress,
+        }
+    }
+
+    /// Returns the index of the worker running the job.
+    pub fn worker_id(&self) -> usize {
+        self.worker
+    }
+
+    /// Returns the id the pool assigned to the job.
+    pub fn job_id(&self) -> JobId {
+        self.job
+    }
+
+    /// Returns the deadline of the job, if it has one.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Returns the time left before the deadline, which is zero once it
+    /// has passed, or None if the job has no deadline.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /// Checks whether cancellation was requested through the job handle.
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Returns the cancellation token of the job.
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    /// Reports the fraction of its work the job has done to the progress
+    /// channel of the pool, if it has one. See
+    /// [`WorkerPoolBuilder::progress_channel`](crate::pool::WorkerPoolBuilder::progress_channel).
+    ///
+    /// **fraction**: f64 - The fraction done, clamped between 0 and 1.
+    pub fn progress(&self, fraction: f64) {
+        let fraction = if fraction.is_nan() {
+            0.0
+        } else {
+            fraction.clamp(0.0, 1.0)
+        };
+        self.fraction.store(fraction.to_bits(), Ordering::Relaxed);
+        self.report(None);
+    }
+
+    /// Reports a message to the progress channel of the pool, if it has
+    /// one, along with the last fraction reported.
+    ///
+    /// **message**: Into<String> - The message.
+    pub fn progress_message<M: Into<String>>(&self, message: M) {
+        self.report(Some(message.into()));
+    }
+
+    // Sends an update to the progress channel. Updates are dropped once
+    // the receiver is gone.
+    fn report(&self, message: Option<String>) {
+        let Some(progress) = &self.progress else {
+            return;
+        };
+        let fraction = match self.fraction.load(Ordering::Relaxed) {
+            NO_FRACTION => None,
+            bits => Some(f64::from_bits(bits)),
+        };
+        let _ = progress.send(ProgressUpdate {
+            job: self.job,
+            worker: self.worker,
+            fraction,
+            message,
+            started: self.started,
+            at: Instant::now(),
+        });
+    }
+}
+
+// This sections are the beginning of context module unit tests.
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    #[test]
+    fn context_remaining_should_saturate_after_the_deadline() {
+        let past = Instant::now() - Duration::from_millis(1);
+        let ctx = JobContext::new(0, JobId::new(1), Some(past), CancellationToken::new(), None);
+        assert_eq!(Some(Duration::ZERO), ctx.remaining());
+        assert_eq!("job-1", ctx.job_id().to_string());
+    }
+
+    #[test]
+    fn context_should_report_the_last_fraction_with_messages() {
+        let (tx, rx) = mpsc::channel();
+        let ctx = JobContext::new(1, JobId::new(2), None, CancellationToken::new(), Some(tx));
+        ctx.progress_message("starting");
+        ctx.progress(1.5);
+        ctx.progress_message("finishing");
+        let updates: Vec<_> = rx
+            .try_iter()
+            .map(|update| (update.fraction, update.message))
+            .collect();
+        assert_eq!(
+            vec![
+                (None, Some(String::from("starting"))),
+                (Some(1.0), None),
+                (Some(1.0), Some(String::from("finishing"))),
+            ],
+            updates
+        );
+    }
+}
@@ -0,0 +1,38 @@
+//! ## Dead letter
+//!
+//! This module contains the records sent to the channel set with
+//! [`WorkerPoolBuilder::dead_letter`](crate::pool::WorkerPoolBuilder::dead_letter)
+//! for every job that failed for good: a job that panicked, or a job of
+//! [`WorkerPool::submit_retry`](crate::pool::WorkerPool::submit_retry)
+//! whose every attempt failed.
+
+use crate::context::JobId;
+use std::{any::Any, time::Instant};
+
+/// Why a job failed.
+#[derive(Debug)]
+pub enum Failure {
+    /// The last attempt of a retried job returned this error, as formatted
+    /// by `Debug`.
+    Error(String),
+    /// The job panicked with this payload.
+    Panic(Box<dyn Any + Send>),
+}
+
+/// A job that failed for good.
+#[derive(Debug)]
+pub struct DeadLetter {
+    /// The id of the job, for the jobs that have one, as those of
+    /// `submit_tracked`, `execute_ctx` and `submit_retry`.
+    pub job: Option<JobId>,
+    /// The label of the job, for the jobs of `execute_labeled`.
+    pub label: Option<&'static str>,
+    /// Why the job failed.
+    pub failure: Failure,
+    /// The number of attempts made.
+    pub attempts: u32,
+    /// When the first attempt started.
+    pub started: Instant,
+    /// When the job failed.
+    pub failed: Instant,
+}
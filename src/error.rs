@@ -0,0 +1,127 @@
+//! ## Error
+//!
+//! This module contains the error type of the crate. Each operation
+//! returns its own error, as [`ExecuteError`] or [`JobError`], and every
+//! one of them converts into [`Error`], so a function submitting jobs and
+//! waiting for them can use `?` on both.
+//!
+//! ## Examples
+//!
+//! ```
+//! use workerpool_rs::pool::WorkerPool;
+//! use workerpool_rs::Error;
+//!
+//! fn sum(pool: &WorkerPool) -> Result<u64, Error> {
+//!     let a = pool.submit(|| 20)?;
+//!     let b = pool.submit(|| 22)?;
+//!     Ok(a.wait()? + b.wait()?)
+//! }
+//!
+//! assert_eq!(Ok(42), sum(&WorkerPool::new(2)));
+//! ```
+
+use crate::{
+    handle::JobError,
+    pool::{ExecuteError, Rejected},
+};
+use std::{fmt::Display, io, sync::PoisonError};
+
+/// The errors of the pool operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// A lock of the pool was poisoned by a thread that panicked while
+    /// holding it.
+    PoisonedQueue,
+    /// There is no worker left to receive the job, or the job was dropped
+    /// without producing a value.
+    Disconnected,
+    /// The bounded job queue is full.
+    QueueFull,
+    /// The job didn't finish before the timeout.
+    Timeout,
+    /// The job panicked.
+    JobPanicked,
+    /// The job was cancelled before producing a value.
+    Cancelled,
+    /// A thread of the pool couldn't be spawned, with the kind of the
+    /// error returned by the system.
+    Spawn(io::ErrorKind),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::PoisonedQueue => write!(f, "a lock of the pool was poisoned"),
+            Error::Disconnected => write!(f, "the job or its worker is gone"),
+            Error::QueueFull => write!(f, "the job queue is full"),
+            Error::Timeout => write!(f, "the job didn't finish in time"),
+            Error::JobPanicked => write!(f, "the job panicked"),
+            Error::Cancelled => write!(f, "the job was cancelled"),
+            Error::Spawn(kind) => write!(f, "a thread of the pool couldn't be spawned: {}", kind),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<ExecuteError> for Error {
+    fn from(error: ExecuteError) -> Error {
+        match error {
+            ExecuteError::Disconnected => Error::Disconnected,
+            ExecuteError::QueueFull => Error::QueueFull,
+            ExecuteError::Timeout => Error::Timeout,
+        }
+    }
+}
+
+impl<F> From<Rejected<F>> for Error {
+    fn from(rejected: Rejected<F>) -> Error {
+        Error::from(rejected.error())
+    }
+}
+
+impl From<JobError> for Error {
+    fn from(error: JobError) -> Error {
+        match error {
+            JobError::Timeout => Error::Timeout,
+            JobError::Disconnected => Error::Disconnected,
+            JobError::Cancelled => Error::Cancelled,
+            JobError::Panicked(_) => Error::JobPanicked,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Error {
+        Error::Spawn(error.kind())
+    }
+}
+
+impl<T> From<PoisonError<T>> for Error {
+    fn from(_: PoisonError<T>) -> Error {
+        Error::PoisonedQueue
+    }
+}
+
+// This sections are the beginning of error module unit tests.
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    #[test]
+    fn poisoned_lock_should_convert_into_poisoned_queue() {
+        let lock = Arc::new(Mutex::new(()));
+        let poisoner = Arc::clone(&lock);
+        let _ = thread::spawn(move || {
+            let _guard = poisoner.lock().unwrap();
+            panic!("poison the lock");
+        })
+        .join();
+        let error = lock.lock().map(|_| ()).map_err(Error::from);
+        assert_eq!(Err(Error::PoisonedQueue), error);
+        assert_eq!(Error::QueueFull, Error::from(ExecuteError::QueueFull));
+    }
+}
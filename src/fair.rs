@@ -0,0 +1,85 @@
+//! ## Fair
+//!
+//! This module contains the producer lanes used when
+//! [`WorkerPoolBuilder::fair_scheduling`](crate::pool::WorkerPoolBuilder::fair_scheduling)
+//! is enabled. Each producer has a FIFO lane, and jobs are handed out one
+//! lane at a time, in round-robin order, so a busy producer can't starve
+//! the others.
+
+use crate::task::Task;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Mutex, PoisonError},
+};
+
+// The waiting jobs of each producer, and the producers that have some, in
+// the order they get their next turn.
+#[derive(Default)]
+struct Lanes {
+    jobs: HashMap<u64, VecDeque<Task>>,
+    turns: VecDeque<u64>,
+}
+
+// The lanes of the producers that have a job waiting.
+pub(crate) struct FairLanes {
+    lanes: Mutex<Lanes>,
+}
+
+impl FairLanes {
+    pub(crate) fn new() -> FairLanes {
+        FairLanes {
+            lanes: Mutex::new(Lanes::default()),
+        }
+    }
+
+    // Appends a job to the lane of its producer. A producer without
+    // waiting jobs takes the last turn.
+    pub(crate) fn enqueue(&self, producer: u64, job: Task) {
+        let mut lanes = self.lanes.lock().unwrap_or_else(PoisonError::into_inner);
+        let lane = lanes.jobs.entry(producer).or_default();
+        lane.push_back(job);
+        if lane.len() == 1 {
+            lanes.turns.push_back(producer);
+        }
+    }
+
+    // Returns the oldest job of the producer whose turn it is. A producer
+    // with more waiting jobs goes back to the last turn.
+    pub(crate) fn next(&self) -> Option<Task> {
+        let mut lanes = self.lanes.lock().unwrap_or_else(PoisonError::into_inner);
+        let producer = lanes.turns.pop_front()?;
+        let lane = lanes.jobs.get_mut(&producer)?;
+        let job = lane.pop_front();
+        if lane.is_empty() {
+            lanes.jobs.remove(&producer);
+        } else {
+            lanes.turns.push_back(producer);
+        }
+        job
+    }
+}
+
+// This sections are the beginning of fair module unit tests.
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn lanes_should_take_turns_between_producers() {
+        let lanes = FairLanes::new();
+        let (tx, rx) = mpsc::channel();
+        for (producer, id) in [(1, 10), (1, 11), (1, 12), (2, 20), (3, 30), (2, 21)] {
+            let tx = tx.clone();
+            lanes.enqueue(producer, Task::new(move || tx.send(id).unwrap()));
+        }
+        while let Some(job) = lanes.next() {
+            job.run();
+        }
+        assert_eq!(
+            vec![10, 20, 30, 11, 21, 12],
+            rx.try_iter().collect::<Vec<_>>()
+        );
+        assert!(lanes.next().is_none());
+    }
+}
@@ -0,0 +1,314 @@
+//! ## Future
+//!
+//! This module contains the future returned by
+//! [`WorkerPool::submit_async`](crate::pool::WorkerPool::submit_async), so
+//! async code can offload a blocking job to the pool and await its value
+//! without blocking the executor, and the stream of values returned by
+//! [`WorkerPool::submit_stream`](crate::pool::WorkerPool::submit_stream).
+//! It works with any executor, as the job wakes the task that polled the
+//! future when it finishes.
+//!
+//! A [`JobFuture`] can be awaited from a tokio task, and dropping it, as
+//! when the task is aborted or a `select!` branch loses, cancels the job if
+//! it didn't start yet. With the `tokio` feature, `tokio::BlockingPool`
+//! also bounds the jobs of the tasks by awaiting permits.
+
+use crate::handle::{CancellationToken, JobError};
+use std::{
+    collections::VecDeque,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex, PoisonError},
+    task::{Context, Poll, Waker},
+};
+
+// The result of a job and the waker of the task awaiting it.
+struct Slot<R> {
+    result: Option<Result<R, JobError>>,
+    waker: Option<Waker>,
+}
+
+type SharedSlot<R> = Arc<Mutex<Slot<R>>>;
+
+/// A future resolving to the value returned by a job, or to
+/// `Err(JobError::Disconnected)` if the job panicked or was discarded.
+/// Dropping the future cancels the job, unless it already started.
+///
+/// ## Examples
+///
+/// ```
+/// use workerpool_rs::pool::WorkerPool;
+/// use std::future::Future;
+/// use std::pin::pin;
+/// use std::sync::Arc;
+/// use std::task::{Context, Poll, Wake};
+/// use std::thread::{self, Thread};
+///
+/// // A minimal executor running a single future on the current thread.
+/// struct Unparker(Thread);
+///
+/// impl Wake for Unparker {
+///     fn wake(self: Arc<Self>) {
+///         self.0.unpark();
+///     }
+/// }
+///
+/// fn block_on<F: Future>(future: F) -> F::Output {
+///     let mut future = pin!(future);
+///     let waker = Arc::new(Unparker(thread::current())).into();
+///     let mut cx = Context::from_waker(&waker);
+///     loop {
+///         match future.as_mut().poll(&mut cx) {
+///             Poll::Ready(output) => return output,
+///             Poll::Pending => thread::park(),
+///         }
+///     }
+/// }
+///
+/// let pool = WorkerPool::new(2);
+/// let future = pool.submit_async(|| 6 * 7).unwrap();
+/// assert_eq!(Ok(42), block_on(future));
+/// ```
+pub struct JobFuture<R> {
+    slot: SharedSlot<R>,
+    token: CancellationToken,
+}
+
+impl<R> JobFuture<R> {
+    // Constructs a new JobFuture and the completer the job must use to
+    // deliver its value.
+    pub(crate) fn new() -> (Completer<R>, JobFuture<R>) {
+        let slot = Arc::new(Mutex::new(Slot {
+            result: None,
+            waker: None,
+        }));
+        let completer = Completer {
+            slot: Some(Arc::clone(&slot)),
+        };
+        let future = JobFuture {
+            slot,
+            token: CancellationToken::new(),
+        };
+        (completer, future)
+    }
+
+    // Returns a clone of the token the job must check before it starts.
+    pub(crate) fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+}
+
+impl<R> Drop for JobFuture<R> {
+    fn drop(&mut self) {
+        self.token.cancel();
+    }
+}
+
+impl<R> Future for JobFuture<R> {
+    type Output = Result<R, JobError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut slot = self.slot.lock().unwrap_or_else(PoisonError::into_inner);
+        match slot.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+// Delivers the result of a job to its future. When dropped without a
+// value, as when the job panics or is discarded, the future resolves to
+// Err(JobError::Disconnected).
+pub(crate) struct Completer<R> {
+    slot: Option<SharedSlot<R>>,
+}
+
+impl<R> Completer<R> {
+    pub(crate) fn complete(mut self, value: R) {
+        self.finish(Ok(value));
+    }
+
+    fn finish(&mut self, result: Result<R, JobError>) {
+        if let Some(slot) = self.slot.take() {
+            let mut slot = slot.lock().unwrap_or_else(PoisonError::into_inner);
+            slot.result = Some(result);
+            if let Some(waker) = slot.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl<R> Drop for Completer<R> {
+    fn drop(&mut self) {
+        self.finish(Err(JobError::Disconnected));
+    }
+}
+
+// The values of a stream not taken yet, the number of jobs still running
+// and the waker of the task awaiting the next value.
+struct Queue<R> {
+    ready: VecDeque<R>,
+    remaining: usize,
+    waker: Option<Waker>,
+}
+
+type SharedQueue<R> = Arc<Mutex<Queue<R>>>;
+
+/// A stream of the values returned by the jobs of
+/// [`WorkerPool::submit_stream`](crate::pool::WorkerPool::submit_stream),
+/// in the order they complete. It ends once every job finished; the value
+/// of a job that panicked is skipped.
+///
+/// The crate doesn't depend on the `futures` crate, so it doesn't
+/// implement its `Stream` trait, but [`poll_next`](JobStream::poll_next)
+/// has the same signature, and [`recv`](JobStream::recv) can be awaited
+/// directly.
+pub struct JobStream<R> {
+    queue: SharedQueue<R>,
+}
+
+impl<R> JobStream<R> {
+    // Constructs a new JobStream expecting count values, and the sender
+    // its jobs must share to deliver them.
+    pub(crate) fn new(count: usize) -> (StreamSender<R>, JobStream<R>) {
+        let queue = Arc::new(Mutex::new(Queue {
+            ready: VecDeque::new(),
+            remaining: count,
+            waker: None,
+        }));
+        let sender = StreamSender {
+            queue: Arc::clone(&queue),
+        };
+        (sender, JobStream { queue })
+    }
+
+    /// Polls for the next value.
+    ///
+    /// **returns**: `Poll::Ready(Some(value))` for the next value,
+    /// `Poll::Ready(None)` once every job finished, or `Poll::Pending`.
+    pub fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<R>> {
+        let mut queue = self.queue.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(value) = queue.ready.pop_front() {
+            return Poll::Ready(Some(value));
+        }
+        if queue.remaining == 0 {
+            return Poll::Ready(None);
+        }
+        queue.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    /// Returns a future resolving to the next value, or to None once every
+    /// job finished.
+    pub fn recv(&mut self) -> Recv<'_, R> {
+        Recv { stream: self }
+    }
+}
+
+/// The future returned by [`JobStream::recv`].
+pub struct Recv<'a, R> {
+    stream: &'a mut JobStream<R>,
+}
+
+impl<R> Future for Recv<'_, R> {
+    type Output = Option<R>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<R>> {
+        Pin::new(&mut *self.stream).poll_next(cx)
+    }
+}
+
+// Shared by the jobs of a stream to deliver their values.
+pub(crate) struct StreamSender<R> {
+    queue: SharedQueue<R>,
+}
+
+impl<R> StreamSender<R> {
+    // Returns a guard that counts the job as finished when dropped, even
+    // if it panics before sending its value.
+    pub(crate) fn job(&self) -> StreamJob<R> {
+        StreamJob {
+            queue: Arc::clone(&self.queue),
+        }
+    }
+}
+
+// Delivers the value of one job of a stream.
+pub(crate) struct StreamJob<R> {
+    queue: SharedQueue<R>,
+}
+
+impl<R> StreamJob<R> {
+    pub(crate) fn send(self, value: R) {
+        let mut queue = self.queue.lock().unwrap_or_else(PoisonError::into_inner);
+        queue.ready.push_back(value);
+    }
+}
+
+impl<R> Drop for StreamJob<R> {
+    fn drop(&mut self) {
+        let mut queue = self.queue.lock().unwrap_or_else(PoisonError::into_inner);
+        queue.remaining -= 1;
+        if let Some(waker) = queue.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+// This sections are the beginning of future module unit tests.
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use std::task::Wake;
+
+    struct Flag(Mutex<bool>);
+
+    impl Wake for Flag {
+        fn wake(self: Arc<Self>) {
+            *self.0.lock().unwrap() = true;
+        }
+    }
+
+    #[test]
+    fn future_should_be_woken_when_completed() {
+        let flag = Arc::new(Flag(Mutex::new(false)));
+        let waker = Waker::from(Arc::clone(&flag));
+        let mut cx = Context::from_waker(&waker);
+        let (completer, mut future) = JobFuture::new();
+        assert!(Pin::new(&mut future).poll(&mut cx).is_pending());
+        completer.complete(1);
+        assert!(*flag.0.lock().unwrap());
+        assert_eq!(Poll::Ready(Ok(1)), Pin::new(&mut future).poll(&mut cx));
+    }
+
+    #[test]
+    fn future_should_resolve_to_disconnected_when_dropped() {
+        let waker = Waker::from(Arc::new(Flag(Mutex::new(false))));
+        let mut cx = Context::from_waker(&waker);
+        let (completer, mut future) = JobFuture::<i32>::new();
+        drop(completer);
+        assert_eq!(
+            Poll::Ready(Err(JobError::Disconnected)),
+            Pin::new(&mut future).poll(&mut cx)
+        );
+    }
+
+    #[test]
+    fn stream_should_end_after_every_job_finished() {
+        let waker = Waker::from(Arc::new(Flag(Mutex::new(false))));
+        let mut cx = Context::from_waker(&waker);
+        let (sender, mut stream) = JobStream::new(2);
+        assert!(Pin::new(&mut stream).poll_next(&mut cx).is_pending());
+        sender.job().send(1);
+        drop(sender.job());
+        assert_eq!(
+            Poll::Ready(Some(1)),
+            Pin::new(&mut stream).poll_next(&mut cx)
+        );
+        assert_eq!(Poll::Ready(None), Pin::new(&mut stream).poll_next(&mut cx));
+    }
+}
@@ -0,0 +1,101 @@
+//! ## Global
+//!
+//! This module contains a global pool, built the first time it is used,
+//! so small utilities can run jobs without passing a pool around. The
+//! global pool can be configured once with [`init`], before its first use.
+//! Otherwise it has one worker per available core. Either way, the
+//! `WORKERPOOL_` environment variables read by
+//! [`PoolConfig::from_env`](crate::config::PoolConfig::from_env), as
+//! `WORKERPOOL_THREADS` and `WORKERPOOL_QUEUE`, override its settings.
+
+use crate::{
+    config::PoolConfig,
+    handle::JobHandle,
+    pool::{ExecuteError, WorkerPool, WorkerPoolBuilder},
+};
+use std::sync::OnceLock;
+
+/// The environment variable read for the number of workers of the global
+/// pool. `WORKERPOOL_THREADS` overrides it.
+pub const NUM_WORKERS_VAR: &str = "WORKERPOOL_NUM_WORKERS";
+
+static GLOBAL: OnceLock<WorkerPool> = OnceLock::new();
+
+/// Builds the global pool from the given builder, overridden by the
+/// environment variables.
+///
+/// **builder**: WorkerPoolBuilder - The configuration of the global pool. \
+/// **returns**: false if the global pool was already built, in which case
+/// the builder is ignored.
+///
+/// # Examples
+///
+/// ```
+/// use workerpool_rs::global;
+/// use workerpool_rs::pool::WorkerPoolBuilder;
+///
+/// let builder = WorkerPoolBuilder::new().num_workers(2).thread_name_prefix("global");
+/// assert!(global::init(builder));
+/// assert!(!global::init(WorkerPoolBuilder::new()));
+///
+/// assert_eq!(2, global::pool().num_workers());
+/// ```
+pub fn init(builder: WorkerPoolBuilder) -> bool {
+    let mut built = false;
+    GLOBAL.get_or_init(|| {
+        built = true;
+        with_env(builder).build()
+    });
+    built
+}
+
+/// Returns the global pool, building it with the default configuration,
+/// overridden by the environment variables, if it wasn't built yet.
+pub fn pool() -> &'static WorkerPool {
+    GLOBAL.get_or_init(|| with_env(WorkerPoolBuilder::new()).build())
+}
+
+// Overrides the builder of the global pool with the environment
+// variables. The pool is built on first use, where an error can't be
+// returned, so each invalid variable is ignored while the valid ones still
+// apply, and so is a zero number of workers.
+fn with_env(builder: WorkerPoolBuilder) -> WorkerPoolBuilder {
+    let mut config = PoolConfig::from_env_lenient();
+    config.workers = config.workers.filter(|&size| size > 0);
+    config.apply(builder)
+}
+
+/// Submits a job to the global pool, as [`WorkerPool::submit`].
+///
+/// **f**: A FnOnce closure that returns a value. \
+/// **returns**: a JobHandle, or `Err(ExecuteError)` if the job can't be
+/// queued.
+///
+/// # Examples
+///
+/// ```
+/// let handle = workerpool_rs::spawn(|| 6 * 7).unwrap();
+/// assert_eq!(Ok(42), handle.wait());
+/// ```
+pub fn spawn<F, R>(f: F) -> Result<JobHandle<R>, ExecuteError>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    pool().submit(f)
+}
+
+// This sections are the beginning of global module unit tests.
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    #[test]
+    fn global_pool_should_be_built_once() {
+        let handles: Vec<_> = (0..4u32).map(|i| spawn(move || i).unwrap()).collect();
+        let sum: u32 = handles.into_iter().map(|h| h.wait().unwrap()).sum();
+        assert_eq!(6, sum);
+        assert!(std::ptr::eq(pool(), pool()));
+        assert!(!init(WorkerPoolBuilder::new()));
+    }
+}
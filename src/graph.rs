@@ -0,0 +1,327 @@
+//! ## Graph
+//!
+//! This module contains a [`TaskGraph`] of tasks with dependencies. A task
+//! is queued in the pool as soon as all of its prerequisites succeeded, so
+//! independent tasks run in parallel. A task whose prerequisite failed,
+//! panicked or was skipped is skipped too. A task can only depend on tasks
+//! added before it, so a graph never has a cycle.
+//!
+//! ## Examples
+//!
+//! ```
+//! use workerpool_rs::graph::{TaskGraph, TaskOutcome};
+//! use workerpool_rs::pool::WorkerPool;
+//!
+//! let pool = WorkerPool::new(4);
+//! let mut graph = TaskGraph::new();
+//! let fetch = graph.add(&[], || Ok(()));
+//! let parse = graph.add(&[fetch], || Err("bad syntax"));
+//! let lint = graph.add(&[fetch], || Ok(()));
+//! let build = graph.add(&[parse, lint], || Ok(()));
+//!
+//! let outcomes = graph.run(&pool);
+//! assert_eq!(TaskOutcome::Succeeded, outcomes[lint.index()]);
+//! assert_eq!(TaskOutcome::Failed("bad syntax"), outcomes[parse.index()]);
+//! assert_eq!(TaskOutcome::Skipped, outcomes[build.index()]);
+//! ```
+
+use crate::pool::{JobSender, WorkerPool};
+use std::{
+    mem,
+    sync::{mpsc, Arc, Mutex, PoisonError},
+    thread,
+};
+
+// The closure of a task.
+type TaskFn<E> = Box<dyn FnOnce() -> Result<(), E> + Send>;
+
+/// The id of a task, unique within its graph. Its index is the position of
+/// its outcome in the result of [`TaskGraph::run`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TaskId(usize);
+
+impl TaskId {
+    /// Returns the position of the task in its graph.
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// How a task of a graph ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TaskOutcome<E> {
+    /// The task returned Ok.
+    Succeeded,
+    /// The task returned an error.
+    Failed(E),
+    /// The task panicked.
+    Panicked,
+    /// The task didn't run, as one of its prerequisites didn't succeed, or
+    /// the pool dropped it.
+    Skipped,
+}
+
+impl<E> TaskOutcome<E> {
+    /// Checks whether the task succeeded.
+    pub fn is_success(&self) -> bool {
+        matches!(self, TaskOutcome::Succeeded)
+    }
+}
+
+/// A graph of tasks with dependencies, run on a [`WorkerPool`].
+pub struct TaskGraph<E> {
+    tasks: Vec<TaskFn<E>>,
+    prerequisites: Vec<Vec<TaskId>>,
+}
+
+impl<E: Send + 'static> TaskGraph<E> {
+    /// Constructs a new TaskGraph without tasks.
+    pub fn new() -> TaskGraph<E> {
+        TaskGraph {
+            tasks: Vec::new(),
+            prerequisites: Vec::new(),
+        }
+    }
+
+    /// Adds a task that runs once all of its prerequisites succeeded.
+    ///
+    /// **prerequisites**: &[TaskId] - The tasks to wait for. \
+    /// **f**: A FnOnce closure returning Ok, or an error failing the tasks
+    /// that depend on it. \
+    /// **returns**: the TaskId of the task.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a prerequisite isn't a task of this graph.
+    pub fn add<F>(&mut self, prerequisites: &[TaskId], f: F) -> TaskId
+    where
+        F: FnOnce() -> Result<(), E> + Send + 'static,
+    {
+        let id = TaskId(self.tasks.len());
+        assert!(
+            prerequisites.iter().all(|prerequisite| *prerequisite < id),
+            "a prerequisite must be added to the graph first"
+        );
+        self.tasks.push(Box::new(f));
+        self.prerequisites.push(prerequisites.to_vec());
+        id
+    }
+
+    /// Runs every task on the pool, each one as soon as its prerequisites
+    /// succeeded, and blocks until all of them ended. It must not be called
+    /// from a worker of the pool, which could then wait for itself.
+    ///
+    /// **pool**: &WorkerPool - The pool running the tasks. \
+    /// **returns**: the outcome of every task, in the order they were added.
+    pub fn run(self, pool: &WorkerPool) -> Vec<TaskOutcome<E>> {
+        let count = self.tasks.len();
+        let mut dependents = vec![Vec::new(); count];
+        let waiting: Vec<usize> = self
+            .prerequisites
+            .iter()
+            .enumerate()
+            .map(|(id, prerequisites)| {
+                prerequisites
+                    .iter()
+                    .for_each(|prerequisite| dependents[prerequisite.0].push(id));
+                prerequisites.len()
+            })
+            .collect();
+        let roots: Vec<usize> = (0..count).filter(|id| waiting[*id] == 0).collect();
+        let (tx, rx) = mpsc::channel();
+        let run = Arc::new(GraphRun {
+            sender: pool.sender(),
+            dependents,
+            state: Mutex::new(RunState {
+                tasks: self.tasks.into_iter().map(Some).collect(),
+                waiting,
+                blocked: vec![false; count],
+                done: tx,
+            }),
+        });
+        for id in roots {
+            GraphRun::submit(&run, id);
+        }
+        let mut outcomes: Vec<_> = (0..count).map(|_| TaskOutcome::Skipped).collect();
+        for (id, outcome) in rx.iter().take(count) {
+            outcomes[id] = outcome;
+        }
+        outcomes
+    }
+}
+
+impl<E: Send + 'static> Default for TaskGraph<E> {
+    fn default() -> TaskGraph<E> {
+        TaskGraph::new()
+    }
+}
+
+// A graph being run.
+//
+// sender: JobSender - submits the tasks to the pool.\
+// dependents: Vec<Vec<usize>> - the tasks waiting for each task.\
+// state: Mutex<RunState<E>> - the tasks not queued yet and their
+// prerequisites.
+struct GraphRun<E> {
+    sender: JobSender,
+    dependents: Vec<Vec<usize>>,
+    state: Mutex<RunState<E>>,
+}
+
+// tasks: Vec<Option<TaskFn<E>>> - the tasks not queued yet.\
+// waiting: Vec<usize> - the prerequisites of each task not ended yet.\
+// blocked: Vec<bool> - whether a prerequisite of each task didn't succeed.\
+// done: Sender<(usize, TaskOutcome<E>)> - receives the outcome of every
+// task.
+struct RunState<E> {
+    tasks: Vec<Option<TaskFn<E>>>,
+    waiting: Vec<usize>,
+    blocked: Vec<bool>,
+    done: mpsc::Sender<(usize, TaskOutcome<E>)>,
+}
+
+impl<E: Send + 'static> GraphRun<E> {
+    // Queues a task whose prerequisites all succeeded. A task the pool
+    // rejects is skipped when its runner is dropped.
+    fn submit(run: &Arc<GraphRun<E>>, id: usize) {
+        let task = run
+            .state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .tasks[id]
+            .take();
+        let runner = TaskRunner {
+            run: Arc::clone(run),
+            id,
+            task,
+            ended: false,
+        };
+        let _ = run.sender.execute(move || runner.start());
+    }
+
+    // Records how a task ended, skips the dependents that can't run
+    // anymore, and queues the ones that can.
+    fn end(run: &Arc<GraphRun<E>>, id: usize, outcome: TaskOutcome<E>) {
+        let mut ready = Vec::new();
+        let mut state = run.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut ended = vec![(id, !outcome.is_success())];
+        let _ = state.done.send((id, outcome));
+        while let Some((id, failed)) = ended.pop() {
+            for &next in &run.dependents[id] {
+                state.blocked[next] |= failed;
+                state.waiting[next] -= 1;
+                if state.waiting[next] > 0 {
+                    continue;
+                }
+                if state.blocked[next] {
+                    drop(state.tasks[next].take());
+                    let _ = state.done.send((next, TaskOutcome::Skipped));
+                    ended.push((next, true));
+                } else {
+                    ready.push(next);
+                }
+            }
+        }
+        drop(state);
+        for next in ready {
+            GraphRun::submit(run, next);
+        }
+    }
+}
+
+// Runs a task and reports how it ended, even when it panics or is dropped
+// without running.
+struct TaskRunner<E: Send + 'static> {
+    run: Arc<GraphRun<E>>,
+    id: usize,
+    task: Option<TaskFn<E>>,
+    ended: bool,
+}
+
+impl<E: Send + 'static> TaskRunner<E> {
+    fn start(mut self) {
+        let Some(task) = self.task.take() else {
+            return;
+        };
+        let outcome = match task() {
+            Ok(()) => TaskOutcome::Succeeded,
+            Err(error) => TaskOutcome::Failed(error),
+        };
+        self.ended = true;
+        GraphRun::end(&self.run, self.id, outcome);
+    }
+}
+
+impl<E: Send + 'static> Drop for TaskRunner<E> {
+    fn drop(&mut self) {
+        if mem::replace(&mut self.ended, true) {
+            return;
+        }
+        let outcome = if self.task.is_none() && thread::panicking() {
+            TaskOutcome::Panicked
+        } else {
+            TaskOutcome::Skipped
+        };
+        GraphRun::end(&self.run, self.id, outcome);
+    }
+}
+
+// This sections are the beginning of graph module unit tests.
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn graph_should_run_tasks_after_their_prerequisites() {
+        let pool = WorkerPool::new(4);
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let mut graph = TaskGraph::<()>::new();
+        let mut ids = Vec::new();
+        for prerequisites in [vec![], vec![0], vec![0], vec![1, 2], vec![]] {
+            let order = order.clone();
+            let id = ids.len();
+            let prerequisites: Vec<_> = prerequisites.into_iter().map(|p| ids[p]).collect();
+            ids.push(graph.add(&prerequisites, move || {
+                order.lock().unwrap().push(id);
+                Ok(())
+            }));
+        }
+        let outcomes = graph.run(&pool);
+        assert!(outcomes.iter().all(TaskOutcome::is_success));
+        let order = order.lock().unwrap();
+        let position = |id| order.iter().position(|x| *x == id).unwrap();
+        assert!(position(0) < position(1) && position(0) < position(2));
+        assert!(position(1) < position(3) && position(2) < position(3));
+    }
+
+    #[test]
+    fn graph_should_skip_the_dependents_of_a_panicked_task() {
+        let pool = WorkerPool::new(2);
+        let ran = Arc::new(AtomicUsize::new(0));
+        let mut graph = TaskGraph::<()>::new();
+        let bad = graph.add(&[], || panic!("task panicked"));
+        let counted = ran.clone();
+        let next = graph.add(&[bad], move || {
+            counted.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        });
+        let last = graph.add(&[next], || Ok(()));
+        let other = graph.add(&[], || Ok(()));
+        let outcomes = graph.run(&pool);
+        assert_eq!(TaskOutcome::Panicked, outcomes[bad.index()]);
+        assert_eq!(TaskOutcome::Skipped, outcomes[next.index()]);
+        assert_eq!(TaskOutcome::Skipped, outcomes[last.index()]);
+        assert_eq!(TaskOutcome::Succeeded, outcomes[other.index()]);
+        assert_eq!(0, ran.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    #[should_panic(expected = "a prerequisite must be added to the graph first")]
+    fn graph_should_reject_unknown_prerequisites() {
+        let mut other = TaskGraph::<()>::new();
+        other.add(&[], || Ok(()));
+        let foreign = other.add(&[], || Ok(()));
+        TaskGraph::<()>::new().add(&[foreign], || Ok(()));
+    }
+}
@@ -0,0 +1,271 @@
+//! ## Group
+//!
+//! This module contains [`JobGroup`], a named set of related jobs on a
+//! pool that is waited for, cancelled and inspected as a whole, without
+//! tracking the handle of every job.
+//!
+//! ## Examples
+//!
+//! ```
+//! use workerpool_rs::group::JobGroup;
+//! use workerpool_rs::pool::WorkerPool;
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//! use std::sync::Arc;
+//!
+//! let pool = WorkerPool::new(4);
+//! let group = JobGroup::new(&pool, "thumbnails");
+//! let done = Arc::new(AtomicUsize::new(0));
+//!
+//! for _ in 0..10 {
+//!     let done = done.clone();
+//!     group.execute(move || {
+//!         done.fetch_add(1, Ordering::SeqCst);
+//!     }).unwrap();
+//! }
+//!
+//! group.wait();
+//! assert_eq!(10, done.load(Ordering::SeqCst));
+//! assert_eq!(10, group.stats().completed);
+//! ```
+
+use crate::{
+    handle::CancellationToken,
+    pool::{ExecuteError, JobSender, WorkerPool},
+    wait_group::{GroupGuard, WaitGroup},
+};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+/// The counters of a [`JobGroup`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GroupStats {
+    /// The jobs queued in the group.
+    pub submitted: usize,
+    /// The jobs being executed.
+    pub running: usize,
+    /// The jobs that returned.
+    pub completed: usize,
+    /// The jobs that panicked.
+    pub panicked: usize,
+    /// The jobs that never ran, as the group was cancelled or the pool
+    /// dropped them.
+    pub cancelled: usize,
+}
+
+impl GroupStats {
+    /// Returns the number of jobs not finished yet, queued or running.
+    pub fn pending(&self) -> usize {
+        self.submitted
+            .saturating_sub(self.completed + self.panicked + self.cancelled)
+    }
+}
+
+// submitted, running, completed, panicked, cancelled: AtomicUsize - the
+// counters of GroupStats.
+#[derive(Default)]
+struct Counters {
+    submitted: AtomicUsize,
+    running: AtomicUsize,
+    completed: AtomicUsize,
+    panicked: AtomicUsize,
+    cancelled: AtomicUsize,
+}
+
+/// A named group of jobs executed on a [`WorkerPool`]. Clones share the
+/// same group.
+#[derive(Clone)]
+pub struct JobGroup {
+    name: Arc<str>,
+    sender: JobSender,
+    wait: WaitGroup,
+    token: CancellationToken,
+    counters: Arc<Counters>,
+}
+
+impl JobGroup {
+    /// Constructs a new empty JobGroup submitting its jobs to the pool.
+    ///
+    /// **pool**: &WorkerPool - The pool running the jobs. \
+    /// **name**: Into<String> - The name of the group. \
+    /// **returns**: a JobGroup object.
+    pub fn new<N: Into<String>>(pool: &WorkerPool, name: N) -> JobGroup {
+        JobGroup {
+            name: Arc::from(name.into()),
+            sender: pool.sender(),
+            wait: WaitGroup::new(),
+            token: CancellationToken::new(),
+            counters: Arc::new(Counters::default()),
+        }
+    }
+
+    /// Returns the name of the group.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Executes a job in the group. Once the group is cancelled, the job
+    /// is dropped instead of being run.
+    ///
+    /// **f**: A FnOnce closure. \
+    /// **returns**: `Err(ExecuteError)` if the job can't be queued, in which
+    /// case it isn't counted in the group.
+    pub fn execute<F>(&self, f: F) -> Result<(), ExecuteError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.counters.submitted.fetch_add(1, Ordering::SeqCst);
+        self.wait.add(1);
+        let job = GroupJob {
+            counters: Arc::clone(&self.counters),
+            ran: false,
+            _guard: GroupGuard(self.wait.clone()),
+        };
+        let token = self.token.clone();
+        let result = self.sender.execute(move || {
+            if !token.is_cancelled() {
+                job.run(f);
+            }
+        });
+        if result.is_err() {
+            // The rejected job was dropped and counted as cancelled.
+            self.counters.submitted.fetch_sub(1, Ordering::SeqCst);
+            self.counters.cancelled.fetch_sub(1, Ordering::SeqCst);
+        }
+        result
+    }
+
+    /// Blocks the current thread until every job of the group is finished.
+    pub fn wait(&self) {
+        self.wait.wait();
+    }
+
+    /// Blocks the current thread until every job of the group is finished,
+    /// or the timeout expires.
+    ///
+    /// **timeout**: Duration - The maximum time to wait. \
+    /// **returns**: false if some jobs were still unfinished at the timeout.
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        self.wait.wait_timeout(timeout)
+    }
+
+    /// Cancels the group. Its queued jobs are dropped when a worker takes
+    /// them, and its running jobs see the request through
+    /// [`token`](JobGroup::token). Jobs executed afterwards never run.
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    /// Checks whether the group was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Returns the cancellation token of the group, which its jobs can
+    /// poll to return early once the group is cancelled.
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    /// Returns the counters of the group.
+    pub fn stats(&self) -> GroupStats {
+        let counters = &self.counters;
+        GroupStats {
+            submitted: counters.submitted.load(Ordering::SeqCst),
+            running: counters.running.load(Ordering::SeqCst),
+            completed: counters.completed.load(Ordering::SeqCst),
+            panicked: counters.panicked.load(Ordering::SeqCst),
+            cancelled: counters.cancelled.load(Ordering::SeqCst),
+        }
+    }
+}
+
+// A job of a group, counted when it runs, panics or is dropped without
+// running. The guard marks the job done once it was counted.
+struct GroupJob {
+    counters: Arc<Counters>,
+    ran: bool,
+    _guard: GroupGuard,
+}
+
+impl GroupJob {
+    fn run<F: FnOnce()>(mut self, f: F) {
+        self.ran = true;
+        self.counters.running.fetch_add(1, Ordering::SeqCst);
+        f();
+    }
+}
+
+impl Drop for GroupJob {
+    fn drop(&mut self) {
+        if !self.ran {
+            self.counters.cancelled.fetch_add(1, Ordering::SeqCst);
+            return;
+        }
+        self.counters.running.fetch_sub(1, Ordering::SeqCst);
+        if thread::panicking() {
+            self.counters.panicked.fetch_add(1, Ordering::SeqCst);
+        } else {
+            self.counters.completed.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+}
+
+// This sections are the beginning of group module unit tests.
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn group_should_count_and_cancel_its_jobs() {
+        let pool = WorkerPool::new(1);
+        let group = JobGroup::new(&pool, "batch");
+        let (started_tx, started_rx) = mpsc::channel();
+        let (tx, rx) = mpsc::channel::<()>();
+        group
+            .execute(move || {
+                started_tx.send(()).unwrap();
+                rx.recv().unwrap()
+            })
+            .unwrap();
+        group.execute(|| panic!("group job panicked")).unwrap();
+        group.execute(|| {}).unwrap();
+        started_rx.recv().unwrap();
+        assert_eq!(3, group.stats().pending());
+        assert_eq!(1, group.stats().running);
+
+        group.cancel();
+        tx.send(()).unwrap();
+        group.wait();
+        let stats = group.stats();
+        assert_eq!("batch", group.name());
+        assert_eq!(
+            GroupStats {
+                submitted: 3,
+                running: 0,
+                completed: 1,
+                panicked: 0,
+                cancelled: 2,
+            },
+            stats
+        );
+        assert_eq!(0, stats.pending());
+    }
+
+    #[test]
+    fn group_should_count_panicked_jobs() {
+        let pool = WorkerPool::new(2);
+        let group = JobGroup::new(&pool, "panics");
+        group.execute(|| panic!("group job panicked")).unwrap();
+        group.execute(|| {}).unwrap();
+        assert!(group.wait_timeout(Duration::from_secs(5)));
+        assert_eq!(1, group.stats().panicked);
+        assert_eq!(1, group.stats().completed);
+    }
+}
@@ -0,0 +1,741 @@
+//! ## Handle
+//!
+//! This module contains the handle returned when a job is submitted
+//! with [`WorkerPool::submit`](crate::pool::WorkerPool::submit). It is
+//! used to wait for the job and get back the value it returned, or to
+//! cancel it through its [`CancellationToken`]. Handles can also be
+//! chained with [`then`](JobHandle::then) and gathered with
+//! [`join_all`](JobHandle::join_all), without blocking a thread between
+//! the steps.
+
+use std::{
+    any::Any,
+    cell::{Cell, RefCell},
+    fmt::{Debug, Display},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex, PoisonError,
+    },
+    thread,
+    time::Duration,
+};
+
+/// Errors returned while waiting for a [`JobHandle`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum JobError {
+    /// The job didn't finish before the timeout, either the one given to
+    /// the wait or the execution timeout of the job.
+    Timeout,
+    /// The job was dropped without producing a value, as when it is
+    /// discarded by a shutdown, or its value was already taken.
+    Disconnected,
+    /// The job was cancelled before producing a value.
+    Cancelled,
+    /// The job panicked with this payload.
+    Panicked(PanicPayload),
+}
+
+impl Display for JobError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JobError::Timeout => write!(f, "the job didn't finish in time"),
+            JobError::Disconnected => write!(f, "the job was dropped without a result"),
+            JobError::Cancelled => write!(f, "the job was cancelled"),
+            JobError::Panicked(payload) => match payload.message() {
+                Some(message) => write!(f, "the job panicked: {}", message),
+                None => write!(f, "the job panicked"),
+            },
+        }
+    }
+}
+
+/// The payload a job panicked with, as `std::thread::JoinHandle::join`
+/// returns it. Two payloads are equal when they carry the same message.
+pub struct PanicPayload(Box<dyn Any + Send>);
+
+impl PanicPayload {
+    /// Returns the panic message, if the payload is a string, as it is for
+    /// the `panic!` macro.
+    pub fn message(&self) -> Option<&str> {
+        message(self.0.as_ref())
+    }
+
+    /// Returns the payload, to be inspected with `downcast` or given to
+    /// `std::panic::resume_unwind`.
+    pub fn into_inner(self) -> Box<dyn Any + Send> {
+        self.0
+    }
+}
+
+impl Debug for PanicPayload {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("PanicPayload")
+            .field(&self.message().unwrap_or("Box<dyn Any>"))
+            .finish()
+    }
+}
+
+impl PartialEq for PanicPayload {
+    fn eq(&self, other: &Self) -> bool {
+        self.message() == other.message()
+    }
+}
+
+impl Eq for PanicPayload {}
+
+impl std::error::Error for JobError {}
+
+/// A flag shared between a job and its handle to request cooperative
+/// cancellation. A job that is still queued when cancelled never starts,
+/// while a running job can poll [`is_cancelled`](CancellationToken::is_cancelled)
+/// to stop early.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Constructs a new token that is not cancelled.
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    /// Requests cancellation. Every clone of the token observes it.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Checks whether cancellation was requested.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Checks whether cancellation was requested, as an error, so a job can
+    /// stop early with the `?` operator.
+    ///
+    /// **returns**: `Err(JobError::Cancelled)` if cancellation was requested.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::handle::{CancellationToken, JobError};
+    ///
+    /// fn count(token: &CancellationToken) -> Result<u64, JobError> {
+    ///     let mut rounds = 0;
+    ///     loop {
+    ///         token.check()?;
+    ///         rounds += 1;
+    ///         if rounds == 3 {
+    ///             token.cancel();
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(Err(JobError::Cancelled), count(&CancellationToken::new()));
+    /// ```
+    pub fn check(&self) -> Result<(), JobError> {
+        if self.is_cancelled() {
+            Err(JobError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+// A callback run with the value of a job, or with None if the job was
+// dropped without producing one.
+type Continuation<R> = Box<dyn FnOnce(Option<R>) + Send>;
+
+// The callback waiting for the value of a job, shared by the job and its
+// handle.
+type SharedContinuation<R> = Arc<Mutex<Option<Continuation<R>>>>;
+
+// Why a job failed, as when it panicked or timed out, shared by the job and
+// its handle.
+type FailureSlot = Arc<Mutex<Option<JobError>>>;
+
+// A callback run with the payload caught by the worker, or with None if the
+// panic was caught by the job itself.
+type PanicWaiter = Box<dyn FnOnce(Option<Box<dyn Any + Send>>)>;
+
+thread_local! {
+    // The completers of the jobs that panicked on this worker and wait for
+    // the payload, or None when the thread isn't running a job.
+    static PANICKED: RefCell<Option<Vec<PanicWaiter>>> = const { RefCell::new(None) };
+}
+
+// Makes the completers dropped by a panic on this thread wait for its
+// payload. The worker calls it before each job.
+pub(crate) fn watch_panics() {
+    PANICKED.with(|panicked| *panicked.borrow_mut() = Some(Vec::new()));
+}
+
+// Checks whether this thread watches for panics, as it runs a job.
+pub(crate) fn is_watching() -> bool {
+    PANICKED.with(|panicked| panicked.borrow().is_some())
+}
+
+// Hands the payload caught by the worker to the completers that wait for
+// it, and stops watching. Every completer but the last one gets a copy of
+// the message. Returns the payload left for the worker: the original one,
+// or a copy if a completer took it.
+pub(crate) fn deliver_panic(payload: Option<Box<dyn Any + Send>>) -> Option<Box<dyn Any + Send>> {
+    let waiters = PANICKED
+        .with(|panicked| panicked.borrow_mut().take())
+        .unwrap_or_default();
+    let Some(payload) = payload else {
+        waiters.into_iter().for_each(|waiter| waiter(None));
+        return None;
+    };
+    let copy = || match message(payload.as_ref()) {
+        Some(message) => Box::new(String::from(message)) as Box<dyn Any + Send>,
+        None => Box::new("Box<dyn Any>"),
+    };
+    let mut waiters = waiters.into_iter().rev();
+    let Some(last) = waiters.next() else {
+        return Some(payload);
+    };
+    waiters.for_each(|waiter| waiter(Some(copy())));
+    let left = copy();
+    last(Some(payload));
+    Some(left)
+}
+
+// The sending side of a JobHandle. The value goes to the continuation set
+// on the handle, if any, and through the channel otherwise. A completer
+// dropped without sending runs the continuation with None. When a job
+// panics on a worker, its completer waits for the worker to catch the
+// payload before it is dropped.
+pub(crate) struct Completer<R: Send + 'static> {
+    sender: mpsc::Sender<R>,
+    next: SharedContinuation<R>,
+    failure: FailureSlot,
+}
+
+impl<R: Send + 'static> Completer<R> {
+    // Delivers the value. The continuation lock is held while the value
+    // goes through the channel, so a handle setting its continuation
+    // either finds the value in the channel or gets called with it.
+    pub(crate) fn send(&self, value: R) -> Result<(), mpsc::SendError<R>> {
+        let mut next = self.next.lock().unwrap_or_else(PoisonError::into_inner);
+        match next.take() {
+            Some(continuation) => {
+                drop(next);
+                continuation(Some(value));
+                Ok(())
+            }
+            None => self.sender.send(value),
+        }
+    }
+
+    // Drops the completer, failing the handle with the error.
+    pub(crate) fn fail(self, error: JobError) {
+        *self.failure.lock().unwrap_or_else(PoisonError::into_inner) = Some(error);
+    }
+}
+
+impl<R: Send + 'static> Drop for Completer<R> {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            let waiting = PANICKED.with(|panicked| match &mut *panicked.borrow_mut() {
+                Some(waiters) => {
+                    let completer = Completer {
+                        sender: self.sender.clone(),
+                        next: Arc::clone(&self.next),
+                        failure: Arc::clone(&self.failure),
+                    };
+                    waiters.push(Box::new(move |payload| {
+                        if let Some(payload) = payload {
+                            completer.fail(JobError::Panicked(PanicPayload(payload)));
+                        }
+                    }));
+                    true
+                }
+                None => false,
+            });
+            if waiting {
+                // The continuation now belongs to the waiting clone.
+                return;
+            }
+        }
+        let next = self
+            .next
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if let Some(continuation) = next {
+            continuation(None);
+        }
+    }
+}
+
+/// A handle to a job submitted to the pool. The value returned by the
+/// job is delivered through an internal oneshot channel. Dropping the
+/// handle detaches the job, unless it was made
+/// [`cancel_on_drop`](JobHandle::cancel_on_drop).
+pub struct JobHandle<R> {
+    receiver: mpsc::Receiver<R>,
+    result: Cell<Option<R>>,
+    token: CancellationToken,
+    next: SharedContinuation<R>,
+    failure: FailureSlot,
+    cancel_on_drop: bool,
+}
+
+impl<R> JobHandle<R> {
+    // Returns a clone of the token the job must check before it starts.
+    pub(crate) fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    /// Makes dropping the handle cancel the job, as dropping an async task
+    /// does, so a job whose result nobody waits for anymore doesn't run.
+    /// A job still queued never starts, and a running job sees the request
+    /// through its token. Waiting for the job or chaining it with
+    /// [`then`](JobHandle::then) keeps the mode.
+    ///
+    /// **returns**: the JobHandle.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use std::sync::{mpsc, Arc};
+    ///
+    /// let pool = WorkerPool::new(1);
+    /// let (tx, rx) = mpsc::channel::<()>();
+    /// pool.execute(move || rx.recv().unwrap()).unwrap();
+    ///
+    /// let ran = Arc::new(AtomicBool::new(false));
+    /// let flag = ran.clone();
+    /// let handle = pool
+    ///     .submit(move || flag.store(true, Ordering::SeqCst))
+    ///     .unwrap()
+    ///     .cancel_on_drop();
+    /// drop(handle);
+    ///
+    /// tx.send(()).unwrap();
+    /// pool.join();
+    /// assert!(!ran.load(Ordering::SeqCst));
+    /// ```
+    pub fn cancel_on_drop(mut self) -> JobHandle<R> {
+        self.cancel_on_drop = true;
+        self
+    }
+
+    /// Cancels the job. A job still queued never starts, and a running
+    /// job sees the request through its token.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::handle::JobError;
+    /// use workerpool_rs::pool::WorkerPool;
+    /// use std::sync::mpsc;
+    ///
+    /// let pool = WorkerPool::new(1);
+    /// let (started_tx, started_rx) = mpsc::channel();
+    /// let (tx, rx) = mpsc::channel::<()>();
+    /// pool.execute(move || {
+    ///     started_tx.send(()).unwrap();
+    ///     rx.recv().unwrap();
+    /// })
+    /// .unwrap();
+    /// started_rx.recv().unwrap();
+    ///
+    /// let handle = pool.submit(|| 42).unwrap();
+    /// handle.cancel();
+    /// tx.send(()).unwrap();
+    ///
+    /// assert_eq!(Err(JobError::Cancelled), handle.wait());
+    /// ```
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    // Maps a disconnected channel to the reason the job was dropped.
+    fn dropped(&self) -> JobError {
+        dropped(&self.token, &self.failure)
+    }
+
+    /// Blocks until the job finishes and returns its value.
+    ///
+    /// **returns**: the job value, `Err(JobError::Cancelled)` if the job was
+    /// cancelled, `Err(JobError::Panicked)` with the payload if it
+    /// panicked, or `Err(JobError::Disconnected)` if the job was dropped
+    /// without producing one.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::handle::JobError;
+    /// use workerpool_rs::pool::WorkerPool;
+    ///
+    /// let pool = WorkerPool::new(2);
+    /// let handle = pool.submit(|| 6 * 7).unwrap();
+    /// assert_eq!(Ok(42), handle.wait());
+    ///
+    /// let handle = pool.submit(|| -> u32 { panic!("out of range") }).unwrap();
+    /// match handle.wait() {
+    ///     Err(JobError::Panicked(payload)) => {
+    ///         assert_eq!(Some("out of range"), payload.message())
+    ///     }
+    ///     _ => unreachable!("the job panicked"),
+    /// }
+    /// ```
+    pub fn wait(self) -> Result<R, JobError> {
+        if let Some(value) = self.result.take() {
+            return Ok(value);
+        }
+        self.receiver.recv().map_err(|_| self.dropped())
+    }
+
+    /// Blocks until the job finishes or the timeout expires. The value can
+    /// be taken only once, so later calls return `Err(JobError::Disconnected)`.
+    ///
+    /// **timeout**: Duration - The maximum time to wait. \
+    /// **returns**: the job value, or `Err(JobError::Timeout)` if the job
+    /// is still running.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::handle::JobError;
+    /// use workerpool_rs::pool::WorkerPool;
+    /// use std::sync::mpsc;
+    /// use std::time::Duration;
+    ///
+    /// let pool = WorkerPool::new(1);
+    /// let (tx, rx) = mpsc::channel::<()>();
+    /// let handle = pool.submit(move || rx.recv().is_ok()).unwrap();
+    ///
+    /// let timeout = Duration::from_millis(10);
+    /// assert_eq!(Err(JobError::Timeout), handle.wait_timeout(timeout));
+    ///
+    /// tx.send(()).unwrap();
+    /// assert_eq!(Ok(true), handle.wait_timeout(Duration::from_secs(5)));
+    /// ```
+    pub fn wait_timeout(&self, timeout: Duration) -> Result<R, JobError> {
+        if let Some(value) = self.result.take() {
+            return Ok(value);
+        }
+        self.receiver.recv_timeout(timeout).map_err(|e| match e {
+            mpsc::RecvTimeoutError::Timeout => JobError::Timeout,
+            mpsc::RecvTimeoutError::Disconnected => self.dropped(),
+        })
+    }
+
+    /// Checks, without blocking, whether the job has finished.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    ///
+    /// let pool = WorkerPool::new(1);
+    /// let handle = pool.submit(|| "done").unwrap();
+    ///
+    /// pool.join();
+    /// assert!(handle.is_finished());
+    /// assert_eq!(Ok("done"), handle.wait());
+    /// ```
+    pub fn is_finished(&self) -> bool {
+        let value = self.result.take();
+        if value.is_some() {
+            self.result.set(value);
+            return true;
+        }
+        match self.receiver.try_recv() {
+            Ok(value) => {
+                self.result.set(Some(value));
+                true
+            }
+            Err(mpsc::TryRecvError::Empty) => false,
+            Err(mpsc::TryRecvError::Disconnected) => true,
+        }
+    }
+}
+
+impl<R: Send + 'static> JobHandle<R> {
+    // Constructs a new JobHandle and the completer the job must use to
+    // deliver its value.
+    pub(crate) fn new() -> (Completer<R>, JobHandle<R>) {
+        let (tx, rx) = mpsc::channel();
+        let next = Arc::new(Mutex::new(None));
+        let failure = Arc::new(Mutex::new(None));
+        let completer = Completer {
+            sender: tx,
+            next: Arc::clone(&next),
+            failure: Arc::clone(&failure),
+        };
+        let handle = JobHandle {
+            receiver: rx,
+            result: Cell::new(None),
+            token: CancellationToken::new(),
+            next,
+            failure,
+            cancel_on_drop: false,
+        };
+        (completer, handle)
+    }
+
+    // Runs f with the result of the job once it is known: right away if
+    // the job already finished, or else on the thread that finishes it.
+    fn on_complete<F>(self, f: F)
+    where
+        F: FnOnce(Result<R, JobError>) + Send + 'static,
+    {
+        let mut next = self.next.lock().unwrap_or_else(PoisonError::into_inner);
+        let result = match self.result.take() {
+            Some(value) => Ok(value),
+            None => match self.receiver.try_recv() {
+                Ok(value) => Ok(value),
+                Err(mpsc::TryRecvError::Disconnected) => Err(self.dropped()),
+                Err(mpsc::TryRecvError::Empty) => {
+                    let token = self.token.clone();
+                    let failure = Arc::clone(&self.failure);
+                    *next = Some(Box::new(move |value: Option<R>| {
+                        f(value.ok_or_else(|| dropped(&token, &failure)))
+                    }));
+                    return;
+                }
+            },
+        };
+        drop(next);
+        f(result);
+    }
+
+    // Returns a handle to the same job that unparks the thread once the job
+    // finishes, whether it produced a value or not.
+    pub(crate) fn unpark_on_complete(mut self, thread: thread::Thread) -> JobHandle<R> {
+        let (tx, mut handle) = JobHandle::new();
+        handle.token = self.token.clone();
+        handle.cancel_on_drop = std::mem::take(&mut self.cancel_on_drop);
+        self.on_complete(move |result| {
+            match result {
+                Ok(value) => {
+                    let _ = tx.send(value);
+                }
+                Err(error) => tx.fail(error),
+            }
+            thread.unpark();
+        });
+        handle
+    }
+
+    /// Chains a continuation to the job. The continuation runs with the job
+    /// value on the worker that finished the job, right after it, or on the
+    /// calling thread if the job already finished, so no thread blocks in
+    /// between. It should be short, or submit its own jobs. Cancelling the
+    /// returned handle cancels the job too.
+    ///
+    /// **f**: A FnOnce closure taking the job value. \
+    /// **returns**: a JobHandle to the value of f. It returns the error of
+    /// the job if the job didn't produce a value, and `Err(JobError::Panicked)`
+    /// if either the job or f panicked.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    ///
+    /// let pool = WorkerPool::new(2);
+    /// let handle = pool
+    ///     .submit(|| 6)
+    ///     .unwrap()
+    ///     .then(|x| x * 7)
+    ///     .then(|x| format!("{}", x));
+    ///
+    /// assert_eq!(Ok(String::from("42")), handle.wait());
+    /// ```
+    pub fn then<U, F>(mut self, f: F) -> JobHandle<U>
+    where
+        U: Send + 'static,
+        F: FnOnce(R) -> U + Send + 'static,
+    {
+        let (tx, mut handle) = JobHandle::new();
+        handle.token = self.token.clone();
+        handle.cancel_on_drop = std::mem::take(&mut self.cancel_on_drop);
+        self.on_complete(move |result| match result {
+            Ok(value) => {
+                let _ = tx.send(f(value));
+            }
+            Err(error) => tx.fail(error),
+        });
+        handle
+    }
+
+    /// Gathers the values of many jobs, in the order of the handles, into
+    /// one handle, without blocking a thread until they are all done. It
+    /// returns `Err(JobError::Disconnected)` as soon as one of them fails.
+    /// The gathered handles no longer cancel their job when dropped.
+    ///
+    /// **handles**: Vec<JobHandle<R>> - The handles to gather. \
+    /// **returns**: a JobHandle to the values of the jobs.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::handle::JobHandle;
+    /// use workerpool_rs::pool::WorkerPool;
+    ///
+    /// let pool = WorkerPool::new(4);
+    /// let squares: Vec<_> = (1..=4u64)
+    ///     .map(|i| pool.submit(move || i * i).unwrap())
+    ///     .collect();
+    /// let sum = JobHandle::join_all(squares).then(|values| values.iter().sum::<u64>());
+    ///
+    /// assert_eq!(Ok(30), sum.wait());
+    /// ```
+    pub fn join_all(handles: Vec<JobHandle<R>>) -> JobHandle<Vec<R>> {
+        let (tx, handle) = JobHandle::new();
+        if handles.is_empty() {
+            let _ = tx.send(Vec::new());
+            return handle;
+        }
+        let slots: Vec<Option<R>> = handles.iter().map(|_| None).collect();
+        let state = Arc::new(Mutex::new((slots, handles.len(), Some(tx))));
+        for (index, mut job) in handles.into_iter().enumerate() {
+            job.cancel_on_drop = false;
+            let state = Arc::clone(&state);
+            job.on_complete(move |result| {
+                let mut state = state.lock().unwrap_or_else(PoisonError::into_inner);
+                let (slots, left, tx) = &mut *state;
+                let Ok(value) = result else {
+                    drop(tx.take());
+                    return;
+                };
+                slots[index] = Some(value);
+                *left -= 1;
+                if *left == 0 {
+                    if let Some(tx) = tx.take() {
+                        let _ = tx.send(slots.drain(..).flatten().collect());
+                    }
+                }
+            });
+        }
+        handle
+    }
+}
+
+impl<R> Drop for JobHandle<R> {
+    fn drop(&mut self) {
+        if self.cancel_on_drop {
+            self.token.cancel();
+        }
+    }
+}
+
+// Returns the message of a panic payload, if it is a string.
+fn message(payload: &(dyn Any + Send)) -> Option<&str> {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+}
+
+// Returns why a job was dropped without producing a value.
+fn dropped(token: &CancellationToken, failure: &FailureSlot) -> JobError {
+    let failure = failure
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take();
+    match failure {
+        Some(error) => error,
+        None if token.is_cancelled() => JobError::Cancelled,
+        None => JobError::Disconnected,
+    }
+}
+
+// This sections are the beginning of handle module unit tests.
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    #[test]
+    fn handle_should_return_sent_value() {
+        let (tx, handle) = JobHandle::new();
+        tx.send(7).unwrap();
+        assert!(handle.is_finished());
+        assert_eq!(Ok(7), handle.wait());
+    }
+
+    #[test]
+    fn handle_should_return_disconnected_when_sender_drops() {
+        let (tx, handle) = JobHandle::<u8>::new();
+        assert!(!handle.is_finished());
+        drop(tx);
+        assert!(handle.is_finished());
+        assert_eq!(Err(JobError::Disconnected), handle.wait());
+    }
+
+    #[test]
+    fn handle_should_return_cancelled_when_cancelled_job_drops() {
+        let (tx, handle) = JobHandle::<u8>::new();
+        let token = handle.token();
+        handle.cancel();
+        assert!(token.is_cancelled());
+        drop(tx);
+        assert_eq!(Err(JobError::Cancelled), handle.wait());
+    }
+
+    #[test]
+    fn then_should_run_on_values_sent_before_or_after() {
+        let (tx, handle) = JobHandle::new();
+        tx.send(2).unwrap();
+        assert_eq!(Ok(3), handle.then(|x| x + 1).wait());
+
+        let (tx, handle) = JobHandle::new();
+        let next = handle.then(|x: i32| x * 10);
+        assert!(!next.is_finished());
+        tx.send(4).unwrap();
+        assert_eq!(Ok(40), next.wait());
+    }
+
+    #[test]
+    fn then_and_join_all_should_pass_on_failures() {
+        let (tx, handle) = JobHandle::<u8>::new();
+        let next = handle.then(|x| x + 1);
+        next.cancel();
+        drop(tx);
+        assert_eq!(Err(JobError::Cancelled), next.wait());
+
+        let (first_tx, first) = JobHandle::<u8>::new();
+        let (second_tx, second) = JobHandle::new();
+        let joined = JobHandle::join_all(vec![first, second]);
+        first_tx.send(1).unwrap();
+        drop(second_tx);
+        assert_eq!(Err(JobError::Disconnected), joined.wait());
+        assert_eq!(Ok(vec![]), JobHandle::<u8>::join_all(Vec::new()).wait());
+    }
+
+    #[test]
+    fn handle_should_get_the_payload_of_a_watched_panic() {
+        let (tx, handle) = JobHandle::<u8>::new();
+        let next = handle.then(|x| x + 1);
+        watch_panics();
+        let result = std::panic::catch_unwind(move || {
+            let _tx = tx;
+            panic!("job panicked");
+        });
+        let left = deliver_panic(result.err());
+        assert_eq!(
+            Some("job panicked"),
+            left.as_deref()
+                .and_then(|p| p.downcast_ref::<String>().map(String::as_str))
+        );
+        let error = JobError::Panicked(PanicPayload(Box::new("job panicked")));
+        assert_eq!(Err(error), next.wait());
+    }
+
+    #[test]
+    fn then_should_keep_the_cancel_on_drop_mode() {
+        let (_tx, handle) = JobHandle::<u8>::new();
+        let token = handle.token();
+        let next = handle.cancel_on_drop().then(|x| x + 1);
+        assert!(!token.is_cancelled());
+        drop(next);
+        assert!(token.is_cancelled());
+    }
+}
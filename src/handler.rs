@@ -0,0 +1,125 @@
+//! ## Handler
+//!
+//! This module contains a message-oriented pool, as an alternative to the
+//! closure API. Each worker owns a [`TaskHandler`] that turns inputs into
+//! outputs, and the pool dispatches the inputs and sends the outputs back
+//! over a channel, in the style of an actor pool.
+
+use crate::{
+    pool::{ExecuteError, WorkerPool},
+    state::StatefulPool,
+};
+use std::sync::mpsc;
+
+/// Handles the messages sent to a [`HandlerPool`]. Each worker builds its
+/// own handler, so `handle` gets exclusive access to it.
+pub trait TaskHandler: 'static {
+    /// The message the handler receives.
+    type Input: Send + 'static;
+    /// The message the handler produces.
+    type Output: Send + 'static;
+
+    /// Handles one input and returns its output.
+    fn handle(&mut self, input: Self::Input) -> Self::Output;
+}
+
+/// A pool whose workers each own a handler of type `H`. Inputs are sent
+/// with [`send`](HandlerPool::send), and outputs are received, in the order
+/// they are produced, from the receiver returned by
+/// [`new`](HandlerPool::new).
+///
+/// # Examples
+///
+/// ```
+/// use workerpool_rs::handler::{HandlerPool, TaskHandler};
+///
+/// struct Doubler;
+///
+/// impl TaskHandler for Doubler {
+///     type Input = u32;
+///     type Output = u32;
+///
+///     fn handle(&mut self, input: u32) -> u32 {
+///         input * 2
+///     }
+/// }
+///
+/// let (pool, outputs) = HandlerPool::new(2, || Doubler);
+/// for i in 1..=4 {
+///     pool.send(i).unwrap();
+/// }
+/// drop(pool);
+///
+/// let mut outputs: Vec<_> = outputs.iter().collect();
+/// outputs.sort();
+/// assert_eq!(vec![2, 4, 6, 8], outputs);
+/// ```
+pub struct HandlerPool<H: TaskHandler> {
+    pool: StatefulPool<H>,
+    outputs: mpsc::Sender<H::Output>,
+}
+
+impl<H: TaskHandler> HandlerPool<H> {
+    /// Constructs a new HandlerPool of size x.
+    ///
+    /// **size**: usize - Is the number of workers in the pool. \
+    /// **factory**: A Fn closure that builds the handler of a worker. \
+    /// **returns**: the pool and the receiver of the outputs. The receiver
+    /// is disconnected once the pool is dropped and its inputs handled.
+    pub fn new<F>(size: usize, factory: F) -> (HandlerPool<H>, mpsc::Receiver<H::Output>)
+    where
+        F: Fn() -> H + Send + Sync + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+        let pool = HandlerPool {
+            pool: WorkerPool::with_state(size, factory),
+            outputs: tx,
+        };
+        (pool, rx)
+    }
+
+    /// Sends an input to be handled by one of the workers.
+    ///
+    /// **input**: H::Input - The message to handle. \
+    /// **returns**: `Err(ExecuteError)` if the input can't be queued.
+    pub fn send(&self, input: H::Input) -> Result<(), ExecuteError> {
+        let outputs = self.outputs.clone();
+        self.pool.execute_with_state(move |handler: &mut H| {
+            let _ = outputs.send(handler.handle(input));
+        })
+    }
+
+    /// Blocks the current thread until every input is handled.
+    pub fn join(&self) {
+        self.pool.join();
+    }
+}
+
+// This sections are the beginning of handler module unit tests.
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    // Counts the inputs handled by one worker.
+    struct Counter(usize);
+
+    impl TaskHandler for Counter {
+        type Input = ();
+        type Output = usize;
+
+        fn handle(&mut self, _input: ()) -> usize {
+            self.0 += 1;
+            self.0
+        }
+    }
+
+    #[test]
+    fn handler_should_keep_its_state_between_inputs() {
+        let (pool, outputs) = HandlerPool::new(1, || Counter(0));
+        for _ in 0..3 {
+            pool.send(()).unwrap();
+        }
+        pool.join();
+        assert_eq!(vec![1, 2, 3], outputs.try_iter().collect::<Vec<_>>());
+    }
+}
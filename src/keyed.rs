@@ -0,0 +1,92 @@
+//! ## Keyed
+//!
+//! This module contains the lanes used by
+//! [`WorkerPool::execute_keyed`](crate::pool::WorkerPool::execute_keyed).
+//! Each key has a FIFO lane, and only the job at the front of a lane is in
+//! the pool queue, so jobs with the same key run one at a time and in order.
+
+use crate::pool::Job;
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, VecDeque},
+    hash::{Hash, Hasher},
+    sync::{Mutex, PoisonError},
+};
+
+// The lanes of the keys that have a job queued or running. A lane without
+// waiting jobs means its front job is in the pool queue or running.
+pub(crate) struct KeyedLanes {
+    lanes: Mutex<HashMap<u64, VecDeque<Job>>>,
+}
+
+impl KeyedLanes {
+    pub(crate) fn new() -> KeyedLanes {
+        KeyedLanes {
+            lanes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Hashes a key into its lane id. Keys with the same hash share a lane.
+    pub(crate) fn lane_id<K: Hash>(key: &K) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    // Appends a job to its lane. If the lane was idle, it is created and
+    // the job is given back, as the caller must queue it in the pool.
+    pub(crate) fn enqueue(&self, lane: u64, job: Job) -> Option<Job> {
+        let mut lanes = self.lanes.lock().unwrap_or_else(PoisonError::into_inner);
+        match lanes.get_mut(&lane) {
+            Some(waiting) => {
+                waiting.push_back(job);
+                None
+            }
+            None => {
+                lanes.insert(lane, VecDeque::new());
+                Some(job)
+            }
+        }
+    }
+
+    // Returns the next job of a lane, or removes the lane if it is empty.
+    pub(crate) fn next(&self, lane: u64) -> Option<Job> {
+        let mut lanes = self.lanes.lock().unwrap_or_else(PoisonError::into_inner);
+        let next = lanes.get_mut(&lane).and_then(VecDeque::pop_front);
+        if next.is_none() {
+            lanes.remove(&lane);
+        }
+        next
+    }
+
+    // Removes a lane and returns its waiting jobs.
+    pub(crate) fn remove(&self, lane: u64) -> Vec<Job> {
+        let mut lanes = self.lanes.lock().unwrap_or_else(PoisonError::into_inner);
+        lanes.remove(&lane).map(Vec::from).unwrap_or_default()
+    }
+}
+
+// This sections are the beginning of keyed module unit tests.
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    #[test]
+    fn lanes_should_give_back_only_the_first_job() {
+        let lanes = KeyedLanes::new();
+        let lane = KeyedLanes::lane_id(&"account");
+        assert!(lanes.enqueue(lane, Box::new(|| {})).is_some());
+        assert!(lanes.enqueue(lane, Box::new(|| {})).is_none());
+        assert!(lanes.next(lane).is_some());
+        assert!(lanes.next(lane).is_none());
+        assert!(lanes.enqueue(lane, Box::new(|| {})).is_some());
+    }
+
+    #[test]
+    fn lanes_should_remove_waiting_jobs() {
+        let lanes = KeyedLanes::new();
+        assert!(lanes.enqueue(7, Box::new(|| {})).is_some());
+        assert!(lanes.enqueue(7, Box::new(|| {})).is_none());
+        assert_eq!(1, lanes.remove(7).len());
+        assert!(lanes.remove(7).is_empty());
+    }
+}
@@ -23,43 +23,89 @@
 //!     pool.execute(move|| {
 //!         let tx = atx.lock().unwrap();
 //!         tx.send(1).expect("channel will be there waiting for the pool");
-//!     });
+//!     }).unwrap();
 //! }
 //!
 //! assert_eq!(rx.iter().take(n_jobs).fold(0, |a, b| a + b), 8);
 //!```
 //!
-//! ### Sinchronized with Barrier
-//!```
+//! ### Waiting for a batch of jobs
+//!
+//! Waiting on a `Barrier` shared by the jobs deadlocks as soon as there are
+//! more jobs than workers, as the jobs holding the workers wait for jobs
+//! that can't start. Counting completions has no such limit.
 //!
+//!```
 //! use std::sync::atomic::{AtomicUsize, Ordering};
-//! use std::sync::{Arc, Barrier};
-//! use workerpool_rs::pool::WorkerPool;
+//! use std::sync::Arc;
+//! use workerpool_rs::pool::{Job, WorkerPool};
 //!
-//! let n_workers = 42;
+//! let n_workers = 4;
 //! let n_jobs = 23;
 //! let pool = WorkerPool::new(n_workers);
 //! let an_atomic = Arc::new(AtomicUsize::new(0));
 //!
-//! assert!(n_jobs <= n_workers, "too many jobs, will deadlock");
-//!
-//! let barrier = Arc::new(Barrier::new(n_jobs + 1));
-//! for _ in 0..n_jobs {
-//!     let barrier = barrier.clone();
+//! let jobs = (0..n_jobs).map(|_| {
 //!     let an_atomic = an_atomic.clone();
-//!
-//!     pool.execute(move|| {
+//!     Box::new(move || {
 //!         // do the heavy work
 //!         an_atomic.fetch_add(1, Ordering::Relaxed);
+//!     }) as Job
+//! });
 //!
-//!         // then wait for the other threads
-//!         barrier.wait();
-//!     });
-//! }
-//!
-//! barrier.wait();
+//! pool.execute_all_and_wait(jobs).unwrap();
 //! assert_eq!(an_atomic.load(Ordering::SeqCst), /* n_jobs = */ 23);
 //!```
 
-// Imports and makes pool public.
+// Imports and makes pool, affinity, autoscale, config, context, deadletter,
+// error, global, graph, group, handle, handler, observer, parallel, pipeline,
+// progress, rate, results, retry, schedule, scope, scoped, state, stats,
+// thread_priority, tokio, wait_group and watchdog public.
+#[cfg(feature = "affinity")]
+pub mod affinity;
+pub mod autoscale;
+#[cfg(feature = "crossbeam")]
+mod channel;
+pub mod config;
+pub mod context;
+pub mod deadletter;
+pub mod error;
+mod fair;
+#[cfg(feature = "futures")]
+pub mod future;
+pub mod global;
+pub mod graph;
+pub mod group;
+pub mod handle;
+pub mod handler;
+mod keyed;
+pub mod observer;
+pub mod parallel;
+pub mod pipeline;
 pub mod pool;
+pub mod progress;
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
+mod queue;
+pub mod rate;
+pub mod results;
+pub mod retry;
+#[cfg(not(feature = "crossbeam"))]
+mod ring;
+pub mod schedule;
+pub mod scope;
+pub mod scoped;
+pub mod state;
+pub mod stats;
+mod tagged;
+mod task;
+#[cfg(feature = "thread-priority")]
+pub mod thread_priority;
+#[cfg(feature = "tokio")]
+pub mod tokio;
+pub mod wait_group;
+pub mod watchdog;
+mod weighted;
+
+pub use error::Error;
+pub use global::spawn;
@@ -0,0 +1,132 @@
+//! ## Observer
+//!
+//! This module contains the [`PoolObserver`] trait, whose callbacks are
+//! called on the lifecycle events of every job. It can be registered with
+//! [`WorkerPoolBuilder::observer`](crate::pool::WorkerPoolBuilder::observer)
+//! to plug in logging, metrics or tracing.
+//!
+//! ### Tracing
+//!
+//! With the `tracing` feature, every job runs in an info `job` span whose
+//! parent is the span current when the job was submitted, so traces
+//! aren't broken at the pool boundary. The span records the `worker`, the
+//! `label` of the job, its `queue_wait_us`, and `panicked` once the job
+//! panicked.
+
+use std::{
+    any::Any,
+    time::{Duration, Instant},
+};
+
+/// A job an observer callback is called about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JobInfo {
+    /// The id of the worker running the job.
+    pub worker: usize,
+    /// The label of the job, as given to
+    /// [`WorkerPool::execute_labeled`](crate::pool::WorkerPool::execute_labeled),
+    /// for the jobs that have one.
+    pub label: Option<&'static str>,
+    /// When the event happened: the start of the job for
+    /// [`on_start`](PoolObserver::on_start), and its end otherwise.
+    pub at: Instant,
+}
+
+/// Callbacks for the lifecycle events of the jobs of a pool. Every method
+/// has an empty default, so an observer implements only the events it
+/// needs. The callbacks run on the submitting thread or on the worker
+/// thread, so they should be quick; a callback that panics on a worker
+/// kills the worker, as a panicking panic handler does.
+///
+/// # Examples
+///
+/// ```
+/// use workerpool_rs::observer::{JobInfo, PoolObserver};
+/// use workerpool_rs::pool::WorkerPoolBuilder;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// #[derive(Default)]
+/// struct Completed(AtomicUsize);
+///
+/// impl PoolObserver for Completed {
+///     fn on_complete(&self, _job: &JobInfo, _elapsed: Duration) {
+///         self.0.fetch_add(1, Ordering::SeqCst);
+///     }
+/// }
+///
+/// let observer = Arc::new(Completed::default());
+/// let pool = WorkerPoolBuilder::new().observer(Arc::clone(&observer)).build();
+/// for _ in 0..4 {
+///     pool.execute(|| {}).unwrap();
+/// }
+///
+/// pool.join();
+/// assert_eq!(4, observer.0.load(Ordering::SeqCst));
+/// ```
+pub trait PoolObserver: Send + Sync {
+    /// Called on the submitting thread once a job is accepted, which may
+    /// be after a worker already started it.
+    ///
+    /// **at**: Instant - When the job was accepted.
+    fn on_submit(&self, at: Instant) {
+        let _ = at;
+    }
+
+    /// Called on the worker thread right before a job starts.
+    ///
+    /// **job**: &JobInfo - The worker, the label and the start of the job.
+    fn on_start(&self, job: &JobInfo) {
+        let _ = job;
+    }
+
+    /// Called on the worker thread when a job returns normally.
+    ///
+    /// **job**: &JobInfo - The worker, the label and the end of the job. \
+    /// **elapsed**: Duration - How long the job ran.
+    fn on_complete(&self, job: &JobInfo, elapsed: Duration) {
+        let _ = (job, elapsed);
+    }
+
+    /// Called on the worker thread when a job panics, instead of
+    /// [`on_complete`](PoolObserver::on_complete).
+    ///
+    /// **job**: &JobInfo - The worker, the label and the end of the job. \
+    /// **payload**: &(dyn Any + Send) - The panic payload.
+    fn on_panic(&self, job: &JobInfo, payload: &(dyn Any + Send)) {
+        let _ = (job, payload);
+    }
+
+    /// Called on a worker thread that couldn't be pinned to its cores,
+    /// with the `affinity` feature, once it starts. The worker runs its
+    /// jobs unpinned.
+    ///
+    /// **worker**: usize - The id of the worker. \
+    /// **cores**: &[usize] - The cores it should have been pinned to.
+    fn on_pin_failed(&self, worker: usize, cores: &[usize]) {
+        let _ = (worker, cores);
+    }
+}
+
+impl<O: PoolObserver + ?Sized> PoolObserver for std::sync::Arc<O> {
+    fn on_submit(&self, at: Instant) {
+        (**self).on_submit(at)
+    }
+
+    fn on_start(&self, job: &JobInfo) {
+        (**self).on_start(job)
+    }
+
+    fn on_complete(&self, job: &JobInfo, elapsed: Duration) {
+        (**self).on_complete(job, elapsed)
+    }
+
+    fn on_panic(&self, job: &JobInfo, payload: &(dyn Any + Send)) {
+        (**self).on_panic(job, payload)
+    }
+
+    fn on_pin_failed(&self, worker: usize, cores: &[usize]) {
+        (**self).on_pin_failed(worker, cores)
+    }
+}
@@ -0,0 +1,160 @@
+//! ## Parallel
+//!
+//! This module contains the [`IntoParallelJobs`] extension trait, which
+//! turns anything iterable into parallel jobs on a pool, with an
+//! iterator-like chain of adapters. The adapters are layered over
+//! [`WorkerPool::map`], [`WorkerPool::map_reduce`] and
+//! [`WorkerPool::for_each`], so the items are split the same way.
+//!
+//! ## Examples
+//!
+//! ```
+//! use workerpool_rs::parallel::IntoParallelJobs;
+//! use workerpool_rs::pool::WorkerPool;
+//!
+//! let pool = WorkerPool::new(4);
+//! let lengths: Vec<usize> = vec!["parallel", "jobs"]
+//!     .into_par_jobs(&pool)
+//!     .map(str::len)
+//!     .collect();
+//!
+//! assert_eq!(vec![8, 4], lengths);
+//! ```
+
+use crate::pool::WorkerPool;
+use std::iter::FromIterator;
+
+/// Turns a value into parallel jobs on a [`WorkerPool`]. It is implemented
+/// for every `IntoIterator` whose items can be sent to the workers.
+pub trait IntoParallelJobs: IntoIterator + Sized {
+    /// Starts a chain of parallel adapters over the items.
+    ///
+    /// **pool**: &WorkerPool - The pool running the jobs. \
+    /// **returns**: the ParallelJobs over the items.
+    fn into_par_jobs(self, pool: &WorkerPool) -> ParallelJobs<'_, Self>;
+}
+
+impl<I> IntoParallelJobs for I
+where
+    I: IntoIterator,
+    I::Item: Send,
+{
+    fn into_par_jobs(self, pool: &WorkerPool) -> ParallelJobs<'_, I> {
+        ParallelJobs { pool, items: self }
+    }
+}
+
+/// Items to process in parallel, returned by
+/// [`IntoParallelJobs::into_par_jobs`]. Nothing runs until a consuming
+/// method is called.
+pub struct ParallelJobs<'pool, I> {
+    pool: &'pool WorkerPool,
+    items: I,
+}
+
+impl<'pool, I> ParallelJobs<'pool, I>
+where
+    I: IntoIterator,
+    I::Item: Send,
+{
+    /// Maps every item with f.
+    ///
+    /// **f**: A Fn closure applied to each item. \
+    /// **returns**: the ParallelMap of the items.
+    pub fn map<F, R>(self, f: F) -> ParallelMap<'pool, I, F>
+    where
+        F: Fn(I::Item) -> R + Sync,
+        R: Send,
+    {
+        ParallelMap {
+            pool: self.pool,
+            items: self.items,
+            f,
+        }
+    }
+
+    /// Calls f on every item, and returns once every call has finished.
+    ///
+    /// **f**: A Fn closure called with each item.
+    ///
+    /// # Panics
+    ///
+    /// Panics if f panics on any item or the jobs can't be queued.
+    pub fn for_each<F>(self, f: F)
+    where
+        F: Fn(I::Item) + Sync,
+    {
+        self.pool.for_each(self.items, 0, f)
+    }
+}
+
+/// Items mapped in parallel, returned by [`ParallelJobs::map`].
+pub struct ParallelMap<'pool, I, F> {
+    pool: &'pool WorkerPool,
+    items: I,
+    f: F,
+}
+
+impl<I, F, R> ParallelMap<'_, I, F>
+where
+    I: IntoIterator,
+    I::Item: Send,
+    F: Fn(I::Item) -> R + Sync,
+    R: Send,
+{
+    /// Runs the jobs and collects the mapped values, in the order of the
+    /// items.
+    ///
+    /// **returns**: the collection of the mapped values.
+    ///
+    /// # Panics
+    ///
+    /// Panics if f panics on any item or the jobs can't be queued.
+    pub fn collect<C: FromIterator<R>>(self) -> C {
+        self.pool.map(self.items, self.f).into_iter().collect()
+    }
+
+    /// Runs the jobs and reduces the mapped values, in the order of the
+    /// items, as [`WorkerPool::map_reduce`] does.
+    ///
+    /// **identity**: A Fn closure returning the neutral value of reduce. \
+    /// **reduce**: A Fn closure combining two values. \
+    /// **returns**: the reduced value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a closure panics or the jobs can't be queued.
+    pub fn reduce<ID, G>(self, identity: ID, reduce: G) -> R
+    where
+        ID: Fn() -> R + Sync,
+        G: Fn(R, R) -> R + Sync,
+    {
+        self.pool.map_reduce(self.items, self.f, identity, reduce)
+    }
+}
+
+// This sections are the beginning of parallel module unit tests.
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn parallel_jobs_should_map_reduce_and_visit_every_item() {
+        let pool = WorkerPool::new(3);
+        let squares: Vec<u64> = (1..=4u64).into_par_jobs(&pool).map(|i| i * i).collect();
+        assert_eq!(vec![1, 4, 9, 16], squares);
+
+        let sum = (1..=100u64)
+            .into_par_jobs(&pool)
+            .map(|i| i * 2)
+            .reduce(|| 0, |a, b| a + b);
+        assert_eq!(10100, sum);
+
+        let visited = AtomicUsize::new(0);
+        vec![1, 2, 3].into_par_jobs(&pool).for_each(|i| {
+            visited.fetch_add(i, Ordering::SeqCst);
+        });
+        assert_eq!(6, visited.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,445 @@
+//! ## Pipeline
+//!
+//! This module contains a pipeline of stages, each backed by its own
+//! [`WorkerPool`] and connected to the next one by a bounded channel. A
+//! full channel blocks the stage before it, so a slow stage slows down the
+//! whole pipeline instead of buffering its input. Closing the input shuts
+//! the stages down in order, once each one has processed what it received.
+//! Items come out as soon as they are done, or in input order once the
+//! pipeline is [`ordered`](Pipeline::ordered).
+//!
+//! ## Examples
+//!
+//! ```
+//! use workerpool_rs::pipeline::Pipeline;
+//!
+//! let mut squares = Pipeline::new(16)
+//!     .map(4, |x: u64| x * x)
+//!     .filter(2, |x| x % 2 == 0)
+//!     .run(1..=10);
+//!
+//! squares.sort();
+//! assert_eq!(vec![4, 16, 36, 64, 100], squares);
+//! ```
+
+use crate::pool::{ExecuteError, WorkerPool, WorkerPoolBuilder};
+use std::{
+    cell::{Cell, RefCell},
+    collections::BTreeMap,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        mpsc::{self, Receiver, SyncSender},
+        Arc, Mutex, PoisonError,
+    },
+    thread,
+};
+
+// An item with its position in the input. In an ordered pipeline, an item
+// dropped by a stage goes on as None, so the output can be put back in
+// order.
+type Item<T> = (u64, Option<T>);
+
+// Spawns the stages of a pipeline, given the receiver of its input and
+// whether the pipeline is ordered, and returns the receiver of its output.
+// The pools of the stages are pushed in order.
+type Connect<I, O> =
+    Box<dyn FnOnce(Receiver<Item<I>>, bool, &mut Vec<WorkerPool>) -> Receiver<Item<O>> + Send>;
+
+/// A pipeline taking items of type `I` and giving items of type `O`, built
+/// stage by stage. Nothing runs until it is started.
+pub struct Pipeline<I, O> {
+    capacity: usize,
+    ordered: bool,
+    connect: Connect<I, O>,
+}
+
+impl<I: Send + 'static> Pipeline<I, I> {
+    /// Constructs a new Pipeline without stages.
+    ///
+    /// **capacity**: usize - The number of items each channel between two
+    /// stages holds before the sending stage blocks.
+    pub fn new(capacity: usize) -> Pipeline<I, I> {
+        Pipeline {
+            capacity,
+            ordered: false,
+            connect: Box::new(|input, _, _| input),
+        }
+    }
+}
+
+impl<I, O> Pipeline<I, O>
+where
+    I: Send + 'static,
+    O: Send + 'static,
+{
+    /// Makes the pipeline give its output in input order. Items done ahead
+    /// of an earlier one are held at the output until it is done, so a slow
+    /// item makes the output wait, and the held items aren't bounded by the
+    /// channel capacity.
+    ///
+    /// **enabled**: bool - Whether the output keeps the input order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pipeline::Pipeline;
+    /// use std::thread;
+    /// use std::time::Duration;
+    ///
+    /// let output = Pipeline::new(8)
+    ///     .ordered(true)
+    ///     .map(4, |x: u64| {
+    ///         thread::sleep(Duration::from_millis(10 - x));
+    ///         x * 2
+    ///     })
+    ///     .filter(2, |x| x % 3 != 0)
+    ///     .run(0..10);
+    ///
+    /// assert_eq!(vec![2, 4, 8, 10, 14, 16], output);
+    /// ```
+    pub fn ordered(mut self, enabled: bool) -> Pipeline<I, O> {
+        self.ordered = enabled;
+        self
+    }
+
+    /// Adds a stage giving the value of f for each item.
+    ///
+    /// **workers**: usize - The number of workers of the stage, at least one. \
+    /// **f**: A Fn closure mapping an item.
+    pub fn map<P, F>(self, workers: usize, f: F) -> Pipeline<I, P>
+    where
+        P: Send + 'static,
+        F: Fn(O) -> P + Send + Sync + 'static,
+    {
+        self.stage(workers, true, move |item| Some(f(item)))
+    }
+
+    /// Adds a stage keeping only the items for which f returns true.
+    ///
+    /// **workers**: usize - The number of workers of the stage, at least one. \
+    /// **f**: A Fn closure testing an item.
+    pub fn filter<F>(self, workers: usize, f: F) -> Pipeline<I, O>
+    where
+        F: Fn(&O) -> bool + Send + Sync + 'static,
+    {
+        self.stage(workers, true, move |item| f(&item).then_some(item))
+    }
+
+    /// Adds a last stage consuming each item, so the pipeline gives no
+    /// output. Nothing has to be received while the items are sent.
+    ///
+    /// **workers**: usize - The number of workers of the stage, at least one. \
+    /// **f**: A Fn closure consuming an item.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pipeline::Pipeline;
+    /// use std::sync::atomic::{AtomicU64, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// let total = Arc::new(AtomicU64::new(0));
+    /// let sum = total.clone();
+    /// let pipeline = Pipeline::new(4)
+    ///     .map(2, |x: u64| x + 1)
+    ///     .for_each(2, move |x| {
+    ///         sum.fetch_add(x, Ordering::SeqCst);
+    ///     })
+    ///     .start();
+    ///
+    /// for i in 0..10 {
+    ///     pipeline.send(i).unwrap();
+    /// }
+    /// assert!(pipeline.finish().is_empty());
+    /// assert_eq!(55, total.load(Ordering::SeqCst));
+    /// ```
+    pub fn for_each<F>(self, workers: usize, f: F) -> Pipeline<I, ()>
+    where
+        F: Fn(O) + Send + Sync + 'static,
+    {
+        // The stage has no value to put back in order, so it sends nothing,
+        // and its output can't fill up while no one reads it.
+        self.stage(workers, false, move |item| {
+            f(item);
+            None::<()>
+        })
+    }
+
+    // Adds a stage sending on the value of step for each item, if any, or
+    // a placeholder for the dropped items if placeholders is set and the
+    // pipeline is ordered once started.
+    fn stage<P, S>(self, workers: usize, placeholders: bool, step: S) -> Pipeline<I, P>
+    where
+        P: Send + 'static,
+        S: Fn(O) -> Option<P> + Send + Sync + 'static,
+    {
+        let capacity = self.capacity;
+        let connect = self.connect;
+        Pipeline {
+            capacity,
+            ordered: self.ordered,
+            connect: Box::new(move |input, ordered, pools| {
+                let input = Arc::new(Mutex::new(connect(input, ordered, pools)));
+                let placeholders = placeholders && ordered;
+                let (tx, output) = mpsc::sync_channel(capacity);
+                let workers = workers.max(1);
+                let pool = WorkerPoolBuilder::new()
+                    .num_workers(workers)
+                    .thread_name_prefix(&format!("stage{}", pools.len()))
+                    .build();
+                let step = Arc::new(step);
+                for _ in 0..workers {
+                    let (input, tx, step) = (Arc::clone(&input), tx.clone(), Arc::clone(&step));
+                    pool.execute(move || run_stage(&input, &tx, placeholders, &*step))
+                        .expect("a new stage pool accepts its workers");
+                }
+                pools.push(pool);
+                output
+            }),
+        }
+    }
+
+    /// Spawns the stages and returns the running pipeline.
+    pub fn start(self) -> RunningPipeline<I, O> {
+        let (input, rx) = mpsc::sync_channel(self.capacity);
+        let mut pools = Vec::new();
+        let output = (self.connect)(rx, self.ordered, &mut pools);
+        RunningPipeline {
+            input: Some(input),
+            sent: Cell::new(0),
+            output,
+            order: self.ordered.then(|| RefCell::new(Resequencer::default())),
+            pools,
+        }
+    }
+
+    /// Runs every item through the pipeline, fed from another thread, and
+    /// returns the output once the stages are done.
+    ///
+    /// **input**: IntoIterator - The items to process. \
+    /// **returns**: the output items, in the order they came out, or in
+    /// input order if the pipeline is ordered.
+    ///
+    /// # Panics
+    ///
+    /// Panics if iterating over the input panics.
+    pub fn run<T>(self, input: T) -> Vec<O>
+    where
+        T: IntoIterator<Item = I>,
+        T::IntoIter: Send + 'static,
+    {
+        let mut pipeline = self.start();
+        let tx = pipeline
+            .input
+            .take()
+            .expect("a started pipeline has an input");
+        let input = input.into_iter();
+        let feeder = thread::spawn(move || {
+            for (seq, item) in (0..).zip(input) {
+                if tx.send((seq, Some(item))).is_err() {
+                    break;
+                }
+            }
+        });
+        let output = std::iter::from_fn(|| pipeline.recv()).collect();
+        if let Err(payload) = feeder.join() {
+            panic::resume_unwind(payload);
+        }
+        output
+    }
+}
+
+/// A running pipeline. Its output must be read as items are sent, with
+/// [`recv`](RunningPipeline::recv), unless the last channel can hold it
+/// all or the last stage is a [`for_each`](Pipeline::for_each), as a full
+/// channel blocks the stages and then the sender. Dropping
+/// it closes the input and shuts the stages down.
+pub struct RunningPipeline<I, O> {
+    input: Option<SyncSender<Item<I>>>,
+    sent: Cell<u64>,
+    output: Receiver<Item<O>>,
+    order: Option<RefCell<Resequencer<O>>>,
+    pools: Vec<WorkerPool>,
+}
+
+// The position of the next output item in input order, and the items done
+// ahead of it.
+struct Resequencer<O> {
+    next: u64,
+    early: BTreeMap<u64, Option<O>>,
+}
+
+impl<O> Default for Resequencer<O> {
+    fn default() -> Resequencer<O> {
+        Resequencer {
+            next: 0,
+            early: BTreeMap::new(),
+        }
+    }
+}
+
+impl<I, O> RunningPipeline<I, O> {
+    /// Sends an item to the first stage, blocking while its channel is
+    /// full.
+    ///
+    /// **item**: I - The item to process. \
+    /// **returns**: `Err(ExecuteError::Disconnected)` if the input is
+    /// closed.
+    pub fn send(&self, item: I) -> Result<(), ExecuteError> {
+        let input = self.input.as_ref().ok_or(ExecuteError::Disconnected)?;
+        let seq = self.sent.get();
+        input
+            .send((seq, Some(item)))
+            .map_err(|_| ExecuteError::Disconnected)?;
+        self.sent.set(seq + 1);
+        Ok(())
+    }
+
+    /// Blocks until the last stage gives an item.
+    ///
+    /// **returns**: the next output item, or None once the input is closed
+    /// and every stage is done.
+    pub fn recv(&self) -> Option<O> {
+        let Some(order) = &self.order else {
+            return self.output.iter().find_map(|(_, value)| value);
+        };
+        let mut order = order.borrow_mut();
+        loop {
+            let next = order.next;
+            let value = match order.early.remove(&next) {
+                Some(value) => value,
+                None => {
+                    let (seq, value) = self.output.recv().ok()?;
+                    if seq != next {
+                        order.early.insert(seq, value);
+                        continue;
+                    }
+                    value
+                }
+            };
+            order.next += 1;
+            if value.is_some() {
+                return value;
+            }
+        }
+    }
+
+    /// Closes the input. The stages stop once they processed the items
+    /// already sent.
+    pub fn close(&mut self) {
+        self.input = None;
+    }
+
+    /// Closes the input and waits for the stages to finish.
+    ///
+    /// **returns**: the output items not received yet.
+    pub fn finish(mut self) -> Vec<O> {
+        self.close();
+        let output = std::iter::from_fn(|| self.recv()).collect();
+        self.pools.clear();
+        output
+    }
+}
+
+// Takes items from the stage input until it is closed, and sends on the
+// value of step, until the next stage is gone. An item whose step panics is
+// dropped, and so is its placeholder unless placeholders is set.
+fn run_stage<O, P>(
+    input: &Mutex<Receiver<Item<O>>>,
+    tx: &SyncSender<Item<P>>,
+    placeholders: bool,
+    step: &dyn Fn(O) -> Option<P>,
+) {
+    loop {
+        let item = input.lock().unwrap_or_else(PoisonError::into_inner).recv();
+        let Ok((seq, item)) = item else {
+            return;
+        };
+        let value = item.and_then(|item| {
+            panic::catch_unwind(AssertUnwindSafe(|| step(item)))
+                .ok()
+                .flatten()
+        });
+        if value.is_none() && !placeholders {
+            continue;
+        }
+        if tx.send((seq, value)).is_err() {
+            return;
+        }
+    }
+}
+
+// This sections are the beginning of pipeline module unit tests.
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    #[test]
+    fn pipeline_should_go_on_after_a_stage_panics() {
+        let mut output = Pipeline::new(2)
+            .map(2, |x: i32| if x == 3 { panic!("bad item") } else { x })
+            .map(1, |x| x * 10)
+            .run(0..6);
+        output.sort();
+        assert_eq!(vec![0, 10, 20, 40, 50], output);
+    }
+
+    #[test]
+    fn dropped_pipeline_should_shut_down_its_stages() {
+        let pipeline = Pipeline::new(1).map(2, |x: i32| x).start();
+        for i in 0..3 {
+            pipeline.send(i).unwrap();
+        }
+        drop(pipeline);
+
+        let mut closed = Pipeline::new(1).map(1, |x: i32| x).start();
+        closed.close();
+        assert_eq!(Err(ExecuteError::Disconnected), closed.send(1));
+        assert_eq!(None, closed.recv());
+    }
+
+    #[test]
+    fn ordered_pipeline_should_keep_input_order_across_drops() {
+        let pipeline = Pipeline::new(4)
+            .ordered(true)
+            .map(3, |x: i32| if x == 2 { panic!("bad item") } else { x })
+            .filter(2, |x| x % 5 != 0)
+            .start();
+        for i in 0..12 {
+            pipeline.send(i).unwrap();
+        }
+        assert_eq!(Some(1), pipeline.recv());
+        assert_eq!(vec![3, 4, 6, 7, 8, 9, 11], pipeline.finish());
+    }
+
+    #[test]
+    fn pipeline_ordered_after_its_stages_should_keep_input_order() {
+        let output = Pipeline::new(8)
+            .map(2, |x: u64| x)
+            .filter(2, |x| x % 3 != 0)
+            .ordered(true)
+            .map(2, |x| x)
+            .run(0..10);
+        assert_eq!(vec![1, 2, 4, 5, 7, 8], output);
+    }
+
+    #[test]
+    fn for_each_pipeline_should_not_need_its_output_read() {
+        for ordered in [false, true] {
+            let total = Arc::new(Mutex::new(0));
+            let sum = Arc::clone(&total);
+            let pipeline = Pipeline::new(2)
+                .ordered(ordered)
+                .map(2, |x: u64| x * 2)
+                .filter(2, |x| x % 3 != 0)
+                .for_each(2, move |x| *sum.lock().unwrap() += x)
+                .start();
+            for i in 0..1000 {
+                pipeline.send(i).unwrap();
+            }
+            assert!(pipeline.finish().is_empty());
+            let expected: u64 = (0..1000).map(|x| x * 2).filter(|x| x % 3 != 0).sum();
+            assert_eq!(expected, *total.lock().unwrap());
+        }
+    }
+}
@@ -4,23 +4,370 @@
 //! start jobs, wait for workers, and many others concurrent
 //! tasks are made easy.
 
+use crate::{
+    autoscale::{AutoScale, Load, Supervisor},
+    config::PoolConfig,
+    context::{JobContext, JobId, JobStatus},
+    deadletter::{DeadLetter, Failure},
+    error::Error,
+    fair::FairLanes,
+    handle::{self, CancellationToken, Completer, JobError, JobHandle},
+    keyed::KeyedLanes,
+    observer::{JobInfo, PoolObserver},
+    progress::ProgressUpdate,
+    queue::JobQueue,
+    rate::{RateLimit, RateLimiter, RatePolicy},
+    results::{ResultChannels, Results},
+    retry::{Exhausted, RetryPolicy},
+    schedule::{RepeatMode, ScheduledHandle, Scheduler},
+    scope::Scope,
+    state::StatefulPool,
+    stats::{self, Histogram, LabelCounters, LabelStats, PoolStats, WorkerHeartbeat},
+    tagged::TagGroups,
+    task::Task,
+    wait_group::{GroupGuard, WaitGroup},
+    watchdog::{StuckJob, Watchdog, Watcher},
+    weighted::WeightBudget,
+};
 use std::{
+    any::Any,
+    cell::{Cell, RefCell},
+    collections::{HashMap, VecDeque},
     fmt::Display,
-    sync::{mpsc, Arc, Mutex},
+    hash::Hash,
+    io,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        mpsc, Arc, Barrier, Condvar, Mutex, OnceLock, PoisonError, Weak,
+    },
     thread,
+    time::{Duration, Instant},
 };
 
+/// A boxed job, as accepted by [`WorkerPool::execute_boxed`]. Passing a
+/// `Job` to [`WorkerPool::execute`] doesn't box it again.
+pub type Job = Box<dyn FnOnce() + Send + 'static>;
+
+// Boxes a closure into a Job, unless it already is one, so submitting a
+// Job costs no extra allocation.
+pub(crate) fn into_job<F>(f: F) -> Job
+where
+    F: FnOnce() + Send + 'static,
+{
+    let mut f = Some(f);
+    if let Some(job) = (&mut f as &mut dyn Any).downcast_mut::<Option<Job>>() {
+        return job.take().expect("job was already taken");
+    }
+    Box::new(f.expect("job was already taken"))
+}
+
 // Basic types for concurrent tasks
-type Job = Box<dyn FnOnce() + Send + Sync + 'static>;
-type JobReceiver = Arc<Mutex<mpsc::Receiver<Job>>>;
 type Handle = thread::JoinHandle<()>;
+type HandleSlot = Arc<Mutex<Option<Handle>>>;
+type SharedState = Arc<Shared>;
+type PanicHandler = Arc<dyn Fn(&(dyn Any + Send)) + Send + Sync>;
+type StatusTable = Arc<Mutex<Statuses>>;
+type MissHandler = Arc<dyn Fn(Duration) + Send + Sync>;
+type StuckHandler = Arc<dyn Fn(StuckJob) + Send + Sync>;
+type WorkerHook = Arc<dyn Fn(usize) + Send + Sync>;
+
+/// Defines how [`WorkerPool::shutdown`] terminates the pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownMode {
+    /// Runs every pending job before stopping the workers.
+    Graceful,
+    /// Lets in-flight jobs finish, but discards the queued ones.
+    Immediate,
+    /// Behaves like `Graceful` until the deadline is reached, then like
+    /// `Immediate`.
+    Timeout(Duration),
+}
+
+/// Defines whether a worker thread that dies is replaced by a new one with
+/// the same id. Job panics are caught and don't kill workers, so this
+/// covers any other failure, as a panicking panic handler. See
+/// [`PanicPolicy`] for what a job panic does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RestartPolicy {
+    /// Dead workers are not replaced.
+    #[default]
+    Never,
+    /// Dead workers are always replaced.
+    Always,
+    /// Each worker is replaced at most n times.
+    UpToN(usize),
+}
+
+/// Defines what `execute` does when a bounded queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SaturationPolicy {
+    /// The caller blocks until there is room for the job.
+    #[default]
+    Block,
+    /// The job is rejected with `ExecuteError::QueueFull`.
+    Error,
+    /// The job next in line is dropped to make room for the new one. The
+    /// dropped job stays counted as submitted in the stats, but never as
+    /// completed, and the observer isn't told about it.
+    DropOldest,
+    /// The new job is dropped. It isn't counted in the stats and the
+    /// observer isn't told about it.
+    DropNewest,
+    /// The new job runs on the calling thread, reported to the observer,
+    /// the stats, the panic handler, the panic policy and the dead letter
+    /// channel like a job run by a worker. A panic doesn't reach the caller.
+    CallerRuns,
+}
+
+/// Defines where the jobs of a pool run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExecutionMode {
+    /// The jobs are queued and run by the worker threads.
+    #[default]
+    Threaded,
+    /// No worker is spawned, and every job runs to completion on the
+    /// thread submitting it, before the submission returns. Tests of code
+    /// using a pool become deterministic and single threaded. The jobs are
+    /// reported as run by worker 0, and as there is no worker thread to
+    /// replace, `PanicPolicy::Restart` behaves as `PanicPolicy::Ignore`.
+    Inline,
+}
+
+/// Defines what a worker does once a job panicked, after the panic was
+/// reported to the observer, the panic handler and the dead letter channel.
+#[derive(Clone, Default)]
+pub enum PanicPolicy {
+    /// The panic is swallowed and the worker takes the next job.
+    #[default]
+    Ignore,
+    /// The worker thread is replaced by a new one with the same id, so no
+    /// thread local state survives the panic. It doesn't count as a
+    /// restart of the [`RestartPolicy`].
+    Restart,
+    /// The pool is shut down: the queue is closed, its jobs are dropped,
+    /// and every later submission fails.
+    AbortPool,
+    /// The callback is called, on the worker thread, with the payload, and
+    /// the worker takes the next job. It is a panic handler, as set with
+    /// [`WorkerPoolBuilder::panic_handler`], that runs after the one set
+    /// there, if any.
+    Callback(PanicHandler),
+}
+
+impl PanicPolicy {
+    /// Constructs a `PanicPolicy::Callback` calling f.
+    ///
+    /// **f**: Fn(&(dyn Any + Send)) - The panic callback. \
+    /// **returns**: the PanicPolicy.
+    pub fn callback<F>(f: F) -> PanicPolicy
+    where
+        F: Fn(&(dyn Any + Send)) + Send + Sync + 'static,
+    {
+        PanicPolicy::Callback(Arc::new(f))
+    }
+}
+
+impl std::fmt::Debug for PanicPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PanicPolicy::Ignore => write!(f, "Ignore"),
+            PanicPolicy::Restart => write!(f, "Restart"),
+            PanicPolicy::AbortPool => write!(f, "AbortPool"),
+            PanicPolicy::Callback(_) => write!(f, "Callback(..)"),
+        }
+    }
+}
+
+impl RestartPolicy {
+    // Checks whether a worker that was already restarted the given number
+    // of times can be restarted again.
+    fn allows(self, restarts: usize) -> bool {
+        match self {
+            RestartPolicy::Never => false,
+            RestartPolicy::Always => true,
+            RestartPolicy::UpToN(n) => restarts < n,
+        }
+    }
+}
+
+/// Defines what an idle worker does while it waits for a job. Spinning
+/// picks up new jobs sooner, at the cost of burning CPU while idle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IdleStrategy {
+    /// The worker parks right away and is woken up by the next job.
+    #[default]
+    Park,
+    /// The worker polls the queue `spins` times in a busy loop, then
+    /// `yields` times yielding its time slice, and then parks.
+    SpinThenPark { spins: u32, yields: u32 },
+}
+
+/// Defines the order workers take jobs in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QueueOrder {
+    /// Jobs are taken by priority and then in queueing order. Jobs with a
+    /// deadline are queued with the normal priority.
+    #[default]
+    Priority,
+    /// Jobs with a deadline are taken first, earliest deadline first, and
+    /// then the other jobs by priority.
+    EarliestDeadlineFirst,
+}
+
+/// Defines which job of a priority level workers take first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DispatchOrder {
+    /// The oldest job is taken first.
+    #[default]
+    Fifo,
+    /// The newest job is taken first, which keeps the data of recursive
+    /// workloads hot in the caches.
+    Lifo,
+}
+
+/// The priority of a job. Workers take higher priority jobs first, but a
+/// queued job is promoted one level for each aging period it waits, as set
+/// by [`WorkerPoolBuilder::priority_aging`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Priority {
+    /// Background work.
+    Low = 0,
+    /// The priority of jobs submitted with `execute`.
+    #[default]
+    Normal = 1,
+    /// Latency sensitive work.
+    High = 2,
+}
+
+// The default waiting time that promotes a queued job one priority level.
+const DEFAULT_PRIORITY_AGING: Duration = Duration::from_secs(1);
+
+// The number of chunks per worker used when splitting items into jobs,
+// so a slow chunk doesn't leave the other workers idle.
+const CHUNKS_PER_WORKER: usize = 4;
+
+// The length under which WorkerPool::sort doesn't split the slice, as
+// queueing the jobs would cost more than sorting it.
+const PARALLEL_SORT_MIN: usize = 4096;
+
+// How long a worker waiting for nested jobs blocks when there is no queued
+// job to run in the meantime.
+const HELP_PARK: Duration = Duration::from_micros(100);
+
+// The number of finished tracked jobs whose status the pool keeps, so the
+// table of statuses stays bounded.
+const FINISHED_STATUSES: usize = 1024;
+
+/// Errors returned when a job can't be submitted to a [`WorkerPool`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecuteError {
+    /// There is no worker left to receive the job.
+    Disconnected,
+    /// The bounded job queue is full.
+    QueueFull,
+    /// The bounded job queue stayed full until the timeout.
+    Timeout,
+}
+
+impl Display for ExecuteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExecuteError::Disconnected => write!(f, "no worker is left to receive the job"),
+            ExecuteError::QueueFull => write!(f, "the job queue is full"),
+            ExecuteError::Timeout => write!(f, "the job queue stayed full until the timeout"),
+        }
+    }
+}
+
+impl std::error::Error for ExecuteError {}
+
+/// A job the pool didn't accept, given back with the reason, so the caller
+/// can retry it later, reroute it or run it inline.
+pub struct Rejected<F> {
+    error: ExecuteError,
+    job: F,
+}
+
+impl<F> Rejected<F> {
+    /// Returns why the job was rejected.
+    pub fn error(&self) -> ExecuteError {
+        self.error
+    }
+
+    /// Gives back the rejected job.
+    pub fn into_job(self) -> F {
+        self.job
+    }
+}
+
+impl<F> std::fmt::Debug for Rejected<F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Rejected")
+            .field("error", &self.error)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F> Display for Rejected<F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl<F> std::error::Error for Rejected<F> {}
+
+impl<F> From<Rejected<F>> for ExecuteError {
+    fn from(rejected: Rejected<F>) -> ExecuteError {
+        rejected.error
+    }
+}
 
 /// Implements a continuous pool of rust threads thats doesn't stops
-/// unless it gets out of scope.
+/// unless it gets out of scope. When dropped, the pool lets the workers
+/// finish the queued jobs and then joins their threads.
+///
+/// Cloning the pool is cheap, and every clone shares the same workers and
+/// queue. The pool stops when its last clone is dropped.
+///
+/// ## Examples
 ///
+/// ```
+/// use workerpool_rs::pool::WorkerPool;
+/// use std::thread;
+///
+/// let pool = WorkerPool::new(2);
+/// let producer = pool.clone();
+/// thread::spawn(move || producer.submit(|| 2 + 2).unwrap().wait())
+///     .join()
+///     .unwrap()
+///     .unwrap();
+///
+/// assert_eq!(Ok(6), pool.submit(|| 3 + 3).unwrap().wait());
+/// ```
+#[derive(Clone)]
 pub struct WorkerPool {
-    workers: Vec<Worker>,
-    sender: mpsc::Sender<Job>,
+    shared: SharedState,
+    owner: Arc<Owner>,
+}
+
+// The parts of a pool owned by all its clones, which stops the pool when
+// the last clone is dropped.
+//
+// shared: SharedState - the state shared with the workers.\
+// scheduler: Mutex<Option<Scheduler>> - the timer, started on first use.\
+// supervisor: Mutex<Option<Supervisor>> - the autoscaler, if enabled.\
+// watchdog: Mutex<Option<Watcher>> - the watchdog, if enabled.\
+// timeouts: Mutex<Option<Scheduler>> - the timer cancelling the jobs out of
+// time, started on first use.\
+// results: ResultChannels - the result channels, by result type.
+struct Owner {
+    shared: SharedState,
+    scheduler: Mutex<Option<Scheduler>>,
+    supervisor: Mutex<Option<Supervisor>>,
+    watchdog: Mutex<Option<Watcher>>,
+    timeouts: Mutex<Option<Scheduler>>,
+    results: ResultChannels,
 }
 
 impl WorkerPool {
@@ -34,28 +381,162 @@ impl WorkerPool {
     /// ```
     /// use workerpool_rs::pool::WorkerPool;
     ///
-    /// let pool = WorkerPool::new(3);
+    /// let pool = WorkerPool::new(2);
     ///
-    /// assert_eq!("workers[] = (id: 0)(id: 1)(id: 2)", pool.to_string());
+    /// assert_eq!(
+    ///     "workers[] = (id: 0, idle, completed: 0)(id: 1, idle, completed: 0)",
+    ///     pool.to_string()
+    /// );
     /// ```
     pub fn new(size: usize) -> WorkerPool {
-        let (tx, rx) = mpsc::channel();
-        let mut workers = Vec::<Worker>::with_capacity(size);
-        let rec = Arc::new(Mutex::new(rx));
+        WorkerPoolBuilder::new().num_workers(size).build()
+    }
 
-        for id in 0..size {
-            workers.push(Worker::new(id, Arc::clone(&rec)));
-        }
+    /// Constructs a new WorkerPool with the settings of a config, as
+    /// [`PoolConfig::builder`] does.
+    ///
+    /// **config**: &PoolConfig - The settings of the pool. \
+    /// **returns**: a WorkerPool object.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bounds of the autoscaler fail [`PoolConfig::validate`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use workerpool_rs::config::PoolConfig;
+    /// use workerpool_rs::pool::WorkerPool;
+    ///
+    /// let config = PoolConfig {
+    ///     workers: Some(3),
+    ///     thread_name: Some("reports".to_string()),
+    ///     ..PoolConfig::default()
+    /// };
+    ///
+    /// let pool = WorkerPool::from_config(&config);
+    /// assert_eq!(3, pool.num_workers());
+    /// ```
+    pub fn from_config(config: &PoolConfig) -> WorkerPool {
+        config.builder().build()
+    }
 
-        WorkerPool {
-            workers,
-            sender: tx,
+    /// Constructs a new WorkerPool with one worker per available core, as
+    /// reported by `std::thread::available_parallelism`. If detection
+    /// fails, a single worker is spawned.
+    ///
+    /// **returns**: a WorkerPool object.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    ///
+    /// let pool = WorkerPool::auto();
+    /// assert_eq!(Ok(4), pool.submit(|| 2 + 2).unwrap().wait());
+    /// ```
+    pub fn auto() -> WorkerPool {
+        WorkerPool::new(default_num_workers())
+    }
+
+    /// Constructs a new WorkerPool of size x, whose job queue holds at most
+    /// `capacity` jobs. When the queue is full, `execute` blocks until a
+    /// worker takes a job, and `try_execute` fails immediately.
+    ///
+    /// **size**: usize - Is the number of workers in WorkerPool object. \
+    /// **capacity**: usize - Is the maximum number of queued jobs. \
+    /// **returns**: a WorkerPool object.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    ///
+    /// let pool = WorkerPool::with_queue_capacity(2, 16);
+    /// for _ in 0..100 {
+    ///     pool.execute(|| {}).unwrap();
+    /// }
+    /// pool.join();
+    /// ```
+    pub fn with_queue_capacity(size: usize, capacity: usize) -> WorkerPool {
+        WorkerPoolBuilder::new()
+            .num_workers(size)
+            .queue_capacity(capacity)
+            .build()
+    }
+
+    /// Constructs a new pool of size x whose workers each own a state built
+    /// by init. See [`StatefulPool`].
+    ///
+    /// **size**: usize - Is the number of workers in the pool. \
+    /// **init**: A Fn closure that builds the state of a worker. \
+    /// **returns**: a StatefulPool object.
+    pub fn with_state<S, I>(size: usize, init: I) -> StatefulPool<S>
+    where
+        S: 'static,
+        I: Fn() -> S + Send + Sync + 'static,
+    {
+        StatefulPool::new(WorkerPool::new(size), init)
+    }
+
+    // Spawns size workers that take their jobs from the shared queue. A
+    // threaded pool without workers can't run a job, so its queue is closed.
+    // When a worker can't be spawned, the ones already running are stopped.
+    fn with_shared(size: usize, shared: SharedState) -> Result<WorkerPool, Error> {
+        if size == 0 && shared.options.mode == ExecutionMode::Threaded {
+            shared.queue.close();
         }
+
+        let owner = Arc::new(Owner {
+            shared: Arc::clone(&shared),
+            scheduler: Mutex::new(None),
+            supervisor: Mutex::new(None),
+            watchdog: Mutex::new(None),
+            timeouts: Mutex::new(None),
+            results: ResultChannels::new(),
+        });
+        let _ = shared.owner.set(Arc::downgrade(&owner));
+        shared.resize(size)?;
+        Ok(WorkerPool { owner, shared })
+    }
+
+    /// Returns a handle to the pool running the calling job, so code deep
+    /// in a job can submit follow-up work without a reference threaded
+    /// through every call. It returns None outside of a job, and once
+    /// every other clone of the pool was dropped.
+    ///
+    /// **returns**: a clone of the pool running the caller, if any.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    /// use std::sync::mpsc;
+    ///
+    /// fn follow_up(tx: mpsc::Sender<&'static str>) {
+    ///     let pool = WorkerPool::current().expect("called from a job");
+    ///     pool.execute(move || tx.send("follow-up").unwrap()).unwrap();
+    /// }
+    ///
+    /// let pool = WorkerPool::new(2);
+    /// let (tx, rx) = mpsc::channel();
+    /// pool.execute(move || follow_up(tx)).unwrap();
+    ///
+    /// assert_eq!("follow-up", rx.recv().unwrap());
+    /// assert!(WorkerPool::current().is_none());
+    /// ```
+    pub fn current() -> Option<WorkerPool> {
+        let shared = CURRENT_SHARED.with(|current| current.borrow().as_ref()?.upgrade())?;
+        let owner = shared.owner.get()?.upgrade()?;
+        Some(WorkerPool { shared, owner })
     }
 
     /// Executes a job. The job is moved to closure, as this function is FnOnce. \
+    /// A closure capturing up to four words, such as a few `Arc`s or a
+    /// [`Job`], is queued without allocating. A larger one is boxed.
     ///
-    /// **f**: A FnOnce closure hosted by a Box smart pointer.
+    /// **f**: A FnOnce closure. \
+    /// **returns**: `Err(ExecuteError)` if the job can't be queued.
     /// ## Examples
     ///
     /// ```
@@ -76,98 +557,6319 @@ impl WorkerPool {
     ///     pool.execute(move || {
     ///         let tx = atx.lock().unwrap();
     ///         tx.send(1).unwrap();
-    ///     });
+    ///     }).unwrap();
     /// }
     ///
     /// let sum = rx.iter().take(njobs).sum();
     /// assert_eq!(njobs, sum);
     /// ```
-    pub fn execute<J>(&self, f: J)
+    pub fn execute<F>(&self, f: F) -> Result<(), ExecuteError>
     where
-        J: FnOnce() + Send + Sync + 'static,
+        F: FnOnce() + Send + 'static,
     {
-        let job = Box::new(f);
-        self.sender.send(job).expect("Cant send job");
+        self.shared.push(Task::new(f), Priority::Normal)
     }
-}
 
-// Implements Display for WorkerPool. This is usefull as we can able
-// to compare and make unit tests more easily.
-impl Display for WorkerPool {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut buffer = String::new();
-        for i in &self.workers {
-            buffer.push_str(&i.to_string());
-        }
-        write!(f, "workers[] = {}", buffer)
+    /// Executes a job that is already boxed. This is useful when jobs are
+    /// stored as trait objects before being submitted, as no extra
+    /// allocation is made.
+    ///
+    /// **job**: Job - A boxed FnOnce closure. \
+    /// **returns**: `Err(ExecuteError)` if the job can't be queued.
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::{Job, WorkerPool};
+    /// use std::sync::mpsc;
+    ///
+    /// let pool = WorkerPool::new(2);
+    /// let (tx, rx) = mpsc::channel();
+    ///
+    /// let jobs: Vec<Job> = (0..4)
+    ///     .map(|i| {
+    ///         let tx = tx.clone();
+    ///         Box::new(move || tx.send(i).unwrap()) as Job
+    ///     })
+    ///     .collect();
+    ///
+    /// for job in jobs {
+    ///     pool.execute_boxed(job).unwrap();
+    /// }
+    ///
+    /// assert_eq!(6, rx.iter().take(4).sum::<i32>());
+    /// ```
+    pub fn execute_boxed(&self, job: Job) -> Result<(), ExecuteError> {
+        self.shared.push(Task::from(job), Priority::Normal)
     }
-}
-
-// A structure that holds an id and thread handle.
-//
-// id: usize - An id for worker indentification.\
-// handle: JoinHandle<()> - a handle that has a working thread.
-struct Worker {
-    id: usize,
-    _handle: Handle,
-}
-
-impl Worker {
-    // Constructs a new Worker.
-    //
-    // id: usize - Worker identificator.
-    // handle: JoinHandle<()> - a thread handle.
-    fn new(id: usize, handle: JobReceiver) -> Worker {
-        let handle = thread::spawn(move || loop {
-            let job = match handle.lock().expect("Cant acquire lock").recv() {
-                Ok(data) => data,
-                Err(_) => continue,
-            };
 
-            job();
-        });
+    /// Executes a batch of jobs. With an unbounded queue the whole batch is
+    /// queued in a single operation, which is cheaper than executing the
+    /// jobs one by one. With a bounded queue the jobs are queued one by one,
+    /// blocking while the queue is full.
+    ///
+    /// **jobs**: `Vec<Job>` - The boxed jobs, queued in order. \
+    /// **returns**: `Err(ExecuteError)` if the jobs can't be queued, in which
+    /// case the jobs not queued yet are dropped.
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::{Job, WorkerPool};
+    /// use std::sync::mpsc;
+    ///
+    /// let pool = WorkerPool::new(4);
+    /// let (tx, rx) = mpsc::channel();
+    ///
+    /// let jobs = (0..1000)
+    ///     .map(|i| {
+    ///         let tx = tx.clone();
+    ///         Box::new(move || tx.send(i).unwrap()) as Job
+    ///     })
+    ///     .collect();
+    /// pool.execute_batch(jobs).unwrap();
+    ///
+    /// assert_eq!(499500, rx.iter().take(1000).sum::<i32>());
+    /// ```
+    pub fn execute_batch(&self, jobs: Vec<Job>) -> Result<(), ExecuteError> {
+        self.shared.push_batch(jobs, Priority::Normal)
+    }
 
-        Worker {
-            id,
-            _handle: handle,
+    /// Executes a batch of jobs, as [`execute_batch`](WorkerPool::execute_batch),
+    /// and blocks until every one of them finished, panicked or was
+    /// discarded. Completions are counted, so the batch may have any number
+    /// of jobs, whatever the number of workers. Other jobs of the pool are
+    /// not waited for. Called from a job of the pool, the worker runs
+    /// queued jobs while it waits, so the batch can't be stuck behind it.
+    ///
+    /// **jobs**: IntoIterator<Item = Job> - The boxed jobs, queued in order. \
+    /// **returns**: `Err(ExecuteError)` if the jobs can't be queued, once the
+    /// jobs queued before the error are over.
+    pub fn execute_all_and_wait<I>(&self, jobs: I) -> Result<(), ExecuteError>
+    where
+        I: IntoIterator<Item = Job>,
+    {
+        let group = WaitGroup::new();
+        let jobs = jobs
+            .into_iter()
+            .map(|job| {
+                group.add(1);
+                let guard = GroupGuard(group.clone());
+                Box::new(move || {
+                    let _guard = guard;
+                    job();
+                }) as Job
+            })
+            .collect();
+        let result = self.execute_batch(jobs);
+        if !self.help_until(|timeout| group.wait_timeout(timeout)) {
+            group.wait();
         }
+        result
     }
-}
 
-// Implements Display for Worker as this simplifys test writing.
-impl Display for Worker {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "(id: {})", self.id,)
+    /// Executes a job with the given priority.
+    ///
+    /// **priority**: Priority - The priority of the job. \
+    /// **f**: A FnOnce closure. \
+    /// **returns**: `Err(ExecuteError)` if the job can't be queued.
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::{Priority, WorkerPool};
+    /// use std::sync::mpsc;
+    ///
+    /// let pool = WorkerPool::new(1);
+    /// let (started_tx, started_rx) = mpsc::channel();
+    /// let (tx, rx) = mpsc::channel::<()>();
+    /// pool.execute(move || {
+    ///     started_tx.send(()).unwrap();
+    ///     rx.recv().unwrap();
+    /// }).unwrap();
+    /// started_rx.recv().unwrap();
+    ///
+    /// let (order_tx, order_rx) = mpsc::channel();
+    /// for (name, priority) in [("low", Priority::Low), ("high", Priority::High)] {
+    ///     let order_tx = order_tx.clone();
+    ///     pool.execute_with_priority(priority, move || order_tx.send(name).unwrap())
+    ///         .unwrap();
+    /// }
+    ///
+    /// tx.send(()).unwrap();
+    /// assert_eq!(vec!["high", "low"], order_rx.iter().take(2).collect::<Vec<_>>());
+    /// ```
+    pub fn execute_with_priority<F>(&self, priority: Priority, f: F) -> Result<(), ExecuteError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.shared.push(Task::new(f), priority)
     }
-}
-
-// This sections are the beginning of workerpool module unit tests.
-#[cfg(test)]
-mod unit_tests {
-    use super::*;
 
-    #[test]
-    fn worker_should_return_new() {
-        let (_, rx) = mpsc::channel();
-        let receiver = Arc::new(Mutex::new(rx));
-        let w = Worker::new(1, Arc::clone(&receiver));
-        assert_eq!("(id: 1)", w.to_string());
+    /// Executes a job that should start before a deadline. With
+    /// [`QueueOrder::EarliestDeadlineFirst`], the queued job closest to its
+    /// deadline is taken first. A job that starts after its deadline still
+    /// runs, but is first reported to the handler set with
+    /// [`WorkerPoolBuilder::deadline_miss_handler`].
+    ///
+    /// **deadline**: Instant - The time the job should start by. \
+    /// **f**: A FnOnce closure. \
+    /// **returns**: `Err(ExecuteError)` if the job can't be queued.
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::{QueueOrder, WorkerPoolBuilder};
+    /// use std::sync::mpsc;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let pool = WorkerPoolBuilder::new()
+    ///     .num_workers(1)
+    ///     .queue_order(QueueOrder::EarliestDeadlineFirst)
+    ///     .build();
+    /// let (started_tx, started_rx) = mpsc::channel();
+    /// let (tx, rx) = mpsc::channel::<()>();
+    /// pool.execute(move || {
+    ///     started_tx.send(()).unwrap();
+    ///     rx.recv().unwrap();
+    /// })
+    /// .unwrap();
+    /// started_rx.recv().unwrap();
+    ///
+    /// let now = Instant::now();
+    /// let (order_tx, order_rx) = mpsc::channel();
+    /// for (name, secs) in [("later", 20), ("sooner", 10)] {
+    ///     let order_tx = order_tx.clone();
+    ///     let deadline = now + Duration::from_secs(secs);
+    ///     pool.execute_with_deadline(deadline, move || order_tx.send(name).unwrap())
+    ///         .unwrap();
+    /// }
+    ///
+    /// tx.send(()).unwrap();
+    /// assert_eq!(vec!["sooner", "later"], order_rx.iter().take(2).collect::<Vec<_>>());
+    /// ```
+    pub fn execute_with_deadline<F>(&self, deadline: Instant, f: F) -> Result<(), ExecuteError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let handler = self.shared.options.miss_handler.clone();
+        let job = Task::new(move || {
+            let now = Instant::now();
+            if let Some(handler) = handler.filter(|_| now > deadline) {
+                handler(now - deadline);
+            }
+            f();
+        });
+        match self.shared.options.queue_order {
+            QueueOrder::Priority => self.shared.push(job, Priority::Normal),
+            QueueOrder::EarliestDeadlineFirst => self.shared.push_deadline(job, deadline),
+        }
     }
 
-    #[test]
-    fn workerpool_should_return_new() {
-        let expected = "workers[] = (id: 0)(id: 1)(id: 2)".to_string();
-        let pool = WorkerPool::new(3);
-        assert_eq!(expected.to_string(), pool.to_string());
+    /// Executes a job that never runs concurrently with, nor before, the
+    /// jobs previously executed with the same key. Jobs with different
+    /// keys still run in parallel, unless their keys hash to the same value.
+    ///
+    /// **key**: K - The key that orders the job. \
+    /// **f**: A FnOnce closure. \
+    /// **returns**: `Err(ExecuteError)` if the job can't be queued.
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let pool = WorkerPool::new(4);
+    /// let events = Arc::new(Mutex::new(Vec::new()));
+    ///
+    /// for i in 0..10 {
+    ///     let events = events.clone();
+    ///     pool.execute_keyed("account-1", move || {
+    ///         events.lock().unwrap().push(i);
+    ///     }).unwrap();
+    /// }
+    ///
+    /// pool.join();
+    /// assert_eq!((0..10).collect::<Vec<_>>(), *events.lock().unwrap());
+    /// ```
+    pub fn execute_keyed<K, F>(&self, key: K, f: F) -> Result<(), ExecuteError>
+    where
+        K: Hash,
+        F: FnOnce() + Send + 'static,
+    {
+        let lane = KeyedLanes::lane_id(&key);
+        self.shared.admit(1);
+        self.shared.pending.increment();
+        let result = match self.shared.keyed.enqueue(lane, into_job(f)) {
+            None => Ok(()),
+            Some(job) => {
+                let runner = KeyedRunner::new(Arc::clone(&self.shared), lane, job);
+                self.shared.dispatch(runner.into_task())
+            }
+        };
+        self.shared.settle(1, result)
     }
 
-    #[test]
-    fn workerpool_should_execute_job_succeed() {
-        let pool = WorkerPool::new(1);
-        for _ in 0..10000 {
-            pool.execute(|| {
-                let _sum = 3 + 1;
-            });
-        }
+    /// Executes a job in the concurrency group of a tag. A tag given a
+    /// limit with [`WorkerPoolBuilder::tag_limit`] never has more jobs
+    /// running at once, and its extra jobs wait, in order, without taking
+    /// a worker. Jobs with another tag run as with `execute`.
+    ///
+    /// **tag**: &str - The concurrency group of the job. \
+    /// **f**: A FnOnce closure. \
+    /// **returns**: `Err(ExecuteError)` if the job can't be queued.
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPoolBuilder;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    /// use std::thread;
+    /// use std::time::Duration;
+    ///
+    /// let pool = WorkerPoolBuilder::new()
+    ///     .num_workers(4)
+    ///     .tag_limit("io", 1)
+    ///     .build();
+    /// let running = Arc::new(AtomicUsize::new(0));
+    ///
+    /// for _ in 0..4 {
+    ///     let running = running.clone();
+    ///     pool.execute_tagged("io", move || {
+    ///         assert_eq!(0, running.fetch_add(1, Ordering::SeqCst));
+    ///         thread::sleep(Duration::from_millis(1));
+    ///         running.fetch_sub(1, Ordering::SeqCst);
+    ///     }).unwrap();
+    /// }
+    ///
+    /// pool.join();
+    /// ```
+    pub fn execute_tagged<F>(&self, tag: &str, f: F) -> Result<(), ExecuteError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        if !self.shared.tags.is_limited(tag) {
+            return self.execute(f);
+        }
+        self.shared.admit(1);
+        self.shared.pending.increment();
+        let result = match self.shared.tags.enqueue(tag, into_job(f)) {
+            None => Ok(()),
+            Some(job) => {
+                let runner = TaggedRunner::new(Arc::clone(&self.shared), tag, job);
+                self.shared.dispatch(runner.into_task())
+            }
+        };
+        self.shared.settle(1, result)
+    }
+
+    /// Executes a job that takes a weight from the budget set with
+    /// [`WorkerPoolBuilder::weight_budget`], such as its memory or
+    /// concurrency units, and gives it back when it finishes. The weight of
+    /// the jobs queued or running never exceeds the budget, and the jobs
+    /// that don't fit wait, in order, without taking a worker. A weight
+    /// above the budget is cut to it, so the job runs alone. Without a
+    /// budget, the job runs as with `execute`.
+    ///
+    /// **weight**: usize - The weight of the job. \
+    /// **f**: A FnOnce closure. \
+    /// **returns**: `Err(ExecuteError)` if the job can't be queued.
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPoolBuilder;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    /// use std::thread;
+    /// use std::time::Duration;
+    ///
+    /// let pool = WorkerPoolBuilder::new()
+    ///     .num_workers(4)
+    ///     .weight_budget(4)
+    ///     .build();
+    /// let used = Arc::new(AtomicUsize::new(0));
+    ///
+    /// for weight in [3, 2, 1, 2, 4, 1] {
+    ///     let used = used.clone();
+    ///     pool.execute_weighted(weight, move || {
+    ///         assert!(used.fetch_add(weight, Ordering::SeqCst) + weight <= 4);
+    ///         thread::sleep(Duration::from_millis(1));
+    ///         used.fetch_sub(weight, Ordering::SeqCst);
+    ///     }).unwrap();
+    /// }
+    ///
+    /// pool.join();
+    /// assert_eq!(0, pool.panic_count());
+    /// ```
+    pub fn execute_weighted<F>(&self, weight: usize, f: F) -> Result<(), ExecuteError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let Some(budget) = &self.shared.weights else {
+            return self.execute(f);
+        };
+        self.shared.admit(1);
+        self.shared.pending.increment();
+        let result = match budget.enqueue(weight, into_job(f)) {
+            None => Ok(()),
+            Some((weight, job)) => {
+                let runner = WeightedRunner::new(Arc::clone(&self.shared), weight, job);
+                self.shared.dispatch(runner.into_task())
+            }
+        };
+        self.shared.settle(1, result)
+    }
+
+    /// Executes a job with a label naming the kind of work it does. The
+    /// label is returned by [`current_label`] on the worker thread from the
+    /// start of the job until the next one starts, so the job itself, the
+    /// `on_complete` and `on_panic` observer callbacks and the panic handler
+    /// can tell the jobs of a shared pool apart. Dead letters carry it too.
+    ///
+    /// **label**: &'static str - The label of the job. \
+    /// **f**: A FnOnce closure. \
+    /// **returns**: `Err(ExecuteError)` if the job can't be queued.
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::{current_label, WorkerPoolBuilder};
+    /// use std::sync::mpsc;
+    /// use std::sync::Mutex;
+    ///
+    /// let (tx, rx) = mpsc::channel();
+    /// let tx = Mutex::new(tx);
+    /// let pool = WorkerPoolBuilder::new()
+    ///     .panic_handler(move |_| tx.lock().unwrap().send(current_label()).unwrap())
+    ///     .build();
+    ///
+    /// pool.execute_labeled("thumbnail", || panic!("bad image")).unwrap();
+    /// assert_eq!(Some("thumbnail"), rx.recv().unwrap());
+    /// ```
+    pub fn execute_labeled<F>(&self, label: &'static str, f: F) -> Result<(), ExecuteError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let counters = self.shared.label_counters(label);
+        let job_counters = Arc::clone(&counters);
+        let queued = Instant::now();
+        // The job may start before execute returns, so it's counted first.
+        counters.submit();
+        let job = Task::new(move || {
+            let started = Instant::now();
+            job_counters.start(started - queued);
+            let _timer = LabelTimer {
+                counters: job_counters,
+                started,
+            };
+            f();
+        });
+        let result = self.shared.push(job.labeled(label), Priority::Normal);
+        if result.is_err() {
+            counters.reject();
+        }
+        result
+    }
+
+    /// Executes a job without blocking. With a bounded queue, the job is
+    /// rejected if the queue is full, and given back in the error so it can
+    /// be retried, spilled elsewhere or run inline.
+    ///
+    /// **f**: A FnOnce closure. \
+    /// **returns**: `Err(Rejected)` giving the closure back, with
+    /// `ExecuteError::QueueFull` if the queue is full.
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::{ExecuteError, WorkerPool};
+    /// use std::sync::mpsc;
+    ///
+    /// let pool = WorkerPool::with_queue_capacity(1, 1);
+    /// let (started_tx, started_rx) = mpsc::channel();
+    /// let (tx, rx) = mpsc::channel::<()>();
+    ///
+    /// pool.try_execute(move || {
+    ///     started_tx.send(()).unwrap();
+    ///     rx.recv().unwrap();
+    /// }).unwrap();
+    /// started_rx.recv().unwrap();
+    ///
+    /// pool.try_execute(|| {}).unwrap();
+    /// let rejected = pool.try_execute(|| {}).unwrap_err();
+    /// assert_eq!(ExecuteError::QueueFull, rejected.error());
+    /// tx.send(()).unwrap();
+    ///
+    /// // Blocks until the job is queued.
+    /// pool.execute(rejected.into_job()).unwrap();
+    /// ```
+    pub fn try_execute<F>(&self, f: F) -> Result<(), Rejected<F>>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.shared
+            .push_until(None, POOL_PRODUCER, f, Priority::Normal)
+    }
+
+    /// Executes a job, blocking up to the timeout while a bounded queue is
+    /// full. Under a blocking [rate limit](WorkerPoolBuilder::rate_limit),
+    /// the timeout also bounds the wait for the limiter.
+    ///
+    /// **timeout**: Duration - The maximum time to wait for room. \
+    /// **f**: A FnOnce closure. \
+    /// **returns**: `Err(Rejected)` giving the closure back, with
+    /// `ExecuteError::Timeout` if the queue stayed full.
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::{ExecuteError, WorkerPool};
+    /// use std::sync::mpsc;
+    /// use std::time::Duration;
+    ///
+    /// let pool = WorkerPool::with_queue_capacity(1, 0);
+    /// let (tx, rx) = mpsc::channel::<()>();
+    /// pool.execute(move || rx.recv().unwrap()).unwrap();
+    ///
+    /// let (done_tx, done_rx) = mpsc::channel();
+    /// let job = move || done_tx.send("done").unwrap();
+    /// let timeout = Duration::from_millis(10);
+    /// let rejected = pool.try_execute_timeout(timeout, job).unwrap_err();
+    /// assert_eq!(ExecuteError::Timeout, rejected.error());
+    ///
+    /// // Runs the job inline instead.
+    /// rejected.into_job()();
+    /// assert_eq!(Ok("done"), done_rx.try_recv());
+    /// tx.send(()).unwrap();
+    /// ```
+    pub fn try_execute_timeout<F>(&self, timeout: Duration, f: F) -> Result<(), Rejected<F>>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let deadline = Instant::now() + timeout;
+        self.shared
+            .push_until(Some(deadline), POOL_PRODUCER, f, Priority::Normal)
+    }
+
+    /// Executes a job and sends the value it returns to a channel. The
+    /// sender is moved into the job, so there is no need to clone it into
+    /// the closure. The value is dropped if the receiver is gone.
+    ///
+    /// **tx**: `Sender<R>` - The channel that receives the value. \
+    /// **f**: A FnOnce closure that returns a value. \
+    /// **returns**: `Err(ExecuteError)` if the job can't be queued.
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    /// use std::sync::mpsc;
+    ///
+    /// let pool = WorkerPool::new(4);
+    /// let (tx, rx) = mpsc::channel();
+    ///
+    /// for i in 0..8 {
+    ///     pool.execute_to(tx.clone(), move || i * 2).unwrap();
+    /// }
+    ///
+    /// assert_eq!(56, rx.iter().take(8).sum::<i32>());
+    /// ```
+    pub fn execute_to<F, R>(&self, tx: mpsc::Sender<R>, f: F) -> Result<(), ExecuteError>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        self.execute(move || {
+            let _ = tx.send(f());
+        })
+    }
+
+    /// Executes a job and sends the value it returns to the result channel
+    /// of its type, built into the pool, so there is no sender to clone
+    /// into the closure. The values are received with
+    /// [`results`](WorkerPool::results). The channel is picked by the exact
+    /// type of the value, so an unsuffixed integer goes to the `i32` one.
+    ///
+    /// **f**: A FnOnce closure that returns a value. \
+    /// **returns**: `Err(ExecuteError)` if the job can't be queued.
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    ///
+    /// let pool = WorkerPool::new(4);
+    /// for word in ["one", "two", "three"] {
+    ///     pool.execute_returning(move || word.len()).unwrap();
+    /// }
+    ///
+    /// let mut lengths: Vec<usize> = pool.results().iter().take(3).collect();
+    /// lengths.sort();
+    /// assert_eq!(vec![3, 3, 5], lengths);
+    /// ```
+    pub fn execute_returning<F, R>(&self, f: F) -> Result<(), ExecuteError>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        self.execute_to(self.owner.results.sender(), f)
+    }
+
+    /// Returns a receiver of the values of type T returned by the jobs
+    /// executed with [`execute_returning`](WorkerPool::execute_returning).
+    /// Every call returns a receiver of the same channel, and each value is
+    /// received once. The channel is disconnected once every clone of the
+    /// pool is dropped and its jobs returned.
+    ///
+    /// **returns**: the `Results<T>` receiver.
+    pub fn results<T: Send + 'static>(&self) -> Results<T> {
+        self.owner.results.receiver()
+    }
+
+    /// Executes a job as part of a group. The job is added to the group
+    /// when submitted and marked done when it finishes, panics or is
+    /// discarded, so [`WaitGroup::wait`] returns once every job of the
+    /// group is over, however many workers the pool has.
+    ///
+    /// **group**: &WaitGroup - The group of the job. \
+    /// **f**: A FnOnce closure. \
+    /// **returns**: `Err(ExecuteError)` if the job can't be queued, in which
+    /// case it isn't left in the group.
+    pub fn execute_in_group<F>(&self, group: &WaitGroup, f: F) -> Result<(), ExecuteError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        group.add(1);
+        let guard = GroupGuard(group.clone());
+        self.execute(move || {
+            let _guard = guard;
+            f();
+        })
+    }
+
+    /// Submits a job and returns a handle to get back the value it
+    /// returns.
+    ///
+    /// **f**: A FnOnce closure that returns a value. \
+    /// **returns**: a JobHandle, or `Err(ExecuteError)` if the job can't be
+    /// queued.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    ///
+    /// let pool = WorkerPool::new(4);
+    /// let handles: Vec<_> = (0..8u64)
+    ///     .map(|i| pool.submit(move || i * i).unwrap())
+    ///     .collect();
+    ///
+    /// let sum: u64 = handles.into_iter().map(|h| h.wait().unwrap()).sum();
+    /// assert_eq!(140, sum);
+    /// ```
+    pub fn submit<F, R>(&self, f: F) -> Result<JobHandle<R>, ExecuteError>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        self.execute_cancellable(move |_| f())
+    }
+
+    /// Submits a fallible job that is attempted again, after the backoff
+    /// of the policy, each time it returns an error. Retries wait in the
+    /// pool timer, not on a worker. Cancelling the handle stops the later
+    /// attempts. A job whose every attempt failed is also sent to the
+    /// [`dead_letter`](WorkerPoolBuilder::dead_letter) channel.
+    ///
+    /// **policy**: RetryPolicy - The attempts and backoff of the job. \
+    /// **f**: A FnMut closure that returns a Result. \
+    /// **returns**: a JobHandle delivering the first success, or
+    /// `Exhausted` with the last error once every attempt failed, or
+    /// `Err(ExecuteError)` if the job can't be queued.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    /// use workerpool_rs::retry::{Backoff, RetryPolicy};
+    /// use std::time::Duration;
+    ///
+    /// let pool = WorkerPool::new(2);
+    /// let policy = RetryPolicy {
+    ///     backoff: Backoff::Fixed(Duration::from_millis(1)),
+    ///     ..RetryPolicy::new(3)
+    /// };
+    ///
+    /// let mut calls = 0;
+    /// let handle = pool
+    ///     .submit_retry(policy, move || {
+    ///         calls += 1;
+    ///         if calls < 3 { Err("not yet") } else { Ok(calls) }
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!(Ok(Ok(3)), handle.wait());
+    ///
+    /// let handle = pool.submit_retry(policy, || Err::<(), _>("never")).unwrap();
+    /// let exhausted = handle.wait().unwrap().unwrap_err();
+    /// assert_eq!((3, "never"), (exhausted.attempts, exhausted.error));
+    /// ```
+    pub fn submit_retry<F, T, E>(
+        &self,
+        policy: RetryPolicy,
+        f: F,
+    ) -> Result<JobHandle<Result<T, Exhausted<E>>>, ExecuteError>
+    where
+        F: FnMut() -> Result<T, E> + Send + 'static,
+        T: Send + 'static,
+        E: std::fmt::Debug + Send + 'static,
+    {
+        let (tx, handle) = JobHandle::new();
+        let retry = Retry {
+            id: JobId::new(self.shared.next_job.fetch_add(1, Ordering::Relaxed)),
+            started: None,
+            owner: Arc::downgrade(&self.owner),
+            policy,
+            attempts: 0,
+            token: handle.token(),
+            f,
+            tx,
+        };
+        self.execute(move || retry.attempt())?;
+        Ok(handle)
+    }
+
+    /// Submits a job and returns a future resolving to the value it
+    /// returns, so async code can await a blocking job without blocking
+    /// its executor. Dropping the future before the job starts cancels it.
+    /// See [`JobFuture`](crate::future::JobFuture).
+    ///
+    /// **f**: A FnOnce closure that returns a value. \
+    /// **returns**: a JobFuture, or `Err(ExecuteError)` if the job can't be
+    /// queued.
+    #[cfg(feature = "futures")]
+    pub fn submit_async<F, R>(&self, f: F) -> Result<crate::future::JobFuture<R>, ExecuteError>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (completer, future) = crate::future::JobFuture::new();
+        let token = future.token();
+        self.execute(move || {
+            if !token.is_cancelled() {
+                completer.complete(f());
+            }
+        })?;
+        Ok(future)
+    }
+
+    /// Submits one job per item, mapping it with f, and returns a stream of
+    /// the values in the order they complete. See
+    /// [`JobStream`](crate::future::JobStream).
+    ///
+    /// **iter**: I - The items to map. \
+    /// **f**: A Fn closure shared by the jobs. \
+    /// **returns**: a JobStream, or `Err(ExecuteError)` if the jobs can't be
+    /// queued.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    /// use std::future::Future;
+    /// use std::pin::pin;
+    /// use std::sync::Arc;
+    /// use std::task::{Context, Poll, Wake};
+    /// use std::thread::{self, Thread};
+    ///
+    /// struct Unparker(Thread);
+    ///
+    /// impl Wake for Unparker {
+    ///     fn wake(self: Arc<Self>) {
+    ///         self.0.unpark();
+    ///     }
+    /// }
+    ///
+    /// fn block_on<F: Future>(future: F) -> F::Output {
+    ///     let mut future = pin!(future);
+    ///     let waker = Arc::new(Unparker(thread::current())).into();
+    ///     let mut cx = Context::from_waker(&waker);
+    ///     loop {
+    ///         match future.as_mut().poll(&mut cx) {
+    ///             Poll::Ready(output) => return output,
+    ///             Poll::Pending => thread::park(),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let pool = WorkerPool::new(4);
+    /// let mut stream = pool.submit_stream(1..=10, |i| i * i).unwrap();
+    ///
+    /// let sum = block_on(async {
+    ///     let mut sum = 0;
+    ///     while let Some(square) = stream.recv().await {
+    ///         sum += square;
+    ///     }
+    ///     sum
+    /// });
+    /// assert_eq!(385, sum);
+    /// ```
+    #[cfg(feature = "futures")]
+    pub fn submit_stream<I, F, R>(
+        &self,
+        iter: I,
+        f: F,
+    ) -> Result<crate::future::JobStream<R>, ExecuteError>
+    where
+        I: IntoIterator,
+        I::Item: Send + 'static,
+        F: Fn(I::Item) -> R + Send + Sync + 'static,
+        R: Send + 'static,
+    {
+        let items: Vec<_> = iter.into_iter().collect();
+        let (sender, stream) = crate::future::JobStream::new(items.len());
+        let f = Arc::new(f);
+        let jobs = items
+            .into_iter()
+            .map(|item| {
+                let f = Arc::clone(&f);
+                let job = sender.job();
+                Box::new(move || job.send(f(item))) as Job
+            })
+            .collect();
+        self.execute_batch(jobs)?;
+        Ok(stream)
+    }
+
+    /// Submits a job that receives a cancellation token, and returns a
+    /// handle to get back its value or cancel it. A job cancelled while
+    /// still queued never starts; a running job should poll the token and
+    /// return early.
+    ///
+    /// **f**: A FnOnce closure that takes a `&CancellationToken` and
+    /// returns a value. \
+    /// **returns**: a JobHandle, or `Err(ExecuteError)` if the job can't be
+    /// queued.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    /// use std::thread;
+    /// use std::time::Duration;
+    ///
+    /// let pool = WorkerPool::new(1);
+    /// let handle = pool
+    ///     .execute_cancellable(|token| {
+    ///         let mut rounds = 0;
+    ///         while !token.is_cancelled() {
+    ///             rounds += 1;
+    ///             thread::sleep(Duration::from_millis(1));
+    ///         }
+    ///         rounds
+    ///     })
+    ///     .unwrap();
+    ///
+    /// thread::sleep(Duration::from_millis(10));
+    /// handle.cancel();
+    /// assert!(handle.wait().is_ok());
+    /// ```
+    pub fn execute_cancellable<F, R>(&self, f: F) -> Result<JobHandle<R>, ExecuteError>
+    where
+        F: FnOnce(&CancellationToken) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        self.execute_context(None, move |ctx| f(ctx.token()))
+    }
+
+    /// Submits a job that receives a [`JobContext`], and returns a handle
+    /// to get back its value or cancel it. Through the context, the job
+    /// knows the worker running it, its id, and whether it was cancelled.
+    ///
+    /// **f**: A FnOnce closure that takes a `&JobContext` and returns a
+    /// value. \
+    /// **returns**: a JobHandle, or `Err(ExecuteError)` if the job can't be
+    /// queued.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    ///
+    /// let pool = WorkerPool::new(1);
+    /// let first = pool.execute_ctx(|ctx| ctx.job_id()).unwrap().wait().unwrap();
+    /// let second = pool.execute_ctx(|ctx| ctx.job_id()).unwrap().wait().unwrap();
+    ///
+    /// assert_ne!(first, second);
+    /// assert_eq!(Ok(0), pool.execute_ctx(|ctx| ctx.worker_id()).unwrap().wait());
+    /// ```
+    pub fn execute_ctx<F, R>(&self, f: F) -> Result<JobHandle<R>, ExecuteError>
+    where
+        F: FnOnce(&JobContext) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        self.execute_context(None, f)
+    }
+
+    /// Submits a job that receives a [`JobContext`] carrying a deadline.
+    /// The deadline is only informative: the job should check
+    /// [`JobContext::remaining`] and return early once it is zero.
+    ///
+    /// **deadline**: Instant - When the job should be done. \
+    /// **f**: A FnOnce closure that takes a `&JobContext` and returns a
+    /// value. \
+    /// **returns**: a JobHandle, or `Err(ExecuteError)` if the job can't be
+    /// queued.
+    pub fn execute_ctx_with_deadline<F, R>(
+        &self,
+        deadline: Instant,
+        f: F,
+    ) -> Result<JobHandle<R>, ExecuteError>
+    where
+        F: FnOnce(&JobContext) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        self.execute_context(Some(deadline), f)
+    }
+
+    /// Submits a job that receives a cancellation token, which the pool
+    /// cancels once the job has run for longer than timeout. Enforcement
+    /// is cooperative: a thread can't be stopped from the outside, so the
+    /// job must poll the token, as with [`CancellationToken::check`], and
+    /// return early. The handle of a job out of time returns
+    /// `Err(JobError::Timeout)`, whatever the job returned.
+    ///
+    /// **timeout**: Duration - The longest time the job may run, from its
+    /// start. \
+    /// **f**: A FnOnce closure that takes a `&CancellationToken` and
+    /// returns a value. \
+    /// **returns**: a JobHandle, or `Err(ExecuteError)` if the job can't be
+    /// queued.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::handle::JobError;
+    /// use workerpool_rs::pool::WorkerPool;
+    /// use std::thread;
+    /// use std::time::Duration;
+    ///
+    /// let pool = WorkerPool::new(1);
+    /// let handle = pool
+    ///     .execute_with_timeout(Duration::from_millis(10), |token| -> Result<(), JobError> {
+    ///         loop {
+    ///             token.check()?;
+    ///             thread::sleep(Duration::from_millis(1));
+    ///         }
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!(Err(JobError::Timeout), handle.wait());
+    ///
+    /// let handle = pool
+    ///     .execute_with_timeout(Duration::from_secs(60), |_| 6 * 7)
+    ///     .unwrap();
+    /// assert_eq!(Ok(42), handle.wait());
+    /// ```
+    pub fn execute_with_timeout<F, R>(
+        &self,
+        timeout: Duration,
+        f: F,
+    ) -> Result<JobHandle<R>, ExecuteError>
+    where
+        F: FnOnce(&CancellationToken) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (tx, handle) = JobHandle::new();
+        let token = handle.token();
+        let owner = Arc::downgrade(&self.owner);
+        self.execute(move || {
+            if token.is_cancelled() {
+                return;
+            }
+            let expired = token.clone();
+            let timer = owner.upgrade().map(|owner| {
+                owner.schedule_timeout(Instant::now() + timeout, move || expired.cancel())
+            });
+            let value = f(&token);
+            if timer.is_some_and(|timer| !timer.cancel()) {
+                tx.fail(JobError::Timeout);
+            } else {
+                let _ = tx.send(value);
+            }
+        })?;
+        Ok(handle)
+    }
+
+    // Submits a job with a fresh id and context, skipping it if it is
+    // cancelled before it starts.
+    fn execute_context<F, R>(
+        &self,
+        deadline: Option<Instant>,
+        f: F,
+    ) -> Result<JobHandle<R>, ExecuteError>
+    where
+        F: FnOnce(&JobContext) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (tx, handle) = JobHandle::new();
+        let token = handle.token();
+        let id = JobId::new(self.shared.next_job.fetch_add(1, Ordering::Relaxed));
+        let progress = self.shared.options.progress.clone();
+        self.execute(move || {
+            if !token.is_cancelled() {
+                set_current_job(id, 1, Instant::now());
+                let worker = CURRENT_WORKER.with(Cell::get).map_or(0, |(_, id)| id);
+                let ctx = JobContext::new(worker, id, deadline, token, progress);
+                let _ = tx.send(f(&ctx));
+            }
+        })?;
+        Ok(handle)
+    }
+
+    /// Executes a job and returns its id, which can be given to
+    /// [`status`](WorkerPool::status) to follow the job or to
+    /// [`cancel`](WorkerPool::cancel) to cancel it. The pool keeps the
+    /// status of every unfinished tracked job, and of the last 1024 that
+    /// finished. Older statuses are forgotten, as are the ones given to
+    /// [`forget`](WorkerPool::forget).
+    ///
+    /// **f**: A FnOnce closure. \
+    /// **returns**: the JobId, or `Err(ExecuteError)` if the job can't be
+    /// queued.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::context::JobStatus;
+    /// use workerpool_rs::pool::WorkerPool;
+    /// use std::sync::mpsc;
+    ///
+    /// let pool = WorkerPool::new(1);
+    /// let (started_tx, started_rx) = mpsc::channel();
+    /// let (tx, rx) = mpsc::channel::<()>();
+    /// let running = pool
+    ///     .submit_tracked(move || {
+    ///         started_tx.send(()).unwrap();
+    ///         rx.recv().unwrap();
+    ///     })
+    ///     .unwrap();
+    /// started_rx.recv().unwrap();
+    ///
+    /// let queued = pool.submit_tracked(|| {}).unwrap();
+    /// assert_eq!(Some(JobStatus::Running), pool.status(running));
+    /// assert_eq!(Some(JobStatus::Queued), pool.status(queued));
+    ///
+    /// tx.send(()).unwrap();
+    /// pool.join();
+    /// assert_eq!(Some(JobStatus::Completed), pool.status(running));
+    /// assert_eq!(Some(JobStatus::Completed), pool.status(queued));
+    /// ```
+    pub fn submit_tracked<F>(&self, f: F) -> Result<JobId, ExecuteError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let id = JobId::new(self.shared.next_job.fetch_add(1, Ordering::Relaxed));
+        let statuses = Arc::clone(&self.shared.statuses);
+        self.set_status(id, JobStatus::Queued);
+        let result = self.execute(move || {
+            let run = TrackedRun { statuses, id };
+            if run.start() {
+                set_current_job(id, 1, Instant::now());
+                f();
+            }
+        });
+        if result.is_err() {
+            self.statuses().remove(id);
+        }
+        result.map(|()| id)
+    }
+
+    /// Returns the status of a job submitted with
+    /// [`submit_tracked`](WorkerPool::submit_tracked).
+    ///
+    /// **id**: JobId - The id of the job. \
+    /// **returns**: the status, or None if the id wasn't given by this pool
+    /// or its status was forgotten.
+    pub fn status(&self, id: JobId) -> Option<JobStatus> {
+        self.statuses().get(id)
+    }
+
+    /// Forgets the status of a finished tracked job, before it would be
+    /// forgotten with the older ones. The status of an unfinished job is
+    /// kept, as the pool needs it to run or cancel the job.
+    ///
+    /// **id**: JobId - The id of the job. \
+    /// **returns**: true if the status was forgotten.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::context::JobStatus;
+    /// use workerpool_rs::pool::WorkerPool;
+    ///
+    /// let pool = WorkerPool::new(1);
+    /// let id = pool.submit_tracked(|| {}).unwrap();
+    /// pool.join();
+    ///
+    /// assert_eq!(Some(JobStatus::Completed), pool.status(id));
+    /// assert!(pool.forget(id));
+    /// assert_eq!(None, pool.status(id));
+    /// ```
+    pub fn forget(&self, id: JobId) -> bool {
+        let mut statuses = self.statuses();
+        match statuses.get(id) {
+            Some(status) if status.is_finished() => statuses.remove(id).is_some(),
+            _ => false,
+        }
+    }
+
+    /// Cancels a tracked job that is still queued, so it never starts.
+    ///
+    /// **id**: JobId - The id of the job. \
+    /// **returns**: true if the job was cancelled by this call.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::context::JobStatus;
+    /// use workerpool_rs::pool::WorkerPool;
+    /// use std::sync::mpsc;
+    ///
+    /// let pool = WorkerPool::new(1);
+    /// let (started_tx, started_rx) = mpsc::channel();
+    /// let (tx, rx) = mpsc::channel::<()>();
+    /// pool.execute(move || {
+    ///     started_tx.send(()).unwrap();
+    ///     rx.recv().unwrap();
+    /// })
+    /// .unwrap();
+    /// started_rx.recv().unwrap();
+    ///
+    /// let id = pool.submit_tracked(|| unreachable!("the job was cancelled")).unwrap();
+    /// assert!(pool.cancel(id));
+    /// tx.send(()).unwrap();
+    /// pool.join();
+    ///
+    /// assert_eq!(Some(JobStatus::Cancelled), pool.status(id));
+    /// ```
+    pub fn cancel(&self, id: JobId) -> bool {
+        let mut statuses = self.statuses();
+        if statuses.get(id) != Some(JobStatus::Queued) {
+            return false;
+        }
+        statuses.set(id, JobStatus::Cancelled);
+        true
+    }
+
+    // Records the status of a tracked job.
+    fn set_status(&self, id: JobId, status: JobStatus) {
+        self.statuses().set(id, status);
+    }
+
+    // Locks the statuses of the tracked jobs.
+    fn statuses(&self) -> std::sync::MutexGuard<'_, Statuses> {
+        self.shared
+            .statuses
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns the number of jobs that panicked. A panicking job doesn't
+    /// kill its worker, which keeps taking jobs from the queue.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    ///
+    /// let pool = WorkerPool::new(1);
+    /// pool.execute(|| panic!("job failed")).unwrap();
+    /// let handle = pool.submit(|| 1).unwrap();
+    ///
+    /// assert_eq!(Ok(1), handle.wait());
+    /// assert_eq!(1, pool.panic_count());
+    /// ```
+    pub fn panic_count(&self) -> usize {
+        self.shared.panics.load(Ordering::SeqCst)
+    }
+
+    /// Returns the number of jobs waiting in the queue.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    ///
+    /// use std::sync::mpsc;
+    ///
+    /// let pool = WorkerPool::new(1);
+    /// let (started_tx, started_rx) = mpsc::channel();
+    /// let (tx, rx) = mpsc::channel::<()>();
+    ///
+    /// pool.execute(move || {
+    ///     started_tx.send(()).unwrap();
+    ///     rx.recv().unwrap();
+    /// }).unwrap();
+    /// started_rx.recv().unwrap();
+    /// pool.execute(|| {}).unwrap();
+    ///
+    /// assert_eq!(1, pool.queued_jobs());
+    /// tx.send(()).unwrap();
+    /// ```
+    pub fn queued_jobs(&self) -> usize {
+        self.shared.queue.len()
+    }
+
+    /// Returns the number of jobs being executed right now.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    /// use std::sync::mpsc;
+    ///
+    /// let pool = WorkerPool::new(2);
+    /// let (started_tx, started_rx) = mpsc::channel();
+    /// let (tx, rx) = mpsc::channel::<()>();
+    ///
+    /// pool.execute(move || {
+    ///     started_tx.send(()).unwrap();
+    ///     rx.recv().unwrap();
+    /// }).unwrap();
+    /// started_rx.recv().unwrap();
+    ///
+    /// assert_eq!(1, pool.active_jobs());
+    /// tx.send(()).unwrap();
+    /// ```
+    pub fn active_jobs(&self) -> usize {
+        self.shared.active.load(Ordering::SeqCst)
+    }
+
+    /// Returns the number of jobs that finished, including the ones that
+    /// panicked.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    ///
+    /// let pool = WorkerPool::new(2);
+    /// for _ in 0..10 {
+    ///     pool.execute(|| {}).unwrap();
+    /// }
+    ///
+    /// pool.join();
+    /// assert_eq!(10, pool.completed_jobs());
+    /// ```
+    pub fn completed_jobs(&self) -> usize {
+        self.shared.completed.load(Ordering::SeqCst)
+    }
+
+    /// Returns a snapshot of the pool counters.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    ///
+    /// let pool = WorkerPool::new(2);
+    /// for _ in 0..10 {
+    ///     pool.execute(|| {}).unwrap();
+    /// }
+    /// pool.execute(|| panic!("job failed")).unwrap();
+    /// pool.join();
+    ///
+    /// let stats = pool.stats();
+    /// assert_eq!(11, stats.submitted);
+    /// assert_eq!(11, stats.completed);
+    /// assert_eq!(1, stats.panicked);
+    /// assert_eq!(0, stats.queued);
+    /// assert_eq!(2, stats.workers.len());
+    /// ```
+    pub fn stats(&self) -> PoolStats {
+        let shared = &self.shared;
+        let workers = shared
+            .workers()
+            .active
+            .iter()
+            .map(|worker| WorkerHeartbeat {
+                id: worker.id,
+                busy: worker.state.busy.load(Ordering::SeqCst),
+                last_beat: *worker
+                    .state
+                    .heartbeat
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner),
+            })
+            .collect();
+        let completed = shared.completed.load(Ordering::SeqCst);
+        let execution = Duration::from_nanos(shared.execution.load(Ordering::Relaxed));
+        let (taken, waited, wait_percentiles) = shared.queue.waited();
+        PoolStats {
+            submitted: shared.submitted.load(Ordering::SeqCst),
+            completed,
+            panicked: shared.panics.load(Ordering::SeqCst),
+            queued: shared.queue.len(),
+            busy_workers: shared.active.load(Ordering::SeqCst),
+            average_wait: stats::average(waited, taken),
+            average_execution: stats::average(execution, completed as u64),
+            wait_percentiles,
+            execution_percentiles: shared.execution_histogram.percentiles(),
+            workers,
+        }
+    }
+
+    /// Returns a snapshot of the counters of the jobs of
+    /// [`execute_labeled`](WorkerPool::execute_labeled), per label. The
+    /// waiting time of a labeled job runs from its submission to its start.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    ///
+    /// let pool = WorkerPool::new(2);
+    /// for _ in 0..3 {
+    ///     pool.execute_labeled("thumbnail", || {}).unwrap();
+    /// }
+    /// pool.execute_labeled("upload", || panic!("network down")).unwrap();
+    /// pool.join();
+    ///
+    /// let stats = pool.stats_by_label();
+    /// assert_eq!(3, stats["thumbnail"].completed);
+    /// assert_eq!(1, stats["upload"].panicked);
+    /// ```
+    pub fn stats_by_label(&self) -> HashMap<&'static str, LabelStats> {
+        let labels = self
+            .shared
+            .labels
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        labels
+            .iter()
+            .map(|(label, counters)| (*label, counters.snapshot()))
+            .collect()
+    }
+
+    /// Returns the number of workers of the pool.
+    pub fn num_workers(&self) -> usize {
+        self.shared.workers().active.len()
+    }
+
+    /// Returns the number of worker threads alive. It is lower than
+    /// [`num_workers`](WorkerPool::num_workers) while workers are stopped
+    /// by the idle timeout.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPoolBuilder;
+    /// use std::thread;
+    /// use std::time::Duration;
+    ///
+    /// let pool = WorkerPoolBuilder::new()
+    ///     .num_workers(2)
+    ///     .idle_timeout(Duration::from_millis(10))
+    ///     .build();
+    /// while pool.live_workers() > 0 {
+    ///     thread::sleep(Duration::from_millis(5));
+    /// }
+    ///
+    /// assert_eq!(Ok(4), pool.submit(|| 2 * 2).unwrap().wait());
+    /// assert_eq!(2, pool.num_workers());
+    /// ```
+    pub fn live_workers(&self) -> usize {
+        self.shared.live.load(Ordering::SeqCst)
+    }
+
+    /// Returns the ids of the workers marked as suspect by the watchdog, as
+    /// they run a job reported as stuck. See [`Watchdog::mark_suspect`].
+    pub fn suspect_workers(&self) -> Vec<usize> {
+        let workers = self.shared.workers();
+        workers
+            .active
+            .iter()
+            .filter(|worker| worker.state.suspect.load(Ordering::SeqCst))
+            .map(|worker| worker.id)
+            .collect()
+    }
+
+    /// Resizes the pool to n workers. Growing spawns the missing workers
+    /// right away. Shrinking retires the workers with the highest ids,
+    /// which leave once their current job is done, so the remaining
+    /// workers keep their ids. With an autoscaler, the size only lasts
+    /// until its next decision.
+    ///
+    /// **n**: usize - The new number of workers.
+    ///
+    /// # Panics
+    ///
+    /// Panics if n is zero, as a pool without workers can't run its jobs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    ///
+    /// let pool = WorkerPool::new(2);
+    /// pool.set_workers(4);
+    /// assert_eq!(4, pool.num_workers());
+    ///
+    /// pool.set_workers(1);
+    /// assert_eq!(1, pool.num_workers());
+    /// assert_eq!(Ok(42), pool.submit(|| 42).unwrap().wait());
+    /// ```
+    pub fn set_workers(&self, n: usize) {
+        self.try_set_workers(n).expect("Cant spawn worker");
+    }
+
+    /// Resizes the pool as [`set_workers`](WorkerPool::set_workers), but
+    /// returns an error instead of panicking when a worker thread can't be
+    /// spawned. The workers spawned before the failure are kept.
+    ///
+    /// **n**: usize - The new number of workers. \
+    /// **returns**: `Err(Error::Spawn)` if a worker thread couldn't be spawned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if n is zero, as a pool without workers can't run its jobs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    ///
+    /// let pool = WorkerPool::new(1);
+    /// pool.try_set_workers(3).unwrap();
+    /// assert_eq!(3, pool.num_workers());
+    /// ```
+    pub fn try_set_workers(&self, n: usize) -> Result<(), Error> {
+        assert!(n > 0, "a pool needs at least one worker");
+        self.shared.resize(n)?;
+        Ok(())
+    }
+
+    /// Blocks the current thread until the job queue is drained and every
+    /// in-flight job has finished. The pool can still be used afterwards.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// let pool = WorkerPool::new(4);
+    /// let counter = Arc::new(AtomicUsize::new(0));
+    ///
+    /// for _ in 0..100 {
+    ///     let counter = counter.clone();
+    ///     pool.execute(move || {
+    ///         counter.fetch_add(1, Ordering::Relaxed);
+    ///     }).unwrap();
+    /// }
+    ///
+    /// pool.join();
+    /// assert_eq!(100, counter.load(Ordering::SeqCst));
+    /// ```
+    pub fn join(&self) {
+        self.shared.pending.wait_zero();
+    }
+
+    /// Blocks the current thread until the pool is idle, with no queued
+    /// nor running job, as [`join`](WorkerPool::join). Jobs scheduled for
+    /// later don't count until they are due.
+    pub fn wait_idle(&self) {
+        self.join();
+    }
+
+    /// Blocks the current thread until the pool is idle or the timeout
+    /// expires. The pool can still be used afterwards, whatever the result.
+    ///
+    /// **timeout**: Duration - The maximum time to wait. \
+    /// **returns**: true if the pool became idle before the timeout.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    /// use std::sync::mpsc;
+    /// use std::time::Duration;
+    ///
+    /// let pool = WorkerPool::new(1);
+    /// let (tx, rx) = mpsc::channel::<()>();
+    /// pool.execute(move || rx.recv().unwrap()).unwrap();
+    ///
+    /// assert!(!pool.wait_idle_timeout(Duration::from_millis(10)));
+    /// tx.send(()).unwrap();
+    /// assert!(pool.wait_idle_timeout(Duration::from_secs(5)));
+    /// ```
+    pub fn wait_idle_timeout(&self, timeout: Duration) -> bool {
+        self.shared.pending.wait_zero_timeout(timeout)
+    }
+
+    /// Stops accepting jobs and discards every queued job, while jobs
+    /// already running are left to finish. Later submissions fail with
+    /// `ExecuteError::Disconnected`.
+    ///
+    /// **returns**: the queued jobs that were discarded.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::{ExecuteError, WorkerPool};
+    /// use std::sync::mpsc;
+    ///
+    /// let pool = WorkerPool::new(1);
+    /// let (started_tx, started_rx) = mpsc::channel();
+    /// let (tx, rx) = mpsc::channel::<()>();
+    ///
+    /// pool.execute(move || {
+    ///     started_tx.send(()).unwrap();
+    ///     rx.recv().unwrap();
+    /// }).unwrap();
+    /// for _ in 0..3 {
+    ///     pool.execute(|| {}).unwrap();
+    /// }
+    /// started_rx.recv().unwrap();
+    ///
+    /// assert_eq!(3, pool.drain().len());
+    /// assert_eq!(Err(ExecuteError::Disconnected), pool.execute(|| {}));
+    ///
+    /// tx.send(()).unwrap();
+    /// pool.join();
+    /// ```
+    pub fn drain(&self) -> Vec<Job> {
+        self.shared.queue.close();
+        self.shared
+            .discard()
+            .into_iter()
+            .map(Task::into_job)
+            .collect()
+    }
+
+    /// Stops the pool and joins every worker thread. \
+    /// In-flight jobs always run to completion, as threads can't be killed.
+    /// The pool is stopped for all its clones.
+    ///
+    /// **mode**: ShutdownMode - What happens to the queued jobs. \
+    /// **returns**: the queued jobs that were not executed. It is always
+    /// empty for `ShutdownMode::Graceful`.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::{ShutdownMode, WorkerPool};
+    /// use std::sync::mpsc;
+    /// use std::thread;
+    /// use std::time::Duration;
+    ///
+    /// let pool = WorkerPool::new(1);
+    /// let (started_tx, started_rx) = mpsc::channel();
+    /// let (tx, rx) = mpsc::channel();
+    ///
+    /// pool.execute(move || {
+    ///     started_tx.send(()).unwrap();
+    ///     rx.recv().unwrap();
+    /// }).unwrap();
+    /// for _ in 0..3 {
+    ///     pool.execute(|| thread::sleep(Duration::from_millis(1))).unwrap();
+    /// }
+    /// started_rx.recv().unwrap();
+    ///
+    /// let handle = thread::spawn(move || {
+    ///     thread::sleep(Duration::from_millis(50));
+    ///     tx.send(()).unwrap();
+    /// });
+    ///
+    /// let skipped = pool.shutdown(ShutdownMode::Immediate);
+    /// assert_eq!(3, skipped.len());
+    /// handle.join().unwrap();
+    /// ```
+    pub fn shutdown(self, mode: ShutdownMode) -> Vec<Job> {
+        let discard = match mode {
+            ShutdownMode::Graceful => false,
+            ShutdownMode::Immediate => true,
+            ShutdownMode::Timeout(timeout) => !self.shared.pending.wait_zero_timeout(timeout),
+        };
+
+        self.shared.queue.close();
+        let discarded = if discard {
+            self.shared
+                .discard()
+                .into_iter()
+                .map(Task::into_job)
+                .collect()
+        } else {
+            Vec::new()
+        };
+        self.owner.stop();
+        discarded
+    }
+
+    /// Runs f with a [`Scope`] whose jobs may borrow data from the calling
+    /// stack frame, as `std::thread::scope` does. Every job executed in the
+    /// scope is finished before this function returns. Called from a job of
+    /// the same pool, the worker runs queued jobs while it waits, so nested
+    /// jobs don't deadlock a small pool.
+    ///
+    /// **f**: A FnOnce closure that receives the scope. \
+    /// **returns**: the value returned by f.
+    ///
+    /// # Panics
+    ///
+    /// Panics if f or any job executed in the scope panicked.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    ///
+    /// let pool = WorkerPool::new(4);
+    /// let numbers = vec![1, 2, 3, 4, 5];
+    /// let sum = AtomicUsize::new(0);
+    ///
+    /// pool.scope(|s| {
+    ///     for n in &numbers {
+    ///         let sum = &sum;
+    ///         s.execute(move || {
+    ///             sum.fetch_add(*n, Ordering::SeqCst);
+    ///         }).unwrap();
+    ///     }
+    /// });
+    ///
+    /// assert_eq!(15, sum.load(Ordering::SeqCst));
+    /// ```
+    pub fn scope<'env, F, T>(&self, f: F) -> T
+    where
+        F: for<'scope> FnOnce(&'scope Scope<'scope, 'env>) -> T,
+    {
+        let scope = Scope::new(self);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| f(&scope)));
+        let job_panicked = scope.wait();
+
+        match result {
+            Err(payload) => panic::resume_unwind(payload),
+            Ok(_) if job_panicked => panic!("a scoped job panicked"),
+            Ok(value) => value,
+        }
+    }
+
+    /// Applies f to every item in parallel and returns the results in
+    /// input order. Items are split into a few chunks per worker, and
+    /// each chunk runs as a single job.
+    ///
+    /// **iter**: IntoIterator - The items to map. \
+    /// **f**: A Fn closure applied to each item. \
+    /// **returns**: the mapped values, in the order of the items.
+    ///
+    /// # Panics
+    ///
+    /// Panics if f panics on any item or the jobs can't be queued. Called
+    /// from a job of the same pool, the worker runs queued jobs while it
+    /// waits for the chunks, as in [`scope`](WorkerPool::scope).
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    ///
+    /// let pool = WorkerPool::new(4);
+    /// let squares = pool.map(1..=5, |i| i * i);
+    ///
+    /// assert_eq!(vec![1, 4, 9, 16, 25], squares);
+    /// ```
+    pub fn map<I, F, R>(&self, iter: I, f: F) -> Vec<R>
+    where
+        I: IntoIterator,
+        I::Item: Send,
+        F: Fn(I::Item) -> R + Sync,
+        R: Send,
+    {
+        let chunks = self.chunks(iter, None);
+        let mut results: Vec<Vec<R>> = chunks.iter().map(|_| Vec::new()).collect();
+        let f = &f;
+        self.scope(|s| {
+            for (chunk, out) in chunks.into_iter().zip(results.iter_mut()) {
+                s.execute(move || *out = chunk.into_iter().map(f).collect())
+                    .expect("Cant execute job");
+            }
+        });
+        results.into_iter().flatten().collect()
+    }
+
+    /// Maps every item in parallel and reduces the mapped values to one.
+    /// Items are split into a few chunks per worker, and each job reduces
+    /// its chunk on its own, so the partial results are merged once, on the
+    /// calling thread, instead of through a shared accumulator. Values are
+    /// reduced in input order, so reduce must be associative but need not
+    /// be commutative.
+    ///
+    /// **iter**: IntoIterator - The items to map. \
+    /// **map**: A Fn closure applied to each item. \
+    /// **identity**: A Fn closure returning the neutral value of reduce,
+    /// which starts every partial result. \
+    /// **reduce**: A Fn closure combining two values. \
+    /// **returns**: the reduced value, or identity() if there is no item.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a closure panics or the jobs can't be queued.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    ///
+    /// let pool = WorkerPool::new(4);
+    /// let words = ["map", "reduce", "in", "order"];
+    /// let sentence = pool.map_reduce(
+    ///     words,
+    ///     |word| word.to_uppercase(),
+    ///     String::new,
+    ///     |left, right| left + &right,
+    /// );
+    ///
+    /// assert_eq!("MAPREDUCEINORDER", sentence);
+    /// ```
+    pub fn map_reduce<I, M, ID, F, R>(&self, iter: I, map: M, identity: ID, reduce: F) -> R
+    where
+        I: IntoIterator,
+        I::Item: Send,
+        M: Fn(I::Item) -> R + Sync,
+        ID: Fn() -> R + Sync,
+        F: Fn(R, R) -> R + Sync,
+        R: Send,
+    {
+        let chunks = self.chunks(iter, None);
+        let mut partials: Vec<Option<R>> = chunks.iter().map(|_| None).collect();
+        let (map, identity, reduce) = (&map, &identity, &reduce);
+        self.scope(|s| {
+            for (chunk, out) in chunks.into_iter().zip(partials.iter_mut()) {
+                s.execute(move || {
+                    let partial = chunk.into_iter().map(map).fold(identity(), reduce);
+                    *out = Some(partial);
+                })
+                .expect("Cant execute job");
+            }
+        });
+        partials.into_iter().flatten().fold(identity(), reduce)
+    }
+
+    /// Applies a fallible f to every item in parallel, as
+    /// [`map`](WorkerPool::map) does, and collects the values like
+    /// `collect::<Result<Vec<_>, _>>()`. Once an item fails, the jobs skip
+    /// the items they didn't start yet, and the error of the earliest
+    /// failed item is returned.
+    ///
+    /// **iter**: IntoIterator - The items to map. \
+    /// **f**: A Fn closure applied to each item, returning a Result. \
+    /// **returns**: the mapped values, in the order of the items, or the
+    /// first error.
+    ///
+    /// # Panics
+    ///
+    /// Panics if f panics on any item or the jobs can't be queued.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    ///
+    /// let pool = WorkerPool::new(4);
+    /// let parsed = pool.try_map(["1", "2", "3"], |s| s.parse::<i32>());
+    /// assert_eq!(Ok(vec![1, 2, 3]), parsed);
+    ///
+    /// let parsed = pool.try_map(["1", "two", "3"], |s| s.parse::<i32>());
+    /// assert!(parsed.is_err());
+    /// ```
+    pub fn try_map<I, F, T, E>(&self, iter: I, f: F) -> Result<Vec<T>, E>
+    where
+        I: IntoIterator,
+        I::Item: Send,
+        F: Fn(I::Item) -> Result<T, E> + Sync,
+        T: Send,
+        E: Send,
+    {
+        let chunks = self.chunks(iter.into_iter().enumerate(), None);
+        let mut results: Vec<Vec<T>> = chunks.iter().map(|_| Vec::new()).collect();
+        let failed = AtomicBool::new(false);
+        let error: Mutex<Option<(usize, E)>> = Mutex::new(None);
+        let (f, failed, error_slot) = (&f, &failed, &error);
+        self.scope(|s| {
+            for (chunk, out) in chunks.into_iter().zip(results.iter_mut()) {
+                s.execute(move || {
+                    for (index, item) in chunk {
+                        if failed.load(Ordering::SeqCst) {
+                            return;
+                        }
+                        match f(item) {
+                            Ok(value) => out.push(value),
+                            Err(error) => {
+                                failed.store(true, Ordering::SeqCst);
+                                let mut first =
+                                    error_slot.lock().unwrap_or_else(PoisonError::into_inner);
+                                if first.as_ref().is_none_or(|(first, _)| index < *first) {
+                                    *first = Some((index, error));
+                                }
+                                return;
+                            }
+                        }
+                    }
+                })
+                .expect("Cant execute job");
+            }
+        });
+        match error.into_inner().unwrap_or_else(PoisonError::into_inner) {
+            Some((_, error)) => Err(error),
+            None => Ok(results.into_iter().flatten().collect()),
+        }
+    }
+
+    /// Applies f to every item in parallel and returns an iterator that
+    /// yields the results as soon as workers produce them, in no particular
+    /// order. The iterator ends once every item was mapped; items whose
+    /// job panicked or was discarded are missing from it.
+    ///
+    /// **iter**: IntoIterator - The items to map. \
+    /// **f**: A Fn closure applied to each item. \
+    /// **returns**: an iterator over the mapped values.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the jobs can't be queued.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    ///
+    /// let pool = WorkerPool::new(4);
+    /// let mut squares: Vec<_> = pool.map_unordered(1..=5, |i| i * i).collect();
+    /// squares.sort();
+    ///
+    /// assert_eq!(vec![1, 4, 9, 16, 25], squares);
+    /// ```
+    pub fn map_unordered<I, F, R>(&self, iter: I, f: F) -> impl Iterator<Item = R>
+    where
+        I: IntoIterator,
+        I::Item: Send + 'static,
+        F: Fn(I::Item) -> R + Send + Sync + 'static,
+        R: Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+        let f = Arc::new(f);
+        for chunk in self.chunks(iter, None) {
+            let tx = tx.clone();
+            let f = Arc::clone(&f);
+            self.execute(move || {
+                for item in chunk {
+                    if tx.send(f(item)).is_err() {
+                        return;
+                    }
+                }
+            })
+            .expect("Cant execute job");
+        }
+        rx.into_iter()
+    }
+
+    /// Calls f on every item in parallel, and returns once every call has
+    /// finished. Items are grouped into chunks and each chunk runs as a
+    /// single job, which amortizes the queueing cost of very small work.
+    ///
+    /// **iter**: IntoIterator - The items to visit. \
+    /// **chunk_size_hint**: usize - The number of items per job, or 0 to
+    /// make a few chunks per worker. \
+    /// **f**: A Fn closure called with each item.
+    ///
+    /// # Panics
+    ///
+    /// Panics if f panics on any item or the jobs can't be queued.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    ///
+    /// let pool = WorkerPool::new(4);
+    /// let sum = AtomicUsize::new(0);
+    ///
+    /// pool.for_each(1..=100, 10, |i| {
+    ///     sum.fetch_add(i, Ordering::SeqCst);
+    /// });
+    ///
+    /// assert_eq!(5050, sum.load(Ordering::SeqCst));
+    /// ```
+    pub fn for_each<I, F>(&self, iter: I, chunk_size_hint: usize, f: F)
+    where
+        I: IntoIterator,
+        I::Item: Send,
+        F: Fn(I::Item) + Sync,
+    {
+        let size = Some(chunk_size_hint).filter(|&size| size > 0);
+        let chunks = self.chunks(iter, size);
+        let f = &f;
+        self.scope(|s| {
+            for chunk in chunks {
+                s.execute(move || chunk.into_iter().for_each(f))
+                    .expect("Cant execute job");
+            }
+        });
+    }
+
+    /// Calls f on consecutive chunks of a slice in parallel, each chunk
+    /// being mutably borrowed by a single job, and returns once every call
+    /// has finished. The slice is mutated in place, without wrapping it in
+    /// a lock.
+    ///
+    /// **slice**: &mut [T] - The items to mutate. \
+    /// **chunk_size**: usize - The number of items per chunk, or 0 to make
+    /// a few chunks per worker. The last chunk may be shorter. \
+    /// **f**: A Fn closure called with each chunk.
+    ///
+    /// # Panics
+    ///
+    /// Panics if f panics on any chunk or the jobs can't be queued.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    ///
+    /// let pool = WorkerPool::new(4);
+    /// let mut pixels = vec![10u8; 1024];
+    ///
+    /// pool.for_each_mut(&mut pixels, 256, |chunk| {
+    ///     chunk.iter_mut().for_each(|pixel| *pixel = 255 - *pixel);
+    /// });
+    ///
+    /// assert!(pixels.iter().all(|pixel| *pixel == 245));
+    /// ```
+    pub fn for_each_mut<T, F>(&self, slice: &mut [T], chunk_size: usize, f: F)
+    where
+        T: Send,
+        F: Fn(&mut [T]) + Sync,
+    {
+        let size = match chunk_size {
+            0 => {
+                let chunks = self.num_workers().max(1) * CHUNKS_PER_WORKER;
+                slice.len().div_ceil(chunks).max(1)
+            }
+            size => size,
+        };
+        let f = &f;
+        self.scope(|s| {
+            for chunk in slice.chunks_mut(size) {
+                s.execute(move || f(chunk)).expect("Cant execute job");
+            }
+        });
+    }
+
+    /// Sorts a slice in parallel, keeping the order of equal items. The
+    /// slice is split into one run per worker, the runs are sorted in
+    /// parallel, and pairs of neighbouring runs are then merged in
+    /// parallel until one run is left. Short slices are sorted on the
+    /// calling thread.
+    ///
+    /// **slice**: &mut [T] - The items to sort.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the ordering of T panics or the jobs can't be queued.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    ///
+    /// let pool = WorkerPool::new(4);
+    /// let mut values: Vec<u64> = (0..100_000).map(|i| i * 7919 % 100_003).collect();
+    ///
+    /// pool.sort(&mut values);
+    ///
+    /// assert!(values.windows(2).all(|pair| pair[0] <= pair[1]));
+    /// ```
+    pub fn sort<T: Ord + Send>(&self, slice: &mut [T]) {
+        let workers = self.num_workers();
+        if workers < 2 || slice.len() < PARALLEL_SORT_MIN {
+            slice.sort();
+            return;
+        }
+        // The std sort finds the two sorted runs of each merged chunk and
+        // merges them in linear time.
+        let mut size = slice.len().div_ceil(workers);
+        self.for_each_mut(slice, size, <[T]>::sort);
+        while size < slice.len() {
+            size *= 2;
+            self.for_each_mut(slice, size, <[T]>::sort);
+        }
+    }
+
+    /// Runs a closure exactly once on each worker thread, with the worker
+    /// id, and waits for every run. It is meant for per-thread work, as
+    /// setting up thread-local caches. The runs are queued with the high
+    /// priority, and each worker holds on to its run until every worker
+    /// took one, so they are all busy while the broadcast runs.
+    ///
+    /// **f**: A Fn closure taking the worker id. \
+    /// **returns**: the values returned on each worker, ordered by worker
+    /// id, or `Err(ExecuteError)` if the runs can't be queued.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from a job of the pool, as that worker could never
+    /// take its run, and resumes the panic of a run once all of them are
+    /// done.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    /// use std::cell::Cell;
+    ///
+    /// thread_local! {
+    ///     static CACHE: Cell<usize> = Cell::new(0);
+    /// }
+    ///
+    /// let pool = WorkerPool::new(3);
+    /// let ids = pool.broadcast(|id| {
+    ///     CACHE.with(|cache| cache.set(id + 1));
+    ///     id
+    /// }).unwrap();
+    /// assert_eq!(vec![0, 1, 2], ids);
+    ///
+    /// let caches = pool.broadcast(|_| CACHE.with(Cell::get)).unwrap();
+    /// assert_eq!(vec![1, 2, 3], caches);
+    /// ```
+    pub fn broadcast<F, R>(&self, f: F) -> Result<Vec<R>, ExecuteError>
+    where
+        F: Fn(usize) -> R + Send + Sync + 'static,
+        R: Send + 'static,
+    {
+        assert!(
+            self.shared.current_worker().is_none(),
+            "broadcast can't be called from a worker of the pool"
+        );
+        let workers = self.num_workers();
+        let f = Arc::new(f);
+        let barrier = Arc::new(Barrier::new(workers));
+        let (tx, rx) = mpsc::channel();
+        let jobs = (0..workers)
+            .map(|_| {
+                let (f, barrier, tx) = (Arc::clone(&f), Arc::clone(&barrier), tx.clone());
+                into_job(move || {
+                    let id = CURRENT_WORKER.with(Cell::get).map_or(0, |(_, id)| id);
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| f(id)));
+                    barrier.wait();
+                    let _ = tx.send((id, result));
+                })
+            })
+            .collect();
+        drop(tx);
+        self.shared.push_batch(jobs, Priority::High)?;
+        self.shared.start_all();
+        let mut results: Vec<_> = rx.iter().collect();
+        results.sort_by_key(|(id, _)| *id);
+        let values = results
+            .into_iter()
+            .map(|(_, result)| result.unwrap_or_else(|payload| panic::resume_unwind(payload)))
+            .collect();
+        Ok(values)
+    }
+
+    /// Waits for a job submitted from a job of this pool. A worker blocked
+    /// on a handle can deadlock a small pool, as the job it waits for may
+    /// be queued behind it. Called from a worker of the pool, this runs the
+    /// queued jobs, including the awaited one, until the job is finished,
+    /// and sleeps until it finishes once no job is queued; called from any
+    /// other thread, it is the same as
+    /// [`JobHandle::wait`]. The jobs run meanwhile are reported to the
+    /// observer, the stats and the panic policy as any other job, and one
+    /// asking to restart the worker has it restarted once the waiting job
+    /// returns.
+    ///
+    /// **handle**: JobHandle<R> - The handle of the job. \
+    /// **returns**: the value of the job, or `Err(JobError)`.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    ///
+    /// let pool = WorkerPool::new(1);
+    /// let nested = pool.clone();
+    /// let outer = pool
+    ///     .submit(move || {
+    ///         let inner = nested.submit(|| 20).unwrap();
+    ///         nested.join_nested(inner).unwrap() + 1
+    ///     })
+    ///     .unwrap();
+    ///
+    /// assert_eq!(Ok(21), outer.wait());
+    /// ```
+    pub fn join_nested<R: Send + 'static>(&self, handle: JobHandle<R>) -> Result<R, JobError> {
+        if self.shared.current_worker().is_none() {
+            return handle.wait();
+        }
+        // With no queued job left to run, the worker parks until the job
+        // finishing unparks it.
+        let handle = handle.unpark_on_complete(thread::current());
+        self.help_until(|timeout| {
+            if !handle.is_finished() && timeout > Duration::ZERO {
+                thread::park();
+            }
+            handle.is_finished()
+        });
+        handle.wait()
+    }
+
+    // Runs queued jobs on the calling thread, if it is a worker of this
+    // pool, until finished returns true. finished is given how long it may
+    // block, which is zero while there are jobs to run. Returns false if the
+    // caller isn't a worker of this pool.
+    pub(crate) fn help_until<W: FnMut(Duration) -> bool>(&self, mut finished: W) -> bool {
+        let Some(worker) = self.shared.current_worker() else {
+            return false;
+        };
+        while !finished(Duration::ZERO) {
+            if !self.shared.help(worker) && finished(HELP_PARK) {
+                break;
+            }
+        }
+        true
+    }
+
+    /// Runs two closures concurrently and returns both values once both are
+    /// done. The first runs on the pool while the second runs on the
+    /// calling thread, as in a fork-join.
+    ///
+    /// **a**: A FnOnce closure run on the pool. \
+    /// **b**: A FnOnce closure run on the calling thread. \
+    /// **returns**: the values returned by a and b.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either closure panics or the job can't be queued.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    ///
+    /// let pool = WorkerPool::new(2);
+    /// let data = vec![1, 2, 3, 4, 5, 6];
+    /// let (left, right) = data.split_at(3);
+    ///
+    /// let (a, b) = pool.join2(|| left.iter().sum::<i32>(), || right.iter().sum::<i32>());
+    /// assert_eq!((6, 15), (a, b));
+    /// ```
+    pub fn join2<A, B, RA, RB>(&self, a: A, b: B) -> (RA, RB)
+    where
+        A: FnOnce() -> RA + Send,
+        B: FnOnce() -> RB,
+        RA: Send,
+    {
+        let mut ra = None;
+        let rb = self.scope(|s| {
+            s.execute(|| ra = Some(a())).expect("Cant execute job");
+            b()
+        });
+        (ra.expect("joined job has no value"), rb)
+    }
+
+    /// Runs three closures concurrently and returns the three values once
+    /// all are done. The first two run on the pool while the last runs on
+    /// the calling thread.
+    ///
+    /// **a**: A FnOnce closure run on the pool. \
+    /// **b**: A FnOnce closure run on the pool. \
+    /// **c**: A FnOnce closure run on the calling thread. \
+    /// **returns**: the values returned by a, b and c.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any closure panics or the jobs can't be queued.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    ///
+    /// let pool = WorkerPool::new(2);
+    /// let (a, b, c) = pool.join3(|| 1, || "two", || 3.0);
+    ///
+    /// assert_eq!((1, "two", 3.0), (a, b, c));
+    /// ```
+    pub fn join3<A, B, C, RA, RB, RC>(&self, a: A, b: B, c: C) -> (RA, RB, RC)
+    where
+        A: FnOnce() -> RA + Send,
+        B: FnOnce() -> RB + Send,
+        C: FnOnce() -> RC,
+        RA: Send,
+        RB: Send,
+    {
+        let (mut ra, mut rb) = (None, None);
+        let rc = self.scope(|s| {
+            s.execute(|| ra = Some(a())).expect("Cant execute job");
+            s.execute(|| rb = Some(b())).expect("Cant execute job");
+            c()
+        });
+        (
+            ra.expect("joined job has no value"),
+            rb.expect("joined job has no value"),
+            rc,
+        )
+    }
+
+    // Splits the items into chunks of the given size, or into a few chunks
+    // per worker when no size is given.
+    fn chunks<I: IntoIterator>(&self, iter: I, size: Option<usize>) -> Vec<Vec<I::Item>> {
+        let items: Vec<_> = iter.into_iter().collect();
+        let size = size.unwrap_or_else(|| {
+            let chunks = self.num_workers().max(1) * CHUNKS_PER_WORKER;
+            items.len().div_ceil(chunks)
+        });
+        let mut items = items.into_iter().peekable();
+        let mut chunks = Vec::new();
+        while items.peek().is_some() {
+            chunks.push(items.by_ref().take(size.max(1)).collect());
+        }
+        chunks
+    }
+
+    /// Returns a [`JobSender`] that submits jobs to this pool.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    /// use std::sync::mpsc;
+    /// use std::thread;
+    ///
+    /// let pool = WorkerPool::new(2);
+    /// let (tx, rx) = mpsc::channel();
+    ///
+    /// let producers: Vec<_> = (0..4)
+    ///     .map(|i| {
+    ///         let sender = pool.sender();
+    ///         let tx = tx.clone();
+    ///         thread::spawn(move || {
+    ///             sender.execute(move || tx.send(i).unwrap()).unwrap();
+    ///         })
+    ///     })
+    ///     .collect();
+    ///
+    /// for producer in producers {
+    ///     producer.join().unwrap();
+    /// }
+    /// assert_eq!(6, rx.iter().take(4).sum::<i32>());
+    /// ```
+    pub fn sender(&self) -> JobSender {
+        JobSender {
+            shared: Arc::clone(&self.shared),
+            producer: self.shared.next_producer.fetch_add(1, Ordering::SeqCst),
+        }
+    }
+
+    /// Executes a job once the delay has elapsed. Delayed jobs are kept by
+    /// a timer thread, spawned on first use, and are dropped if the pool
+    /// stops before they are due.
+    ///
+    /// **delay**: Duration - The time to wait before queueing the job. \
+    /// **f**: A FnOnce closure. \
+    /// **returns**: a ScheduledHandle to cancel the job.
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    /// use std::sync::mpsc;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let pool = WorkerPool::new(1);
+    /// let (tx, rx) = mpsc::channel();
+    /// let start = Instant::now();
+    ///
+    /// pool.execute_after(Duration::from_millis(20), move || {
+    ///     tx.send(Instant::now()).unwrap();
+    /// });
+    ///
+    /// assert!(rx.recv().unwrap() - start >= Duration::from_millis(20));
+    /// ```
+    pub fn execute_after<F>(&self, delay: Duration, f: F) -> ScheduledHandle
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.execute_at(Instant::now() + delay, f)
+    }
+
+    /// Executes a job once the given instant is reached, as
+    /// [`WorkerPool::execute_after`].
+    ///
+    /// **at**: Instant - When the job is queued. \
+    /// **f**: A FnOnce closure. \
+    /// **returns**: a ScheduledHandle to cancel the job.
+    pub fn execute_at<F>(&self, at: Instant, f: F) -> ScheduledHandle
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.with_scheduler(|scheduler| scheduler.schedule(at, into_job(f)))
+    }
+
+    /// Executes a job repeatedly, every period, until its handle is
+    /// cancelled or the pool stops. The first run is queued one period
+    /// from now.
+    ///
+    /// **period**: Duration - The interval between runs. \
+    /// **mode**: RepeatMode - Whether the period is measured between run
+    /// starts or from the end of the previous run. \
+    /// **f**: A Fn closure. \
+    /// **returns**: a ScheduledHandle to stop the recurrence.
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    /// use workerpool_rs::schedule::RepeatMode;
+    /// use std::sync::mpsc;
+    /// use std::sync::Mutex;
+    /// use std::time::Duration;
+    ///
+    /// let pool = WorkerPool::new(1);
+    /// let (tx, rx) = mpsc::channel();
+    /// let tx = Mutex::new(tx);
+    ///
+    /// let handle = pool.execute_every(Duration::from_millis(5), RepeatMode::FixedRate, move || {
+    ///     let _ = tx.lock().unwrap().send(());
+    /// });
+    ///
+    /// assert_eq!(3, rx.iter().take(3).count());
+    /// handle.cancel();
+    /// ```
+    pub fn execute_every<F>(&self, period: Duration, mode: RepeatMode, f: F) -> ScheduledHandle
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        let at = Instant::now() + period;
+        self.with_scheduler(|scheduler| scheduler.schedule_every(at, period, mode, Arc::new(f)))
+    }
+
+    // Calls f with the scheduler, which is created on first use.
+    fn with_scheduler<T>(&self, f: impl FnOnce(&Scheduler) -> T) -> T {
+        let mut scheduler = self
+            .owner
+            .scheduler
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let scheduler = scheduler.get_or_insert_with(|| {
+            let shared = Arc::clone(&self.shared);
+            Scheduler::new(move |job| {
+                let _ = shared.push(Task::from(job), Priority::Normal);
+            })
+        });
+        f(scheduler)
+    }
+}
+
+impl Owner {
+    // Stops the timer, closes the job queue, so workers leave their loop
+    // once it is empty, and then joins every worker thread but the calling
+    // one, as the last clone may be dropped by a job.
+    fn stop(&self) {
+        let supervisor = self
+            .supervisor
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if let Some(mut supervisor) = supervisor {
+            supervisor.stop();
+        }
+        let watchdog = self
+            .watchdog
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if let Some(mut watchdog) = watchdog {
+            watchdog.stop();
+        }
+        let scheduler = self
+            .scheduler
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if let Some(mut scheduler) = scheduler {
+            scheduler.stop();
+        }
+        let timeouts = self
+            .timeouts
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if let Some(mut timeouts) = timeouts {
+            timeouts.stop();
+        }
+        self.shared.queue.close();
+        let current = self.shared.current_worker();
+        let workers = std::mem::take(&mut *self.shared.workers());
+        for worker in workers.active.iter().chain(&workers.retiring) {
+            if Some(worker.id) != current {
+                worker.join();
+            }
+        }
+    }
+
+    // Schedules f on the timeouts timer, which is created on first use. It
+    // runs its tasks on its own thread, as the workers may all be busy.
+    fn schedule_timeout<F>(&self, at: Instant, f: F) -> ScheduledHandle
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let mut timeouts = self.timeouts.lock().unwrap_or_else(PoisonError::into_inner);
+        timeouts
+            .get_or_insert_with(|| Scheduler::new(|task: Job| task()))
+            .schedule(at, Box::new(f))
+    }
+}
+
+// Same as WorkerPool::auto.
+impl Default for WorkerPool {
+    fn default() -> WorkerPool {
+        WorkerPool::auto()
+    }
+}
+
+// Returns the number of available cores, or 1 if it can't be detected.
+pub(crate) fn default_num_workers() -> usize {
+    thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
+
+/// Configures and builds a [`WorkerPool`].
+///
+/// # Examples
+///
+/// ```
+/// use workerpool_rs::pool::WorkerPoolBuilder;
+/// use std::thread;
+///
+/// let pool = WorkerPoolBuilder::new()
+///     .num_workers(2)
+///     .thread_name_prefix("io")
+///     .stack_size(256 * 1024)
+///     .queue_capacity(64)
+///     .build();
+///
+/// let handle = pool
+///     .submit(|| thread::current().name().map(String::from))
+///     .unwrap();
+/// assert!(handle.wait().unwrap().unwrap().starts_with("io-"));
+/// ```
+#[derive(Clone)]
+pub struct WorkerPoolBuilder {
+    num_workers: usize,
+    queue_capacity: Option<usize>,
+    priority_aging: Duration,
+    work_stealing: bool,
+    idle_strategy: IdleStrategy,
+    dispatch_order: DispatchOrder,
+    idle_timeout: Option<Duration>,
+    autoscale: Option<AutoScale>,
+    watchdog: Option<(Watchdog, StuckHandler)>,
+    tag_limits: Vec<(String, usize)>,
+    options: WorkerOptions,
+}
+
+impl WorkerPoolBuilder {
+    /// Constructs a new WorkerPoolBuilder with one worker per available
+    /// core, threads named `workerpool-{id}`, the default stack size and an
+    /// unbounded queue.
+    pub fn new() -> WorkerPoolBuilder {
+        WorkerPoolBuilder {
+            num_workers: default_num_workers(),
+            queue_capacity: None,
+            priority_aging: DEFAULT_PRIORITY_AGING,
+            work_stealing: false,
+            idle_strategy: IdleStrategy::Park,
+            dispatch_order: DispatchOrder::Fifo,
+            idle_timeout: None,
+            autoscale: None,
+            watchdog: None,
+            tag_limits: Vec::new(),
+            options: WorkerOptions::default(),
+        }
+    }
+
+    /// Sets the number of workers.
+    ///
+    /// **size**: usize - Is the number of workers in WorkerPool object.
+    pub fn num_workers(mut self, size: usize) -> WorkerPoolBuilder {
+        self.num_workers = size;
+        self
+    }
+
+    /// Names the worker threads `{prefix}-{id}`, so debuggers, profilers
+    /// and OS tools tell the pools apart. The default prefix is
+    /// `workerpool`. Keep names under 16 bytes, as Linux truncates longer
+    /// thread names.
+    ///
+    /// **prefix**: &str - The prefix of every worker thread name.
+    pub fn thread_name_prefix(mut self, prefix: &str) -> WorkerPoolBuilder {
+        self.options.name_prefix = Some(prefix.to_string());
+        self
+    }
+
+    /// Sets the stack size, in bytes, of the worker threads, including
+    /// those spawned later by a resize or a restart. Without it, workers
+    /// get the default stack of spawned threads, 2 MiB unless the
+    /// `RUST_MIN_STACK` variable says otherwise, which deeply recursive
+    /// jobs may overflow.
+    ///
+    /// **size**: usize - The stack size in bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPoolBuilder;
+    ///
+    /// fn depth(n: u64) -> u64 {
+    ///     let frame = std::hint::black_box([n; 64]);
+    ///     if n == 0 { 0 } else { 1 + depth(frame[0] - 1) }
+    /// }
+    ///
+    /// let pool = WorkerPoolBuilder::new()
+    ///     .num_workers(1)
+    ///     .stack_size(64 * 1024 * 1024)
+    ///     .build();
+    ///
+    /// assert_eq!(Ok(50_000), pool.submit(|| depth(50_000)).unwrap().wait());
+    /// ```
+    pub fn stack_size(mut self, size: usize) -> WorkerPoolBuilder {
+        self.options.stack_size = Some(size);
+        self
+    }
+
+    /// Pins each worker thread to a core or a set of cores, once it
+    /// starts. The cores the process may run on are read here, and a
+    /// worker that can't be pinned is reported to the observer.
+    ///
+    /// **affinity**: Affinity - The cores of each worker.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use workerpool_rs::affinity::Affinity;
+    /// use workerpool_rs::pool::WorkerPoolBuilder;
+    ///
+    /// let pool = WorkerPoolBuilder::new()
+    ///     .num_workers(2)
+    ///     .affinity(Affinity::RoundRobin)
+    ///     .build();
+    ///
+    /// assert_eq!(Ok(42), pool.submit(|| 42).unwrap().wait());
+    /// ```
+    #[cfg(feature = "affinity")]
+    pub fn affinity(mut self, affinity: crate::affinity::Affinity) -> WorkerPoolBuilder {
+        self.options.affinity = Some(crate::affinity::Pinning::new(affinity));
+        self
+    }
+
+    /// Sets the OS priority of the worker threads, once they start.
+    ///
+    /// **priority**: ThreadPriority - The priority of the workers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPoolBuilder;
+    /// use workerpool_rs::thread_priority::ThreadPriority;
+    ///
+    /// let pool = WorkerPoolBuilder::new()
+    ///     .thread_priority(ThreadPriority::Background)
+    ///     .build();
+    ///
+    /// assert_eq!(Ok(42), pool.submit(|| 42).unwrap().wait());
+    /// ```
+    #[cfg(feature = "thread-priority")]
+    pub fn thread_priority(
+        mut self,
+        priority: crate::thread_priority::ThreadPriority,
+    ) -> WorkerPoolBuilder {
+        self.options.thread_priority = priority;
+        self
+    }
+
+    /// Sets a callback that is called on every worker thread, with the id
+    /// of the worker, before it runs its first job. It is the place to set
+    /// up thread-locals or register the thread with a profiler. A worker
+    /// started again, by autoscaling or after an idle timeout, calls it
+    /// again.
+    ///
+    /// **hook**: Fn(usize) - The start callback.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPoolBuilder;
+    /// use std::cell::Cell;
+    ///
+    /// thread_local! {
+    ///     static WORKER_ID: Cell<Option<usize>> = Cell::new(None);
+    /// }
+    ///
+    /// let pool = WorkerPoolBuilder::new()
+    ///     .num_workers(2)
+    ///     .on_worker_start(|id| WORKER_ID.with(|worker| worker.set(Some(id))))
+    ///     .build();
+    ///
+    /// let id = pool.submit(|| WORKER_ID.with(Cell::get)).unwrap().wait();
+    /// assert!(matches!(id, Ok(Some(0 | 1))));
+    /// ```
+    pub fn on_worker_start<H>(mut self, hook: H) -> WorkerPoolBuilder
+    where
+        H: Fn(usize) + Send + Sync + 'static,
+    {
+        self.options.on_start = Some(Arc::new(hook));
+        self
+    }
+
+    /// Sets a callback that is called on every worker thread, with the id
+    /// of the worker, once it stops taking jobs and before the thread
+    /// exits, so it can flush its thread-local state. It is called when the
+    /// pool stops, and when a worker is retired or stops on its idle
+    /// timeout.
+    ///
+    /// **hook**: Fn(usize) - The stop callback.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPoolBuilder;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// let stopped = Arc::new(AtomicUsize::new(0));
+    /// let counter = stopped.clone();
+    /// let pool = WorkerPoolBuilder::new()
+    ///     .num_workers(3)
+    ///     .on_worker_stop(move |_| {
+    ///         counter.fetch_add(1, Ordering::SeqCst);
+    ///     })
+    ///     .build();
+    ///
+    /// drop(pool);
+    /// assert_eq!(3, stopped.load(Ordering::SeqCst));
+    /// ```
+    pub fn on_worker_stop<H>(mut self, hook: H) -> WorkerPoolBuilder
+    where
+        H: Fn(usize) + Send + Sync + 'static,
+    {
+        self.options.on_stop = Some(Arc::new(hook));
+        self
+    }
+
+    /// Sets a callback that is called, on the worker thread, with the
+    /// payload of every job that panics. It runs after the observer, and
+    /// before the callback of a `PanicPolicy::Callback`.
+    ///
+    /// **handler**: Fn(&(dyn Any + Send)) - The panic callback.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPoolBuilder;
+    /// use std::sync::mpsc;
+    /// use std::sync::Mutex;
+    ///
+    /// let (tx, rx) = mpsc::channel();
+    /// let tx = Mutex::new(tx);
+    /// let pool = WorkerPoolBuilder::new()
+    ///     .panic_handler(move |payload| {
+    ///         let message = payload.downcast_ref::<&str>().unwrap();
+    ///         tx.lock().unwrap().send(message.to_string()).unwrap();
+    ///     })
+    ///     .build();
+    ///
+    /// pool.execute(|| panic!("boom")).unwrap();
+    /// assert_eq!("boom", rx.recv().unwrap());
+    /// ```
+    pub fn panic_handler<H>(mut self, handler: H) -> WorkerPoolBuilder
+    where
+        H: Fn(&(dyn Any + Send)) + Send + Sync + 'static,
+    {
+        self.options.panic_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Sets a callback that is called, on the worker thread, for every job
+    /// executed with [`WorkerPool::execute_with_deadline`] that starts after
+    /// its deadline. It receives how late the job is.
+    ///
+    /// **handler**: Fn(Duration) - The missed deadline callback.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPoolBuilder;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    /// use std::time::Instant;
+    ///
+    /// let missed = Arc::new(AtomicUsize::new(0));
+    /// let counter = missed.clone();
+    /// let pool = WorkerPoolBuilder::new()
+    ///     .deadline_miss_handler(move |_late| {
+    ///         counter.fetch_add(1, Ordering::SeqCst);
+    ///     })
+    ///     .build();
+    ///
+    /// pool.execute_with_deadline(Instant::now(), || {}).unwrap();
+    /// pool.join();
+    /// assert_eq!(1, missed.load(Ordering::SeqCst));
+    /// ```
+    pub fn deadline_miss_handler<H>(mut self, handler: H) -> WorkerPoolBuilder
+    where
+        H: Fn(Duration) + Send + Sync + 'static,
+    {
+        self.options.miss_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Sets the order workers take jobs in. The default is
+    /// `QueueOrder::Priority`.
+    ///
+    /// **order**: QueueOrder - The queue order.
+    pub fn queue_order(mut self, order: QueueOrder) -> WorkerPoolBuilder {
+        self.options.queue_order = order;
+        self
+    }
+
+    /// Sets the channel receiving a [`DeadLetter`] for every job that
+    /// panics, and for every job of [`WorkerPool::submit_retry`] whose
+    /// every attempt failed. Records are dropped once the receiver is gone.
+    ///
+    /// **tx**: Sender<DeadLetter> - The dead letter channel.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use workerpool_rs::deadletter::Failure;
+    /// use workerpool_rs::pool::WorkerPoolBuilder;
+    /// use std::sync::mpsc;
+    ///
+    /// let (tx, rx) = mpsc::channel();
+    /// let pool = WorkerPoolBuilder::new().dead_letter(tx).build();
+    ///
+    /// let id = pool.submit_tracked(|| panic!("boom")).unwrap();
+    /// let letter = rx.recv().unwrap();
+    /// assert_eq!(Some(id), letter.job);
+    /// assert_eq!(1, letter.attempts);
+    /// assert!(matches!(letter.failure, Failure::Panic(_)));
+    /// ```
+    pub fn dead_letter(mut self, tx: mpsc::Sender<DeadLetter>) -> WorkerPoolBuilder {
+        self.options.dead_letter = Some(tx);
+        self
+    }
+
+    /// Sets the channel receiving a [`ProgressUpdate`] every time a job
+    /// executed with [`WorkerPool::execute_ctx`] reports its progress
+    /// through its [`JobContext`]. Updates are dropped once the receiver
+    /// is gone.
+    ///
+    /// **tx**: Sender<ProgressUpdate> - The progress channel.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPoolBuilder;
+    /// use std::sync::mpsc;
+    ///
+    /// let (tx, rx) = mpsc::channel();
+    /// let pool = WorkerPoolBuilder::new().progress_channel(tx).build();
+    ///
+    /// let id = pool
+    ///     .execute_ctx(|ctx| {
+    ///         for step in 1..=4 {
+    ///             ctx.progress(step as f64 / 4.0);
+    ///         }
+    ///         ctx.progress_message("done");
+    ///         ctx.job_id()
+    ///     })
+    ///     .unwrap()
+    ///     .wait()
+    ///     .unwrap();
+    ///
+    /// let updates: Vec<_> = rx.try_iter().collect();
+    /// assert_eq!(5, updates.len());
+    /// assert!(updates.iter().all(|update| update.job == id));
+    /// assert_eq!(Some(0.5), updates[1].fraction);
+    /// assert_eq!(Some("done"), updates[4].message.as_deref());
+    /// ```
+    pub fn progress_channel(mut self, tx: mpsc::Sender<ProgressUpdate>) -> WorkerPoolBuilder {
+        self.options.progress = Some(tx);
+        self
+    }
+
+    /// Registers an observer whose callbacks are called on the lifecycle
+    /// events of every job. See [`PoolObserver`].
+    ///
+    /// **observer**: PoolObserver - The observer of the pool.
+    pub fn observer<O>(mut self, observer: O) -> WorkerPoolBuilder
+    where
+        O: PoolObserver + 'static,
+    {
+        self.options.observer = Some(Arc::new(observer));
+        self
+    }
+
+    /// Sets whether dead workers are replaced. The default is
+    /// `RestartPolicy::Never`.
+    ///
+    /// **policy**: RestartPolicy - The restart policy.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::{RestartPolicy, WorkerPoolBuilder};
+    ///
+    /// let pool = WorkerPoolBuilder::new()
+    ///     .restart_policy(RestartPolicy::Always)
+    ///     .panic_handler(|_| panic!("the worker dies"))
+    ///     .build();
+    ///
+    /// pool.execute(|| panic!("job failed")).unwrap();
+    /// assert_eq!(Ok(42), pool.submit(|| 42).unwrap().wait());
+    /// ```
+    pub fn restart_policy(mut self, policy: RestartPolicy) -> WorkerPoolBuilder {
+        self.options.restart_policy = policy;
+        self
+    }
+
+    /// Sets what a worker does once a job panicked. The default is
+    /// `PanicPolicy::Ignore`.
+    ///
+    /// **policy**: PanicPolicy - The panic policy.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::{ExecuteError, PanicPolicy, WorkerPoolBuilder};
+    ///
+    /// let pool = WorkerPoolBuilder::new()
+    ///     .num_workers(2)
+    ///     .panic_policy(PanicPolicy::AbortPool)
+    ///     .build();
+    ///
+    /// pool.execute(|| panic!("corrupted state")).unwrap();
+    /// pool.join();
+    /// assert_eq!(Err(ExecuteError::Disconnected), pool.execute(|| {}));
+    /// ```
+    pub fn panic_policy(mut self, policy: PanicPolicy) -> WorkerPoolBuilder {
+        self.options.panic_policy = policy;
+        self
+    }
+
+    /// Bounds the job queue, as in [`WorkerPool::with_queue_capacity`].
+    ///
+    /// **capacity**: usize - Is the maximum number of queued jobs.
+    pub fn queue_capacity(mut self, capacity: usize) -> WorkerPoolBuilder {
+        self.queue_capacity = Some(capacity);
+        self
+    }
+
+    /// Sets what `execute` and the other blocking submissions do when the
+    /// bounded queue is full. The default is `SaturationPolicy::Block`.
+    /// Batches and `try_execute` are not affected.
+    ///
+    /// **policy**: SaturationPolicy - The saturation policy.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::{SaturationPolicy, WorkerPoolBuilder};
+    /// use std::sync::mpsc;
+    /// use std::thread;
+    ///
+    /// let pool = WorkerPoolBuilder::new()
+    ///     .num_workers(1)
+    ///     .queue_capacity(1)
+    ///     .saturation_policy(SaturationPolicy::CallerRuns)
+    ///     .build();
+    /// let (started_tx, started_rx) = mpsc::channel();
+    /// let (tx, rx) = mpsc::channel::<()>();
+    /// pool.execute(move || {
+    ///     started_tx.send(()).unwrap();
+    ///     rx.recv().unwrap();
+    /// })
+    /// .unwrap();
+    /// started_rx.recv().unwrap();
+    /// pool.execute(|| {}).unwrap();
+    ///
+    /// let caller = thread::current().id();
+    /// let handle = pool.submit(|| thread::current().id()).unwrap();
+    /// assert_eq!(Ok(caller), handle.wait());
+    /// tx.send(()).unwrap();
+    /// ```
+    pub fn saturation_policy(mut self, policy: SaturationPolicy) -> WorkerPoolBuilder {
+        self.options.saturation = policy;
+        self
+    }
+
+    /// Sets where the jobs run. The default is `ExecutionMode::Threaded`.
+    /// An inline pool spawns no worker and ignores the autoscaler: each job
+    /// runs on the submitting thread, reported to the observer, the stats,
+    /// the panic handler, the panic policy and the dead letter channel as
+    /// on a worker. Keyed, tagged and weighted jobs waiting for their turn
+    /// run on the thread that finishes the job before them. Delayed and
+    /// periodic jobs run on the thread of the scheduler when they are due.
+    ///
+    /// **mode**: ExecutionMode - The execution mode.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::{ExecutionMode, WorkerPoolBuilder};
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let pool = WorkerPoolBuilder::new()
+    ///     .execution_mode(ExecutionMode::Inline)
+    ///     .build();
+    /// let order = Arc::new(Mutex::new(Vec::new()));
+    ///
+    /// for i in 0..3 {
+    ///     let order = order.clone();
+    ///     pool.execute(move || order.lock().unwrap().push(i)).unwrap();
+    /// }
+    ///
+    /// assert_eq!(vec![0, 1, 2], *order.lock().unwrap());
+    /// assert_eq!(0, pool.num_workers());
+    /// ```
+    pub fn execution_mode(mut self, mode: ExecutionMode) -> WorkerPoolBuilder {
+        self.options.mode = mode;
+        self
+    }
+
+    /// Sets how long a queued job waits before it is promoted one
+    /// [`Priority`] level. The default is one second.
+    ///
+    /// **aging**: Duration - The waiting time for each promotion.
+    pub fn priority_aging(mut self, aging: Duration) -> WorkerPoolBuilder {
+        self.priority_aging = aging;
+        self
+    }
+
+    /// Gives each worker its own deque. Jobs submitted from a worker with
+    /// the normal priority go to its deque, where it runs the newest one
+    /// first, and idle workers steal the oldest jobs of the others. This
+    /// suits recursive and fork-join workloads. Disabled by default.
+    ///
+    /// **enabled**: bool - Whether the pool uses work stealing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPoolBuilder;
+    ///
+    /// let pool = WorkerPoolBuilder::new().num_workers(2).work_stealing(true).build();
+    /// let inner = pool.clone();
+    ///
+    /// let handle = pool
+    ///     .submit(move || {
+    ///         let handles: Vec<_> = (0..4).map(|i| inner.submit(move || i * 2).unwrap()).collect();
+    ///         handles.into_iter().map(|h| h.wait().unwrap()).sum::<i32>()
+    ///     })
+    ///     .unwrap();
+    ///
+    /// assert_eq!(Ok(12), handle.wait());
+    /// ```
+    pub fn work_stealing(mut self, enabled: bool) -> WorkerPoolBuilder {
+        self.work_stealing = enabled;
+        self
+    }
+
+    /// Sets what idle workers do while they wait for a job. The default is
+    /// `IdleStrategy::Park`.
+    ///
+    /// **strategy**: IdleStrategy - The idle strategy of every worker.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::{IdleStrategy, WorkerPoolBuilder};
+    ///
+    /// let pool = WorkerPoolBuilder::new()
+    ///     .idle_strategy(IdleStrategy::SpinThenPark { spins: 1000, yields: 10 })
+    ///     .build();
+    ///
+    /// assert_eq!(Ok(3), pool.submit(|| 1 + 2).unwrap().wait());
+    /// ```
+    pub fn idle_strategy(mut self, strategy: IdleStrategy) -> WorkerPoolBuilder {
+        self.idle_strategy = strategy;
+        self
+    }
+
+    /// Sets which job of a priority level workers take first. The default
+    /// is `DispatchOrder::Fifo`. Priority aging looks at the job that would
+    /// be taken next, so the oldest jobs of a LIFO level aren't promoted.
+    ///
+    /// **order**: DispatchOrder - The dispatch order of the shared queue.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::{DispatchOrder, WorkerPoolBuilder};
+    /// use std::sync::mpsc;
+    ///
+    /// let pool = WorkerPoolBuilder::new()
+    ///     .num_workers(1)
+    ///     .dispatch_order(DispatchOrder::Lifo)
+    ///     .build();
+    /// let (started_tx, started_rx) = mpsc::channel();
+    /// let (tx, rx) = mpsc::channel::<()>();
+    /// pool.execute(move || {
+    ///     started_tx.send(()).unwrap();
+    ///     rx.recv().unwrap();
+    /// })
+    /// .unwrap();
+    /// started_rx.recv().unwrap();
+    ///
+    /// let (order_tx, order_rx) = mpsc::channel();
+    /// for i in 0..3 {
+    ///     pool.execute_to(order_tx.clone(), move || i).unwrap();
+    /// }
+    /// tx.send(()).unwrap();
+    /// pool.join();
+    /// assert_eq!(vec![2, 1, 0], order_rx.try_iter().collect::<Vec<_>>());
+    /// ```
+    pub fn dispatch_order(mut self, order: DispatchOrder) -> WorkerPoolBuilder {
+        self.dispatch_order = order;
+        self
+    }
+
+    /// Stops the threads of the workers that waited for a job longer than
+    /// timeout, releasing them. A stopped worker keeps its id, and its
+    /// thread is spawned again when jobs arrive and no other worker is
+    /// free to take them.
+    ///
+    /// **timeout**: Duration - How long a worker stays idle before it
+    /// stops.
+    pub fn idle_timeout(mut self, timeout: Duration) -> WorkerPoolBuilder {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Spawns the worker threads on demand, as jobs arrive and no other
+    /// worker is free to take them, instead of spawning them all when the
+    /// pool is built. A pool that is never used spawns no thread.
+    ///
+    /// **enabled**: bool - Whether workers are spawned lazily.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPoolBuilder;
+    ///
+    /// let pool = WorkerPoolBuilder::new()
+    ///     .num_workers(4)
+    ///     .lazy_spawn(true)
+    ///     .build();
+    /// assert_eq!(0, pool.live_workers());
+    ///
+    /// assert_eq!(Ok(1), pool.submit(|| 1).unwrap().wait());
+    /// assert!(pool.live_workers() >= 1);
+    /// ```
+    pub fn lazy_spawn(mut self, enabled: bool) -> WorkerPoolBuilder {
+        self.options.lazy = enabled;
+        self
+    }
+
+    /// Gives each producer its own lane of jobs with the normal priority,
+    /// and hands out the lanes in round-robin order, so a busy producer
+    /// can't starve the others. Every [`JobSender`] returned by
+    /// [`WorkerPool::sender`] is a producer, shared by its clones, and the
+    /// pool itself is another one. Jobs submitted from a worker skip the
+    /// lanes.
+    ///
+    /// **enabled**: bool - Whether producers take turns.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPoolBuilder;
+    /// use std::sync::mpsc;
+    ///
+    /// let pool = WorkerPoolBuilder::new()
+    ///     .num_workers(1)
+    ///     .fair_scheduling(true)
+    ///     .build();
+    /// let (started_tx, started_rx) = mpsc::channel();
+    /// let (tx, rx) = mpsc::channel::<()>();
+    /// pool.execute(move || {
+    ///     started_tx.send(()).unwrap();
+    ///     rx.recv().unwrap();
+    /// })
+    /// .unwrap();
+    /// started_rx.recv().unwrap();
+    ///
+    /// let (busy, quiet) = (pool.sender(), pool.sender());
+    /// let (order_tx, order_rx) = mpsc::channel();
+    /// for i in 0..3 {
+    ///     let order_tx = order_tx.clone();
+    ///     busy.execute(move || order_tx.send(("busy", i)).unwrap()).unwrap();
+    /// }
+    /// let order_tx = order_tx.clone();
+    /// quiet.execute(move || order_tx.send(("quiet", 0)).unwrap()).unwrap();
+    ///
+    /// tx.send(()).unwrap();
+    /// pool.join();
+    /// let order: Vec<_> = order_rx.try_iter().collect();
+    /// assert_eq!(vec![("busy", 0), ("quiet", 0), ("busy", 1), ("busy", 2)], order);
+    /// ```
+    pub fn fair_scheduling(mut self, enabled: bool) -> WorkerPoolBuilder {
+        self.options.fair = enabled;
+        self
+    }
+
+    /// Limits the number of jobs executed with
+    /// [`WorkerPool::execute_tagged`] and the given tag that run at once.
+    ///
+    /// **tag**: &str - The concurrency group. \
+    /// **max**: usize - The most jobs of the group running at once, at
+    /// least one.
+    pub fn tag_limit(mut self, tag: &str, max: usize) -> WorkerPoolBuilder {
+        self.tag_limits.push((String::from(tag), max));
+        self
+    }
+
+    /// Sets the weight budget shared by the jobs executed with
+    /// [`WorkerPool::execute_weighted`].
+    ///
+    /// **capacity**: usize - The most weight queued or running at once, at
+    /// least one.
+    pub fn weight_budget(mut self, capacity: usize) -> WorkerPoolBuilder {
+        self.options.weight_budget = Some(capacity);
+        self
+    }
+
+    /// Limits the number of jobs started per period with a token bucket.
+    /// See [`RateLimit`] for the settings, and [`RatePolicy`] for where
+    /// the jobs over the limit wait.
+    ///
+    /// **limit**: RateLimit - The settings of the rate limiter.
+    pub fn rate_limit(mut self, limit: RateLimit) -> WorkerPoolBuilder {
+        self.options.rate_limit = Some(limit);
+        self
+    }
+
+    /// Lets a supervisor thread resize the pool with its load, within the
+    /// bounds of the given settings. The pool starts with the minimum
+    /// number of workers, and the number set by `num_workers` is ignored.
+    ///
+    /// **config**: AutoScale - The settings of the autoscaler.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use workerpool_rs::autoscale::AutoScale;
+    /// use workerpool_rs::pool::WorkerPoolBuilder;
+    /// use std::thread;
+    /// use std::time::Duration;
+    ///
+    /// let pool = WorkerPoolBuilder::new()
+    ///     .autoscale(AutoScale::new(1, 4))
+    ///     .build();
+    /// for _ in 0..64 {
+    ///     pool.execute(|| thread::sleep(Duration::from_millis(5))).unwrap();
+    /// }
+    ///
+    /// pool.join();
+    /// assert!(pool.num_workers() > 1);
+    /// ```
+    pub fn autoscale(mut self, config: AutoScale) -> WorkerPoolBuilder {
+        self.autoscale = Some(config);
+        self
+    }
+
+    /// Starts a watchdog that calls on_stuck, on its own thread, once for
+    /// every job running for longer than the configured threshold.
+    ///
+    /// **config**: Watchdog - The settings of the watchdog. \
+    /// **on_stuck**: Fn(StuckJob) - The callback receiving the stuck jobs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPoolBuilder;
+    /// use workerpool_rs::watchdog::Watchdog;
+    /// use std::sync::mpsc;
+    /// use std::sync::Mutex;
+    /// use std::thread;
+    /// use std::time::Duration;
+    ///
+    /// let (tx, rx) = mpsc::channel();
+    /// let tx = Mutex::new(tx);
+    /// let pool = WorkerPoolBuilder::new()
+    ///     .num_workers(1)
+    ///     .watchdog(Watchdog::new(Duration::from_millis(10)), move |stuck| {
+    ///         tx.lock().unwrap().send(stuck).unwrap();
+    ///     })
+    ///     .build();
+    ///
+    /// pool.execute_labeled("sync", || thread::sleep(Duration::from_millis(100)))
+    ///     .unwrap();
+    /// let stuck = rx.recv().unwrap();
+    /// assert_eq!(Some("sync"), stuck.label);
+    /// assert!(stuck.elapsed >= Duration::from_millis(10));
+    /// ```
+    pub fn watchdog<F>(mut self, config: Watchdog, on_stuck: F) -> WorkerPoolBuilder
+    where
+        F: Fn(StuckJob) + Send + Sync + 'static,
+    {
+        self.watchdog = Some((config, Arc::new(on_stuck)));
+        self
+    }
+
+    /// Spawns the workers and returns the WorkerPool.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a thread of the pool can't be spawned. See
+    /// [`try_build`](WorkerPoolBuilder::try_build).
+    pub fn build(self) -> WorkerPool {
+        self.try_build().expect("Cant spawn worker")
+    }
+
+    /// Spawns the workers and returns the WorkerPool, or the error of the
+    /// first thread that couldn't be spawned. The threads already spawned
+    /// are then stopped.
+    ///
+    /// **returns**: `Err(Error::Spawn)` if a thread couldn't be spawned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPoolBuilder;
+    ///
+    /// let pool = WorkerPoolBuilder::new().num_workers(2).try_build().unwrap();
+    /// assert_eq!(Ok(42), pool.submit(|| 42).unwrap().wait());
+    /// ```
+    pub fn try_build(mut self) -> Result<WorkerPool, Error> {
+        self.options.watched = self.watchdog.is_some();
+        if let PanicPolicy::Callback(callback) = &self.options.panic_policy {
+            let callback = Arc::clone(callback);
+            self.options.panic_handler = Some(match self.options.panic_handler.take() {
+                Some(handler) => Arc::new(move |payload: &(dyn Any + Send)| {
+                    handler(payload);
+                    callback(payload);
+                }),
+                None => callback,
+            });
+        }
+        if self.options.mode == ExecutionMode::Inline {
+            self.num_workers = 0;
+            self.autoscale = None;
+        }
+        let num_workers = match &self.autoscale {
+            Some(config) => config.min_workers,
+            None => self.num_workers,
+        };
+        let mut queue = JobQueue::new(self.queue_capacity, self.priority_aging)
+            .idle_strategy(self.idle_strategy)
+            .dispatch_order(self.dispatch_order);
+        if let Some(timeout) = self.idle_timeout {
+            queue = queue.idle_timeout(timeout);
+        }
+        if self.work_stealing {
+            let max_workers = self.autoscale.map_or(num_workers, |c| c.max_workers);
+            queue = queue.work_stealing(max_workers);
+        }
+        let tags = TagGroups::new(self.tag_limits);
+        let shared = Shared::new(queue, tags, self.options);
+        let pool = WorkerPool::with_shared(num_workers, shared)?;
+        if let Some(config) = self.autoscale {
+            let probed = Arc::clone(&pool.shared);
+            let resized = Arc::clone(&pool.shared);
+            *pool
+                .owner
+                .supervisor
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) = Some(Supervisor::new(
+                config,
+                move || Load {
+                    workers: probed.workers().active.len(),
+                    queued: probed.queue.len(),
+                    busy: probed.active.load(Ordering::SeqCst),
+                },
+                move |size| {
+                    // A worker that can't be spawned is retried at the next sample.
+                    let _ = resized.resize(size);
+                },
+            )?);
+        }
+        if let Some((config, on_stuck)) = self.watchdog {
+            let checked = Arc::clone(&pool.shared);
+            *pool
+                .owner
+                .watchdog
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) =
+                Some(Watcher::new(config.interval, move |now| {
+                    checked
+                        .stuck_jobs(&config, now)
+                        .into_iter()
+                        .for_each(|stuck| on_stuck(stuck))
+                })?);
+        }
+        Ok(pool)
+    }
+}
+
+impl Default for WorkerPoolBuilder {
+    fn default() -> WorkerPoolBuilder {
+        WorkerPoolBuilder::new()
+    }
+}
+
+// The producer id of the jobs submitted through the pool itself.
+const POOL_PRODUCER: u64 = 0;
+
+// The prefix of the worker thread names when none is set.
+const DEFAULT_NAME_PREFIX: &str = "workerpool";
+
+// Options used to spawn and run the workers.
+#[derive(Clone, Default)]
+struct WorkerOptions {
+    name_prefix: Option<String>,
+    stack_size: Option<usize>,
+    panic_handler: Option<PanicHandler>,
+    on_start: Option<WorkerHook>,
+    on_stop: Option<WorkerHook>,
+    observer: Option<Arc<dyn PoolObserver>>,
+    restart_policy: RestartPolicy,
+    panic_policy: PanicPolicy,
+    lazy: bool,
+    rate_limit: Option<RateLimit>,
+    miss_handler: Option<MissHandler>,
+    queue_order: QueueOrder,
+    dead_letter: Option<mpsc::Sender<DeadLetter>>,
+    progress: Option<mpsc::Sender<ProgressUpdate>>,
+    saturation: SaturationPolicy,
+    mode: ExecutionMode,
+    fair: bool,
+    weight_budget: Option<usize>,
+    watched: bool,
+    #[cfg(feature = "affinity")]
+    affinity: Option<crate::affinity::Pinning>,
+    #[cfg(feature = "thread-priority")]
+    thread_priority: crate::thread_priority::ThreadPriority,
+}
+
+impl WorkerOptions {
+    // Returns a thread builder for the worker with the given id.
+    fn builder(&self, id: usize) -> thread::Builder {
+        let prefix = self.name_prefix.as_deref().unwrap_or(DEFAULT_NAME_PREFIX);
+        let mut builder = thread::Builder::new().name(format!("{}-{}", prefix, id));
+        if let Some(size) = self.stack_size {
+            builder = builder.stack_size(size);
+        }
+        builder
+    }
+
+    // Sets up the thread of the worker with the given id, once it starts.
+    fn setup(&self, id: usize) {
+        #[cfg(feature = "affinity")]
+        if let Some(pinning) = &self.affinity {
+            let cores = pinning.cores(id);
+            if !cores.is_empty() && !crate::affinity::pin(&cores) {
+                if let Some(observer) = &self.observer {
+                    observer.on_pin_failed(id, &cores);
+                }
+            }
+        }
+        #[cfg(feature = "thread-priority")]
+        crate::thread_priority::apply(self.thread_priority);
+        if let Some(hook) = &self.on_start {
+            hook(id);
+        }
+    }
+
+    // Tears down the thread of the worker with the given id, once it stops
+    // taking jobs.
+    fn teardown(&self, id: usize) {
+        if let Some(hook) = &self.on_stop {
+            hook(id);
+        }
+    }
+}
+
+/// A cloneable handle that submits jobs to a [`WorkerPool`], so producer
+/// threads don't need to share the pool itself. Once the pool is dropped,
+/// every submission fails with `ExecuteError::Disconnected`.
+#[derive(Clone)]
+pub struct JobSender {
+    shared: SharedState,
+    producer: u64,
+}
+
+impl JobSender {
+    /// Executes a job, as [`WorkerPool::execute`].
+    ///
+    /// **f**: A FnOnce closure. \
+    /// **returns**: `Err(ExecuteError)` if the job can't be queued.
+    pub fn execute<F>(&self, f: F) -> Result<(), ExecuteError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.shared
+            .push_from(self.producer, Task::new(f), Priority::Normal)
+    }
+
+    /// Executes a job without blocking, as [`WorkerPool::try_execute`].
+    ///
+    /// **f**: A FnOnce closure. \
+    /// **returns**: `Err(Rejected)` giving the closure back, with
+    /// `ExecuteError::QueueFull` if the queue is full.
+    pub fn try_execute<F>(&self, f: F) -> Result<(), Rejected<F>>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.shared
+            .push_until(None, self.producer, f, Priority::Normal)
+    }
+}
+
+// Dropping the last clone of the pool is the same as a graceful shutdown.
+impl Drop for Owner {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+// State shared between the pool and its workers.
+//
+// queue: JobQueue - the jobs waiting for a worker.\
+// keyed: KeyedLanes - the jobs waiting for a job with the same key.\
+// tags: TagGroups - the jobs waiting for a job with the same tag to finish.\
+// fair: Option<FairLanes> - the jobs waiting for the turn of their
+// producer, in fair mode.\
+// next_producer: AtomicU64 - the producer id of the next JobSender.\
+// weights: Option<WeightBudget> - the weight budget of the weighted jobs,
+// if set.\
+// labels: Mutex<HashMap<&'static str, Arc<LabelCounters>>> - the counters
+// of the labeled jobs, per label.\
+// pending: PendingJobs - the counter of unfinished jobs.\
+// live: AtomicUsize - the number of working threads alive.\
+// panics: AtomicUsize - the number of jobs that panicked.\
+// active: AtomicUsize - the number of jobs being executed.\
+// completed: AtomicUsize - the number of jobs that finished.\
+// submitted: AtomicUsize - the number of jobs accepted.\
+// execution: AtomicU64 - the total time, in nanoseconds, spent running jobs.\
+// execution_histogram: Histogram - the distribution of the running times.\
+// next_job: AtomicU64 - the id of the next job given a context or tracked.\
+// statuses: StatusTable - the status of the tracked jobs.\
+// workers: Mutex<WorkerSet> - the workers of the pool.\
+// awake: AtomicUsize - the number of working threads that take jobs.\
+// elastic: bool - whether workers stop when idle and start on demand.\
+// this: Weak<Shared> - the shared state itself, to spawn workers on demand.\
+// owner: OnceLock<Weak<Owner>> - the owner of the pool, set once built.\
+// limiter: Option<RateLimiter> - the rate limiter, if enabled.\
+// options: WorkerOptions - the options used to spawn workers.
+struct Shared {
+    queue: JobQueue,
+    keyed: KeyedLanes,
+    tags: TagGroups,
+    fair: Option<FairLanes>,
+    next_producer: AtomicU64,
+    weights: Option<WeightBudget>,
+    labels: Mutex<HashMap<&'static str, Arc<LabelCounters>>>,
+    pending: PendingJobs,
+    live: AtomicUsize,
+    panics: AtomicUsize,
+    active: AtomicUsize,
+    completed: AtomicUsize,
+    submitted: AtomicUsize,
+    execution: AtomicU64,
+    execution_histogram: Histogram,
+    next_job: AtomicU64,
+    statuses: StatusTable,
+    workers: Mutex<WorkerSet>,
+    awake: AtomicUsize,
+    elastic: bool,
+    this: Weak<Shared>,
+    owner: OnceLock<Weak<Owner>>,
+    limiter: Option<RateLimiter>,
+    options: WorkerOptions,
+}
+
+// The workers of a pool.
+//
+// active: Vec<Worker> - the workers taking jobs, indexed by id.\
+// retiring: Vec<Worker> - the workers retired by a resize, which may still
+// be finishing their last job.
+#[derive(Default)]
+struct WorkerSet {
+    active: Vec<Worker>,
+    retiring: Vec<Worker>,
+}
+
+impl Shared {
+    fn new(queue: JobQueue, tags: TagGroups, options: WorkerOptions) -> SharedState {
+        Arc::new_cyclic(|this| Shared {
+            elastic: queue.has_idle_timeout() || options.lazy,
+            queue,
+            keyed: KeyedLanes::new(),
+            tags,
+            fair: options.fair.then(FairLanes::new),
+            next_producer: AtomicU64::new(POOL_PRODUCER + 1),
+            weights: options.weight_budget.map(WeightBudget::new),
+            labels: Mutex::new(HashMap::new()),
+            pending: PendingJobs::new(),
+            live: AtomicUsize::new(0),
+            panics: AtomicUsize::new(0),
+            active: AtomicUsize::new(0),
+            completed: AtomicUsize::new(0),
+            submitted: AtomicUsize::new(0),
+            execution: AtomicU64::new(0),
+            execution_histogram: Histogram::new(),
+            next_job: AtomicU64::new(0),
+            statuses: Arc::new(Mutex::new(Statuses::default())),
+            workers: Mutex::new(WorkerSet::default()),
+            awake: AtomicUsize::new(0),
+            this: Weak::clone(this),
+            owner: OnceLock::new(),
+            limiter: options.rate_limit.map(RateLimiter::new),
+            options,
+        })
+    }
+
+    fn workers(&self) -> std::sync::MutexGuard<'_, WorkerSet> {
+        self.workers.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Spawns or retires workers until there are size of them. Retired
+    // workers that already left are forgotten. Lazy workers are only
+    // started by grow. It stops at the first worker that can't be spawned.
+    fn resize(self: &Arc<Self>, size: usize) -> io::Result<()> {
+        let mut workers = self.workers();
+        workers.retiring.retain(|worker| !worker.is_finished());
+        let mut retired = false;
+        while workers.active.len() > size {
+            let worker = workers.active.pop().expect("Cant retire worker");
+            worker.state.retired.store(true, Ordering::SeqCst);
+            workers.retiring.push(worker);
+            retired = true;
+        }
+        while workers.active.len() < size {
+            let worker = Worker::new(workers.active.len());
+            if !self.options.lazy {
+                worker.start(Arc::clone(self))?;
+            }
+            workers.active.push(worker);
+        }
+        if retired {
+            self.queue.wake_all();
+        }
+        Ok(())
+    }
+
+    // Queues a job of the pool itself, as push_from.
+    fn push(&self, job: Task, priority: Priority) -> Result<(), ExecuteError> {
+        self.push_from(POOL_PRODUCER, job, priority)
+    }
+
+    // Queues a job, blocking while a bounded queue is full. A job with the
+    // normal priority submitted from a worker goes to its local deque, and
+    // as a worker blocking on its own queue could never finish, it only
+    // ignores the capacity under SaturationPolicy::Block.
+    fn push_from(&self, producer: u64, job: Task, priority: Priority) -> Result<(), ExecuteError> {
+        self.admit(1);
+        self.pending.increment();
+        if self.options.mode == ExecutionMode::Inline {
+            if self.queue.is_closed() {
+                return self.settle(1, Err(ExecuteError::Disconnected));
+            }
+            self.settle(1, Ok(()))?;
+            self.run_inline(job);
+            return Ok(());
+        }
+        let local = self
+            .current_worker()
+            .filter(|_| priority == Priority::Normal);
+        let blocking = self.options.saturation == SaturationPolicy::Block;
+        let result = match local {
+            Some(id) if blocking => self.queue.push_local(id, job),
+            None if blocking => self
+                .queue
+                .wait_room(None)
+                .map(|()| self.insert(producer, job, priority)),
+            _ => match self.queue.reserve() {
+                Ok(()) => {
+                    match local {
+                        Some(id) => self.queue.insert_local(id, job),
+                        None => self.insert(producer, job, priority),
+                    }
+                    Ok(())
+                }
+                Err(ExecuteError::QueueFull) => return self.saturated(job, priority),
+                Err(error) => Err(error),
+            },
+        };
+        self.settle(1, result)
+    }
+
+    // Inserts a job in the room reserved for it. In fair mode, a job with
+    // the normal priority waits in the lane of its producer, and a turn
+    // takes its place in the queue.
+    fn insert(&self, producer: u64, job: Task, priority: Priority) {
+        match &self.fair {
+            Some(fair) if priority == Priority::Normal => {
+                fair.enqueue(producer, job);
+                let turn = FairTurn {
+                    shared: Weak::clone(&self.this),
+                    ran: false,
+                };
+                self.queue.insert(turn.into_task(), priority);
+            }
+            _ => self.queue.insert(job, priority),
+        }
+    }
+
+    // Applies the saturation policy to a pending job that found the queue
+    // full.
+    fn saturated(&self, job: Task, priority: Priority) -> Result<(), ExecuteError> {
+        match self.options.saturation {
+            SaturationPolicy::Block => self.settle(1, self.queue.push(job, priority)),
+            SaturationPolicy::Error => self.settle(1, Err(ExecuteError::QueueFull)),
+            SaturationPolicy::DropOldest => match self.queue.replace_next(job, priority) {
+                Some(next) => {
+                    drop(next);
+                    self.pending.decrement();
+                    self.settle(1, Ok(()))
+                }
+                None => self.settle(1, Err(ExecuteError::QueueFull)),
+            },
+            SaturationPolicy::DropNewest => {
+                drop(job);
+                self.pending.decrement();
+                Ok(())
+            }
+            SaturationPolicy::CallerRuns => {
+                self.settle(1, Ok(()))?;
+                self.run_inline(job);
+                Ok(())
+            }
+        }
+    }
+
+    // Queues a job by its deadline, blocking while a bounded queue is full.
+    fn push_deadline(&self, job: Task, deadline: Instant) -> Result<(), ExecuteError> {
+        if self.options.mode == ExecutionMode::Inline {
+            return self.push(job, Priority::Normal);
+        }
+        self.admit(1);
+        self.pending.increment();
+        let result = self.queue.push_deadline(job, deadline);
+        self.settle(1, result)
+    }
+
+    // Counts the jobs of a submission as accepted, or as no longer pending
+    // if the submission failed.
+    fn settle(&self, count: usize, result: Result<(), ExecuteError>) -> Result<(), ExecuteError> {
+        match result {
+            Ok(()) => {
+                self.submitted.fetch_add(count, Ordering::SeqCst);
+                if let Some(observer) = &self.options.observer {
+                    let now = Instant::now();
+                    (0..count).for_each(|_| observer.on_submit(now));
+                }
+                if self.elastic {
+                    self.grow();
+                }
+            }
+            Err(_) => self.pending.remove(count),
+        }
+        result
+    }
+
+    // Starts a stopped worker when more jobs are queued than there are
+    // free workers. The awake count is read after the jobs were counted in
+    // the queue, while a stopping worker updates it before checking the
+    // queue, so one of them always sees the other.
+    fn grow(&self) {
+        let free = self
+            .awake
+            .load(Ordering::SeqCst)
+            .saturating_sub(self.active.load(Ordering::SeqCst));
+        if self.queue.len() <= free {
+            return;
+        }
+        let Some(this) = self.this.upgrade() else {
+            return;
+        };
+        let workers = self.workers();
+        let stopped = workers.active.iter().find(|worker| !worker.is_running());
+        if let Some(worker) = stopped {
+            // A worker that can't be spawned stays stopped, and is tried
+            // again by the next submission.
+            let _ = worker.start(this);
+        }
+    }
+
+    // Returns the counters of a label, created on first use.
+    fn label_counters(&self, label: &'static str) -> Arc<LabelCounters> {
+        let mut labels = self.labels.lock().unwrap_or_else(PoisonError::into_inner);
+        Arc::clone(labels.entry(label).or_default())
+    }
+
+    // Starts every stopped worker.
+    fn start_all(&self) {
+        let Some(this) = self.this.upgrade() else {
+            return;
+        };
+        let workers = self.workers();
+        for worker in workers.active.iter().filter(|worker| !worker.is_running()) {
+            let _ = worker.start(Arc::clone(&this));
+        }
+    }
+
+    // Takes the next job for a worker, or returns None once it must leave.
+    fn next_job(&self, id: usize, retired: &AtomicBool) -> Option<Task> {
+        loop {
+            if let Some(job) = self.queue.pop(id, retired) {
+                return Some(job);
+            }
+            if self.queue.is_closed() || retired.load(Ordering::SeqCst) {
+                self.awake.fetch_sub(1, Ordering::SeqCst);
+                return None;
+            }
+            // The idle timeout expired, but a job may have arrived since.
+            let workers = self.workers();
+            self.awake.fetch_sub(1, Ordering::SeqCst);
+            if self.queue.len() > 0 {
+                self.awake.fetch_add(1, Ordering::SeqCst);
+                continue;
+            }
+            if let Some(worker) = workers.active.get(id) {
+                worker.running.store(false, Ordering::SeqCst);
+            }
+            return None;
+        }
+    }
+
+    // Flags the jobs running for longer than the watchdog threshold and
+    // returns the ones not reported yet, marking their workers as suspect
+    // if configured.
+    fn stuck_jobs(&self, config: &Watchdog, now: Instant) -> Vec<StuckJob> {
+        let workers = self.workers();
+        let mut stuck = Vec::new();
+        for worker in workers.active.iter().chain(&workers.retiring) {
+            let mut running = worker
+                .state
+                .running
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            let Some(job) = running.as_mut() else {
+                continue;
+            };
+            let elapsed = now.saturating_duration_since(job.started);
+            if job.flagged || elapsed <= config.max_execution {
+                continue;
+            }
+            job.flagged = true;
+            if config.mark_suspect {
+                worker.state.suspect.store(true, Ordering::SeqCst);
+            }
+            stuck.push(StuckJob {
+                worker: worker.id,
+                job: job.job,
+                label: job.label,
+                elapsed,
+            });
+        }
+        stuck
+    }
+
+    // Runs one queued job on the calling worker, which waits for nested
+    // jobs. The worker is already counted as active for the job it runs.
+    // A job asking for the thread to be replaced has it replaced once the
+    // running job returns. Returns false if there was no job to run.
+    fn help(&self, worker: usize) -> bool {
+        let Some(job) = self.queue.try_pop(worker) else {
+            return false;
+        };
+        let _guard = JobGuard(&self.pending);
+        if self.run_job(worker, None, job) {
+            CURRENT_STATE.with(|state| {
+                if let Some(state) = &*state.borrow() {
+                    state.replace.store(true, Ordering::SeqCst);
+                }
+            });
+        }
+        true
+    }
+
+    // Queues a job that is already counted as pending, or runs it on the
+    // calling thread in an inline pool.
+    fn dispatch(&self, job: Task) -> Result<(), ExecuteError> {
+        if self.options.mode == ExecutionMode::Threaded {
+            return self.queue.push(job, Priority::Normal);
+        }
+        if self.queue.is_closed() {
+            return Err(ExecuteError::Disconnected);
+        }
+        self.run_inline(job);
+        Ok(())
+    }
+
+    // Queues the next job of a lane, which was accepted while the lane was
+    // busy and so ignores the capacity, or runs it on the calling thread in
+    // an inline pool.
+    fn hand_off(&self, job: Task) {
+        match self.options.mode {
+            ExecutionMode::Threaded => self.queue.force_push(job, Priority::Normal),
+            ExecutionMode::Inline => self.run_inline(job),
+        }
+    }
+
+    // Runs a job of an inline pool on the submitting thread, as worker 0,
+    // which sees the pool as its current one while the job runs.
+    fn run_inline(&self, job: Task) {
+        let shared = Some(Weak::clone(&self.this));
+        let current = CURRENT_SHARED.with(|current| current.replace(shared));
+        let _guard = JobGuard(&self.pending);
+        self.active.fetch_add(1, Ordering::SeqCst);
+        self.run_job(0, None, job);
+        self.active.fetch_sub(1, Ordering::SeqCst);
+        CURRENT_SHARED.with(|shared| *shared.borrow_mut() = current);
+    }
+
+    // Runs a pending job on the calling thread as the given worker, and
+    // reports it to the observer, the stats, the panic handler, the panic
+    // policy and the dead letter channel. The state of the worker is only
+    // given when the job isn't nested in another one, whose labels and
+    // panic watch are restored afterwards. Returns true if the thread must
+    // be replaced, as the job panicked under PanicPolicy::Restart.
+    fn run_job(&self, worker: usize, state: Option<&WorkerState>, job: Task) -> bool {
+        let observer = self.options.observer.as_deref();
+        let started = Instant::now();
+        let label = job.label();
+        let mut info = JobInfo {
+            worker,
+            label,
+            at: started,
+        };
+        if let Some(observer) = observer {
+            observer.on_start(&info);
+        }
+        #[cfg(feature = "tracing")]
+        let span = job.span(worker, started);
+        let current = (
+            CURRENT_JOB.with(|current| current.replace(None)),
+            CURRENT_LABEL.with(|current| current.replace(label)),
+        );
+        let watching = handle::is_watching();
+        if let Some(state) = state {
+            *state
+                .heartbeat
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) = Some(started);
+            // Only the watchdog reads the running job, so it isn't tracked
+            // without one.
+            if self.options.watched {
+                *state.running.lock().unwrap_or_else(PoisonError::into_inner) = Some(RunningJob {
+                    started,
+                    job: None,
+                    label,
+                    flagged: false,
+                });
+            }
+        }
+        handle::watch_panics();
+        let result = {
+            #[cfg(feature = "tracing")]
+            let _entered = span.enter();
+            panic::catch_unwind(AssertUnwindSafe(|| job.run()))
+        };
+        #[cfg(feature = "tracing")]
+        if result.is_err() {
+            span.record("panicked", true);
+        }
+        let finished = Instant::now();
+        if let Some(state) = state {
+            if self.options.watched {
+                *state.running.lock().unwrap_or_else(PoisonError::into_inner) = None;
+                state.suspect.store(false, Ordering::SeqCst);
+            }
+            *state
+                .heartbeat
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) = Some(finished);
+            state.completed.fetch_add(1, Ordering::Relaxed);
+        }
+        let elapsed = finished - started;
+        self.execution
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+        self.execution_histogram.record(elapsed);
+        self.completed.fetch_add(1, Ordering::Relaxed);
+
+        info.at = finished;
+        let replace = match result {
+            Ok(()) => {
+                handle::deliver_panic(None);
+                if let Some(observer) = observer {
+                    observer.on_complete(&info, elapsed);
+                }
+                false
+            }
+            Err(payload) => self.panicked(&info, payload, started),
+        };
+        CURRENT_JOB.with(|job| job.set(current.0));
+        CURRENT_LABEL.with(|label| label.set(current.1));
+        if watching {
+            handle::watch_panics();
+        }
+        replace
+    }
+
+    // Reports a job that panicked and applies the panic policy. Returns
+    // true if the thread must be replaced.
+    fn panicked(&self, info: &JobInfo, payload: Box<dyn Any + Send>, started: Instant) -> bool {
+        self.panics.fetch_add(1, Ordering::SeqCst);
+        if let Some(observer) = self.options.observer.as_deref() {
+            observer.on_panic(info, payload.as_ref());
+        }
+        if let Some(handler) = &self.options.panic_handler {
+            handler(payload.as_ref());
+        }
+        let payload = handle::deliver_panic(Some(payload)).unwrap_or_else(|| Box::new(()));
+        if let Some(dead_letter) = &self.options.dead_letter {
+            let current = CURRENT_JOB.with(Cell::take);
+            let (job, attempts, started) = match current {
+                Some((job, attempts, first)) => (Some(job), attempts, first),
+                None => (None, 1, started),
+            };
+            let _ = dead_letter.send(DeadLetter {
+                job,
+                label: info.label,
+                failure: Failure::Panic(payload),
+                attempts,
+                started,
+                failed: info.at,
+            });
+        }
+        match self.options.panic_policy {
+            PanicPolicy::Restart => true,
+            PanicPolicy::AbortPool => {
+                self.queue.close();
+                self.discard();
+                false
+            }
+            PanicPolicy::Ignore | PanicPolicy::Callback(_) => false,
+        }
+    }
+
+    // Returns the id of the calling worker if it belongs to this pool.
+    fn current_worker(&self) -> Option<usize> {
+        let current = CURRENT_WORKER.with(Cell::get)?;
+        (current.0 == self as *const Shared as usize).then_some(current.1)
+    }
+
+    // Queues a closure, blocking until the deadline while a bounded queue
+    // is full, or not at all without a deadline. A rejected closure is
+    // given back.
+    fn push_until<F>(
+        &self,
+        deadline: Option<Instant>,
+        producer: u64,
+        f: F,
+        priority: Priority,
+    ) -> Result<(), Rejected<F>>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let reject = |error, job| Err(Rejected { error, job });
+        if self.options.mode == ExecutionMode::Inline {
+            if self.queue.is_closed() {
+                return reject(ExecuteError::Disconnected, f);
+            }
+            let _ = self.push_from(producer, Task::new(f), priority);
+            return Ok(());
+        }
+        if let Some(limiter) = self.blocking_limiter() {
+            match deadline {
+                Some(deadline) if !limiter.acquire_until(deadline) => {
+                    return reject(ExecuteError::Timeout, f)
+                }
+                None if !limiter.try_acquire() => return reject(ExecuteError::QueueFull, f),
+                _ => {}
+            }
+        }
+        let reserved = match deadline {
+            Some(deadline) => self.queue.wait_room(Some(deadline)),
+            None => self.queue.reserve(),
+        };
+        if let Err(error) = reserved {
+            return reject(error, f);
+        }
+        self.pending.increment();
+        self.insert(producer, Task::new(f), priority);
+        let _ = self.settle(1, Ok(()));
+        Ok(())
+    }
+
+    // Queues every job. An unbounded queue takes the whole batch at once,
+    // while a bounded one takes the jobs one by one as room frees up.
+    fn push_batch(&self, jobs: Vec<Job>, priority: Priority) -> Result<(), ExecuteError> {
+        if self.queue.is_bounded() || self.options.mode == ExecutionMode::Inline {
+            return jobs
+                .into_iter()
+                .try_for_each(|job| self.push(Task::from(job), priority));
+        }
+        let count = jobs.len();
+        self.admit(count);
+        self.pending.add(count);
+        let tasks = jobs.into_iter().map(Task::from).collect();
+        self.settle(count, self.queue.push_batch(tasks, priority))
+    }
+
+    // Returns the rate limiter if submitters must wait for it.
+    fn blocking_limiter(&self) -> Option<&RateLimiter> {
+        self.limiter
+            .as_ref()
+            .filter(|limiter| limiter.policy == RatePolicy::Block)
+    }
+
+    // Blocks a submitter until count jobs may start, under the blocking
+    // rate policy.
+    fn admit(&self, count: usize) {
+        if let Some(limiter) = self.blocking_limiter() {
+            (0..count).for_each(|_| limiter.acquire());
+        }
+    }
+
+    // Blocks a worker until its next job may start, under the queueing
+    // rate policy.
+    fn throttle(&self) {
+        if let Some(limiter) = &self.limiter {
+            if limiter.policy == RatePolicy::Queue {
+                limiter.acquire();
+            }
+        }
+    }
+
+    // Removes every queued job, which won't be executed anymore.
+    fn discard(&self) -> Vec<Task> {
+        let jobs = self.queue.drain();
+        self.pending.remove(jobs.len());
+        jobs
+    }
+}
+
+// Runs a keyed job and then queues the next job with the same key. If the
+// runner is dropped without running, the waiting jobs of its key are
+// dropped too.
+struct KeyedRunner {
+    shared: SharedState,
+    lane: u64,
+    job: Option<Job>,
+}
+
+impl KeyedRunner {
+    fn new(shared: SharedState, lane: u64, job: Job) -> KeyedRunner {
+        KeyedRunner {
+            shared,
+            lane,
+            job: Some(job),
+        }
+    }
+
+    fn into_task(self) -> Task {
+        Task::new(move || {
+            let mut runner = self;
+            if let Some(job) = runner.job.take() {
+                job();
+            }
+        })
+    }
+}
+
+impl Drop for KeyedRunner {
+    fn drop(&mut self) {
+        if self.job.is_some() {
+            for _ in self.shared.keyed.remove(self.lane) {
+                self.shared.pending.decrement();
+            }
+        } else if let Some(next) = self.shared.keyed.next(self.lane) {
+            let runner = KeyedRunner::new(Arc::clone(&self.shared), self.lane, next);
+            self.shared.hand_off(runner.into_task());
+        }
+    }
+}
+
+// Runs a tagged job and then queues the next waiting job with the same
+// tag. If the runner is dropped without running, the waiting jobs of its
+// tag are dropped too.
+struct TaggedRunner {
+    shared: SharedState,
+    tag: String,
+    job: Option<Job>,
+}
+
+impl TaggedRunner {
+    fn new(shared: SharedState, tag: &str, job: Job) -> TaggedRunner {
+        TaggedRunner {
+            shared,
+            tag: String::from(tag),
+            job: Some(job),
+        }
+    }
+
+    fn into_task(self) -> Task {
+        Task::new(move || {
+            let mut runner = self;
+            if let Some(job) = runner.job.take() {
+                job();
+            }
+        })
+    }
+}
+
+impl Drop for TaggedRunner {
+    fn drop(&mut self) {
+        if self.job.is_some() {
+            for _ in self.shared.tags.remove(&self.tag) {
+                self.shared.pending.decrement();
+            }
+        } else if let Some(next) = self.shared.tags.next(&self.tag) {
+            let runner = TaggedRunner::new(Arc::clone(&self.shared), &self.tag, next);
+            self.shared.hand_off(runner.into_task());
+        }
+    }
+}
+
+// Runs a weighted job and then gives its weight back, queueing the waiting
+// jobs that fit. If the runner is dropped without running once the queue
+// is closed, the waiting jobs are dropped too.
+struct WeightedRunner {
+    shared: SharedState,
+    weight: usize,
+    job: Option<Job>,
+}
+
+impl WeightedRunner {
+    fn new(shared: SharedState, weight: usize, job: Job) -> WeightedRunner {
+        WeightedRunner {
+            shared,
+            weight,
+            job: Some(job),
+        }
+    }
+
+    fn into_task(self) -> Task {
+        Task::new(move || {
+            let mut runner = self;
+            if let Some(job) = runner.job.take() {
+                job();
+            }
+        })
+    }
+}
+
+impl Drop for WeightedRunner {
+    fn drop(&mut self) {
+        let Some(budget) = &self.shared.weights else {
+            return;
+        };
+        if self.job.is_some() && self.shared.queue.is_closed() {
+            for _ in budget.remove(self.weight) {
+                self.shared.pending.decrement();
+            }
+            return;
+        }
+        for (weight, next) in budget.release(self.weight) {
+            let runner = WeightedRunner::new(Arc::clone(&self.shared), weight, next);
+            self.shared.hand_off(runner.into_task());
+        }
+    }
+}
+
+// Runs the oldest job of the producer whose turn it is. Every job waiting
+// in a producer lane has one turn queued, so a turn dropped without
+// running drops a waiting job too.
+struct FairTurn {
+    shared: Weak<Shared>,
+    ran: bool,
+}
+
+impl FairTurn {
+    fn into_task(self) -> Task {
+        Task::new(move || {
+            let mut turn = self;
+            turn.ran = true;
+            if let Some(job) = turn.next() {
+                job.run();
+            }
+        })
+    }
+
+    fn next(&self) -> Option<Task> {
+        let shared = self.shared.upgrade()?;
+        shared.fair.as_ref()?.next()
+    }
+}
+
+impl Drop for FairTurn {
+    fn drop(&mut self) {
+        if !self.ran {
+            drop(self.next());
+        }
+    }
+}
+
+// A job retried by submit_retry. It only holds a weak reference to the
+// pool, so pending retries don't keep a dropped pool alive.
+struct Retry<F, T: Send + 'static, E: Send + 'static> {
+    id: JobId,
+    started: Option<Instant>,
+    owner: Weak<Owner>,
+    policy: RetryPolicy,
+    attempts: u32,
+    token: CancellationToken,
+    f: F,
+    tx: Completer<Result<T, Exhausted<E>>>,
+}
+
+impl<F, T, E> Retry<F, T, E>
+where
+    F: FnMut() -> Result<T, E> + Send + 'static,
+    T: Send + 'static,
+    E: std::fmt::Debug + Send + 'static,
+{
+    // Makes an attempt, and delivers its result or schedules the next one.
+    fn attempt(mut self) {
+        if self.token.is_cancelled() {
+            return;
+        }
+        self.attempts += 1;
+        let started = *self.started.get_or_insert_with(Instant::now);
+        set_current_job(self.id, self.attempts, started);
+        let error = match (self.f)() {
+            Ok(value) => {
+                let _ = self.tx.send(Ok(value));
+                return;
+            }
+            Err(error) => error,
+        };
+        let owner = self.owner.upgrade();
+        if self.attempts >= self.policy.max_attempts {
+            let attempts = self.attempts;
+            let dead_letter = owner.and_then(|owner| owner.shared.options.dead_letter.clone());
+            if let Some(dead_letter) = dead_letter {
+                let _ = dead_letter.send(DeadLetter {
+                    job: Some(self.id),
+                    label: current_label(),
+                    failure: Failure::Error(format!("{:?}", error)),
+                    attempts,
+                    started,
+                    failed: Instant::now(),
+                });
+            }
+            let _ = self.tx.send(Err(Exhausted { attempts, error }));
+            return;
+        }
+        let at = Instant::now() + self.policy.delay(self.attempts);
+        if let Some(owner) = owner {
+            let pool = WorkerPool {
+                shared: Arc::clone(&owner.shared),
+                owner,
+            };
+            pool.with_scheduler(|scheduler| {
+                scheduler.schedule(at, Box::new(move || self.attempt()))
+            });
+        }
+    }
+}
+
+// Counts the jobs that were submitted but are not finished yet. The
+// counter is atomic, and the lock is only taken when a thread waits for
+// it to get back to zero.
+//
+// count: AtomicUsize - the number of unfinished jobs.\
+// waiters: AtomicUsize - the number of threads waiting for zero.\
+// lock: Mutex<()> - taken to park and wake up the waiters.\
+// zero: Condvar - notified every time the counter gets back to zero.
+pub(crate) struct PendingJobs {
+    count: AtomicUsize,
+    waiters: AtomicUsize,
+    lock: Mutex<()>,
+    zero: Condvar,
+}
+
+impl PendingJobs {
+    pub(crate) fn new() -> PendingJobs {
+        PendingJobs {
+            count: AtomicUsize::new(0),
+            waiters: AtomicUsize::new(0),
+            lock: Mutex::new(()),
+            zero: Condvar::new(),
+        }
+    }
+
+    pub(crate) fn increment(&self) {
+        self.add(1);
+    }
+
+    pub(crate) fn decrement(&self) {
+        self.remove(1);
+    }
+
+    fn add(&self, count: usize) {
+        self.count.fetch_add(count, Ordering::SeqCst);
+    }
+
+    fn remove(&self, count: usize) {
+        let last = self.count.fetch_sub(count, Ordering::SeqCst) == count;
+        if last && self.waiters.load(Ordering::SeqCst) > 0 {
+            let _lock = self.lock.lock().unwrap_or_else(PoisonError::into_inner);
+            self.zero.notify_all();
+        }
+    }
+
+    pub(crate) fn wait_zero(&self) {
+        self.wait_zero_until(None);
+    }
+
+    // Returns false if the counter didn't reach zero before the timeout.
+    pub(crate) fn wait_zero_timeout(&self, timeout: Duration) -> bool {
+        self.wait_zero_until(Some(Instant::now() + timeout))
+    }
+
+    fn wait_zero_until(&self, deadline: Option<Instant>) -> bool {
+        if self.count.load(Ordering::SeqCst) == 0 {
+            return true;
+        }
+        let mut lock = self.lock.lock().unwrap_or_else(PoisonError::into_inner);
+        self.waiters.fetch_add(1, Ordering::SeqCst);
+        let reached = loop {
+            if self.count.load(Ordering::SeqCst) == 0 {
+                break true;
+            }
+            lock = match deadline {
+                None => self.zero.wait(lock).unwrap_or_else(PoisonError::into_inner),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break false;
+                    }
+                    self.zero
+                        .wait_timeout(lock, deadline - now)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0
+                }
+            };
+        };
+        self.waiters.fetch_sub(1, Ordering::SeqCst);
+        reached
+    }
+}
+
+// The statuses of the tracked jobs. The finished ones are kept in the
+// order they finished, until FINISHED_STATUSES more finished.
+//
+// statuses: HashMap<JobId, JobStatus> - the status of each tracked job.\
+// finished: VecDeque<JobId> - the finished jobs, oldest first.
+#[derive(Default)]
+struct Statuses {
+    statuses: HashMap<JobId, JobStatus>,
+    finished: VecDeque<JobId>,
+}
+
+impl Statuses {
+    fn get(&self, id: JobId) -> Option<JobStatus> {
+        self.statuses.get(&id).copied()
+    }
+
+    // Records a status, forgetting the oldest finished job once too many
+    // finished.
+    fn set(&mut self, id: JobId, status: JobStatus) {
+        self.statuses.insert(id, status);
+        if !status.is_finished() {
+            return;
+        }
+        self.finished.push_back(id);
+        while self.finished.len() > FINISHED_STATUSES {
+            if let Some(oldest) = self.finished.pop_front() {
+                self.statuses.remove(&oldest);
+            }
+        }
+    }
+
+    fn remove(&mut self, id: JobId) -> Option<JobStatus> {
+        let status = self.statuses.remove(&id)?;
+        if status.is_finished() {
+            self.finished.retain(|finished| *finished != id);
+        }
+        Some(status)
+    }
+}
+
+// Tracks the run of a job submitted with WorkerPool::submit_tracked,
+// recording whether it completed or panicked when dropped.
+struct TrackedRun {
+    statuses: StatusTable,
+    id: JobId,
+}
+
+impl TrackedRun {
+    // Marks the job as running, unless it was cancelled.
+    fn start(&self) -> bool {
+        let mut statuses = self.statuses.lock().unwrap_or_else(PoisonError::into_inner);
+        if statuses.get(self.id) != Some(JobStatus::Queued) {
+            return false;
+        }
+        statuses.set(self.id, JobStatus::Running);
+        true
+    }
+}
+
+impl Drop for TrackedRun {
+    fn drop(&mut self) {
+        let mut statuses = self.statuses.lock().unwrap_or_else(PoisonError::into_inner);
+        if statuses.get(self.id) == Some(JobStatus::Running) {
+            let status = if thread::panicking() {
+                JobStatus::Panicked
+            } else {
+                JobStatus::Completed
+            };
+            statuses.set(self.id, status);
+        }
+    }
+}
+
+// Records the execution of a labeled job once it returns or panics.
+struct LabelTimer {
+    counters: Arc<LabelCounters>,
+    started: Instant,
+}
+
+impl Drop for LabelTimer {
+    fn drop(&mut self) {
+        self.counters
+            .finish(self.started.elapsed(), thread::panicking());
+    }
+}
+
+// Marks a job as finished when dropped, so the pending counter stays
+// right even if the job unwinds.
+struct JobGuard<'a>(&'a PendingJobs);
+
+impl Drop for JobGuard<'_> {
+    fn drop(&mut self) {
+        self.0.decrement();
+    }
+}
+
+// Implements Debug for WorkerPool with its counters, as the workers and
+// their threads are not Debug.
+impl std::fmt::Debug for WorkerPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WorkerPool")
+            .field("workers", &self.num_workers())
+            .field("live_workers", &self.live_workers())
+            .field("queued_jobs", &self.queued_jobs())
+            .field("active_jobs", &self.active_jobs())
+            .field("completed_jobs", &self.completed_jobs())
+            .finish()
+    }
+}
+
+// Implements Display for WorkerPool. This is usefull as we can able
+// to compare and make unit tests more easily.
+impl Display for WorkerPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut buffer = String::new();
+        for i in &self.shared.workers().active {
+            buffer.push_str(&i.to_string());
+        }
+        write!(f, "workers[] = {}", buffer)
+    }
+}
+
+// A structure that holds an id and thread handle.
+//
+// id: usize - An id for worker indentification.\
+// handle: HandleSlot - the handle of the current working thread, which is
+// replaced when the thread is respawned.\
+// state: Arc<WorkerState> - the state shared with its working threads.\
+// running: AtomicBool - whether the worker has a working thread, which is
+// cleared when the thread stops on the idle timeout.
+struct Worker {
+    id: usize,
+    handle: HandleSlot,
+    state: Arc<WorkerState>,
+    running: AtomicBool,
+}
+
+// The state a worker shares with its working threads.
+//
+// retired: AtomicBool - set when a resize tells the worker to leave.\
+// busy: AtomicBool - whether the worker is running a job.\
+// completed: AtomicUsize - the number of jobs the worker ran.\
+// running: Mutex<Option<RunningJob>> - the job the worker is running.\
+// suspect: AtomicBool - set by the watchdog while the job is stuck.\
+// heartbeat: Mutex<Option<Instant>> - when the worker last started or
+// finished a job.\
+// replace: AtomicBool - set when a job run while the worker waited for
+// nested jobs asked for the thread to be replaced.
+#[derive(Default)]
+struct WorkerState {
+    retired: AtomicBool,
+    busy: AtomicBool,
+    completed: AtomicUsize,
+    running: Mutex<Option<RunningJob>>,
+    suspect: AtomicBool,
+    heartbeat: Mutex<Option<Instant>>,
+    replace: AtomicBool,
+}
+
+// The job a worker is running, as the watchdog sees it.
+//
+// started: Instant - when the job started.\
+// job: Option<JobId> - the id of the job, once it is known.\
+// label: Option<&'static str> - the label of the job, once it is known.\
+// flagged: bool - whether the watchdog reported the job.
+struct RunningJob {
+    started: Instant,
+    job: Option<JobId>,
+    label: Option<&'static str>,
+    flagged: bool,
+}
+
+impl Worker {
+    // Constructs a new Worker, without a working thread.
+    //
+    // id: usize - Worker identificator.
+    fn new(id: usize) -> Worker {
+        Worker {
+            id,
+            handle: Arc::new(Mutex::new(None)),
+            state: Arc::new(WorkerState::default()),
+            running: AtomicBool::new(false),
+        }
+    }
+
+    // Spawns the working thread. The worker stays stopped if it can't.
+    //
+    // shared: SharedState - the state shared with the pool.
+    fn start(&self, shared: SharedState) -> io::Result<()> {
+        self.running.store(true, Ordering::SeqCst);
+        shared.live.fetch_add(1, Ordering::SeqCst);
+        shared.awake.fetch_add(1, Ordering::SeqCst);
+        let slot = Arc::clone(&self.handle);
+        let spawned = Worker::spawn(
+            self.id,
+            Arc::clone(&shared),
+            slot,
+            Arc::clone(&self.state),
+            0,
+        );
+        if spawned.is_err() {
+            self.running.store(false, Ordering::SeqCst);
+            shared.live.fetch_sub(1, Ordering::SeqCst);
+            shared.awake.fetch_sub(1, Ordering::SeqCst);
+        }
+        spawned
+    }
+
+    fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    // Spawns a working thread and stores its handle in slot. The slot stays
+    // locked until the handle is stored, so a thread that dies right away
+    // can't have its replacement overwritten. The sentinel is only built on
+    // the new thread, so a thread that couldn't be spawned has none.
+    fn spawn(
+        id: usize,
+        shared: SharedState,
+        slot: HandleSlot,
+        state: Arc<WorkerState>,
+        restarts: usize,
+    ) -> io::Result<()> {
+        let builder = shared.options.builder(id);
+        let mut current = slot.lock().unwrap_or_else(PoisonError::into_inner);
+        let sentinel_slot = Arc::clone(&slot);
+        let handle = builder.spawn(move || {
+            let mut sentinel = Sentinel {
+                id,
+                shared,
+                slot: sentinel_slot,
+                state,
+                restarts,
+                replace: false,
+            };
+            sentinel.shared.options.setup(sentinel.id);
+            sentinel.replace = run(&sentinel.shared, sentinel.id, &sentinel.state);
+            sentinel.shared.options.teardown(sentinel.id);
+        })?;
+        *current = Some(handle);
+        Ok(())
+    }
+
+    // Checks whether the working thread has ended.
+    fn is_finished(&self) -> bool {
+        let handle = self.handle.lock().unwrap_or_else(PoisonError::into_inner);
+        handle.as_ref().is_none_or(|handle| handle.is_finished())
+    }
+
+    // Joins the working thread, and then any thread respawned in its place.
+    fn join(&self) {
+        loop {
+            let handle = self
+                .handle
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .take();
+            match handle {
+                Some(handle) => {
+                    let _ = handle.join();
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+thread_local! {
+    // The address of the shared state and the id of the worker running on
+    // this thread, if any.
+    static CURRENT_WORKER: Cell<Option<(usize, usize)>> = const { Cell::new(None) };
+
+    // The id, attempt count and first start of the job running on this
+    // thread, for the jobs that have an id.
+    static CURRENT_JOB: Cell<Option<(JobId, u32, Instant)>> = const { Cell::new(None) };
+
+    // The label of the job running on this thread, if it has one.
+    static CURRENT_LABEL: Cell<Option<&'static str>> = const { Cell::new(None) };
+
+    // The state of the worker running on this thread, if any.
+    static CURRENT_STATE: RefCell<Option<Arc<WorkerState>>> = const { RefCell::new(None) };
+
+    // The shared state of the pool running on this thread, if any.
+    static CURRENT_SHARED: RefCell<Option<Weak<Shared>>> = const { RefCell::new(None) };
+}
+
+/// Returns the label of the job running on the calling thread, as given to
+/// [`WorkerPool::execute_labeled`], or None if the job has no label or the
+/// thread is not a worker.
+pub fn current_label() -> Option<&'static str> {
+    CURRENT_LABEL.with(Cell::get)
+}
+
+// Records the job running on this thread, so a panic can be dead lettered
+// with its id.
+fn set_current_job(id: JobId, attempts: u32, started: Instant) {
+    CURRENT_JOB.with(|current| current.set(Some((id, attempts, started))));
+    update_running(|running| running.job = Some(id));
+}
+
+// Updates the job running on this thread, if the thread is a worker.
+fn update_running<F: FnOnce(&mut RunningJob)>(f: F) {
+    CURRENT_STATE.with(|state| {
+        if let Some(state) = &*state.borrow() {
+            let mut running = state.running.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Some(running) = running.as_mut() {
+                f(running);
+            }
+        }
+    });
+}
+
+// Takes jobs from the queue and runs them until the queue is closed, the
+// worker is retired or it stops on the idle timeout. Returns true if the
+// thread must be replaced, as a job panicked under PanicPolicy::Restart.
+fn run(shared: &Shared, id: usize, state: &Arc<WorkerState>) -> bool {
+    CURRENT_WORKER.with(|current| current.set(Some((shared as *const Shared as usize, id))));
+    CURRENT_STATE.with(|current| *current.borrow_mut() = Some(Arc::clone(state)));
+    CURRENT_SHARED.with(|current| *current.borrow_mut() = Some(Weak::clone(&shared.this)));
+    while let Some(job) = shared.next_job(id, &state.retired) {
+        let _guard = JobGuard(&shared.pending);
+        shared.throttle();
+        shared.active.fetch_add(1, Ordering::SeqCst);
+        state.busy.store(true, Ordering::Relaxed);
+        let replace = shared.run_job(id, Some(state), job);
+        state.busy.store(false, Ordering::Relaxed);
+        shared.active.fetch_sub(1, Ordering::SeqCst);
+        if replace || state.replace.swap(false, Ordering::SeqCst) {
+            return true;
+        }
+    }
+    false
+}
+
+// Lives on a working thread and, when the thread dies unwinding, spawns a
+// replacement with the same id if the restart policy allows it. When the
+// last working thread dies that way, or its replacement can't be spawned,
+// the queue is closed and its jobs dropped. A sentinel marked to replace
+// its thread always tries to spawn one.
+struct Sentinel {
+    id: usize,
+    shared: SharedState,
+    slot: HandleSlot,
+    state: Arc<WorkerState>,
+    restarts: usize,
+    replace: bool,
+}
+
+impl Drop for Sentinel {
+    fn drop(&mut self) {
+        let restarts = if self.replace {
+            Some(self.restarts)
+        } else if thread::panicking() && self.shared.options.restart_policy.allows(self.restarts) {
+            Some(self.restarts + 1)
+        } else {
+            None
+        };
+        let replaced = restarts.is_some_and(|restarts| {
+            Worker::spawn(
+                self.id,
+                Arc::clone(&self.shared),
+                Arc::clone(&self.slot),
+                Arc::clone(&self.state),
+                restarts,
+            )
+            .is_ok()
+        });
+        if replaced {
+            return;
+        }
+        let died = thread::panicking() || restarts.is_some();
+        if self.shared.live.fetch_sub(1, Ordering::SeqCst) == 1 && died {
+            self.shared.queue.close();
+            self.shared.discard();
+        }
+    }
+}
+
+// Implements Display for Worker as this simplifys test writing.
+impl Display for Worker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = if !self.is_running() {
+            "stopped"
+        } else if self.state.busy.load(Ordering::SeqCst) {
+            "busy"
+        } else {
+            "idle"
+        };
+        let completed = self.state.completed.load(Ordering::SeqCst);
+        write!(f, "(id: {}, {}, completed: {})", self.id, state, completed)
+    }
+}
+
+// This sections are the beginning of workerpool module unit tests.
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use crate::handle::JobError;
+
+    // Returns the shared state of a pool with an unbounded queue.
+    fn new_shared() -> SharedState {
+        let queue = JobQueue::new(None, DEFAULT_PRIORITY_AGING);
+        Shared::new(queue, TagGroups::new(None), WorkerOptions::default())
+    }
+
+    #[test]
+    fn worker_should_return_new() {
+        let shared = new_shared();
+        let w = Worker::new(1);
+        assert_eq!("(id: 1, stopped, completed: 0)", w.to_string());
+        w.start(Arc::clone(&shared)).unwrap();
+        assert_eq!("(id: 1, idle, completed: 0)", w.to_string());
+        shared.queue.close();
+    }
+
+    #[test]
+    fn workerpool_should_return_new() {
+        let expected = "workers[] = (id: 0, idle, completed: 0)(id: 1, idle, completed: 0)";
+        let pool = WorkerPool::new(2);
+        assert_eq!(expected, pool.to_string());
+    }
+
+    #[test]
+    fn workerpool_should_execute_job_succeed() {
+        let pool = WorkerPool::new(1);
+        for _ in 0..10000 {
+            pool.execute(|| {
+                let _sum = 3 + 1;
+            })
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn workerpool_should_execute_non_sync_job() {
+        let pool = WorkerPool::new(1);
+        let (tx, rx) = mpsc::channel();
+        let cell = std::cell::Cell::new(41);
+        pool.execute(move || {
+            cell.set(cell.get() + 1);
+            tx.send(cell.get()).unwrap();
+        })
+        .unwrap();
+        assert_eq!(42, rx.recv().unwrap());
+    }
+
+    #[test]
+    fn workerpool_should_join_all_jobs() {
+        let pool = WorkerPool::new(3);
+        let (tx, rx) = mpsc::channel();
+        for i in 0..50 {
+            let tx = tx.clone();
+            pool.execute(move || {
+                thread::sleep(std::time::Duration::from_millis(1));
+                tx.send(i).unwrap();
+            })
+            .unwrap();
+        }
+        pool.join();
+        assert_eq!(50, rx.try_iter().count());
+    }
+
+    #[test]
+    fn workerpool_should_join_without_jobs() {
+        let pool = WorkerPool::new(2);
+        pool.join();
+    }
+
+    #[test]
+    fn workerpool_drop_should_finish_queued_jobs() {
+        let counter = Arc::new(Mutex::new(0));
+        {
+            let pool = WorkerPool::new(2);
+            for _ in 0..20 {
+                let counter = counter.clone();
+                pool.execute(move || {
+                    *counter.lock().unwrap() += 1;
+                })
+                .unwrap();
+            }
+        }
+        assert_eq!(20, *counter.lock().unwrap());
+    }
+
+    #[test]
+    fn worker_should_stop_when_queue_closes() {
+        let shared = new_shared();
+        let w = Worker::new(1);
+        w.start(Arc::clone(&shared)).unwrap();
+        shared.queue.close();
+        w.join();
+        assert_eq!(0, shared.live.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn workerpool_graceful_shutdown_should_run_all_jobs() {
+        let pool = WorkerPool::new(2);
+        let counter = Arc::new(Mutex::new(0));
+        for _ in 0..20 {
+            let counter = counter.clone();
+            pool.execute(move || {
+                *counter.lock().unwrap() += 1;
+            })
+            .unwrap();
+        }
+        let skipped = pool.shutdown(ShutdownMode::Graceful);
+        assert!(skipped.is_empty());
+        assert_eq!(20, *counter.lock().unwrap());
+    }
+
+    #[test]
+    fn workerpool_timeout_shutdown_should_discard_late_jobs() {
+        let pool = WorkerPool::new(1);
+        let (started_tx, started_rx) = mpsc::channel();
+        let (tx, rx) = mpsc::channel::<()>();
+        pool.execute(move || {
+            started_tx.send(()).unwrap();
+            let _ = rx.recv_timeout(Duration::from_millis(200));
+        })
+        .unwrap();
+        for _ in 0..5 {
+            pool.execute(|| {}).unwrap();
+        }
+        started_rx.recv().unwrap();
+        let skipped = pool.shutdown(ShutdownMode::Timeout(Duration::from_millis(10)));
+        assert_eq!(5, skipped.len());
+        drop(tx);
+    }
+
+    #[test]
+    fn workerpool_without_workers_should_return_execute_error() {
+        let pool = WorkerPool::new(0);
+        assert_eq!(Err(ExecuteError::Disconnected), pool.execute(|| {}));
+        pool.join();
+    }
+
+    #[test]
+    fn workerpool_should_submit_and_wait_result() {
+        let pool = WorkerPool::new(2);
+        let handle = pool.submit(|| String::from("result")).unwrap();
+        assert_eq!(Ok(String::from("result")), handle.wait());
+    }
+
+    #[test]
+    fn workerpool_with_queue_capacity_should_run_all_jobs() {
+        let pool = WorkerPool::with_queue_capacity(2, 1);
+        let counter = Arc::new(Mutex::new(0));
+        for _ in 0..50 {
+            let counter = counter.clone();
+            pool.execute(move || {
+                *counter.lock().unwrap() += 1;
+            })
+            .unwrap();
+        }
+        pool.join();
+        assert_eq!(50, *counter.lock().unwrap());
+    }
+
+    #[test]
+    fn workerpool_unbounded_try_execute_should_succeed() {
+        let pool = WorkerPool::new(1);
+        for _ in 0..100 {
+            pool.try_execute(|| {}).unwrap();
+        }
+        pool.join();
+    }
+
+    #[test]
+    fn builder_should_name_worker_threads() {
+        let pool = WorkerPoolBuilder::new()
+            .num_workers(1)
+            .thread_name_prefix("worker")
+            .build();
+        let name = pool
+            .submit(|| thread::current().name().map(String::from))
+            .unwrap()
+            .wait()
+            .unwrap();
+        assert_eq!(Some(String::from("worker-0")), name);
+    }
+
+    #[test]
+    fn workerpool_should_name_worker_threads_by_default() {
+        let pool = WorkerPool::new(2);
+        let mut names = pool
+            .broadcast(|_| thread::current().name().map(String::from))
+            .unwrap();
+        names.sort();
+        let expected = vec![
+            Some(String::from("workerpool-0")),
+            Some(String::from("workerpool-1")),
+        ];
+        assert_eq!(expected, names);
+    }
+
+    #[test]
+    fn builder_should_set_num_workers() {
+        let pool = WorkerPoolBuilder::new().num_workers(2).build();
+        assert_eq!(2, pool.num_workers());
+    }
+
+    #[test]
+    fn workerpool_should_survive_panicking_jobs() {
+        let pool = WorkerPool::new(2);
+        for _ in 0..4 {
+            pool.execute(|| panic!("job panicked")).unwrap();
+        }
+        pool.join();
+        assert_eq!(4, pool.panic_count());
+        assert_eq!(Ok(3), pool.submit(|| 3).unwrap().wait());
+    }
+
+    #[test]
+    fn workerpool_should_respawn_workers_up_to_n_times() {
+        let pool = WorkerPoolBuilder::new()
+            .num_workers(1)
+            .restart_policy(RestartPolicy::UpToN(2))
+            .panic_handler(|_| panic!("worker died"))
+            .build();
+        for _ in 0..2 {
+            pool.execute(|| panic!("job panicked")).unwrap();
+        }
+        assert_eq!(Ok(1), pool.submit(|| 1).unwrap().wait());
+
+        pool.execute(|| panic!("job panicked")).unwrap();
+        let result = pool.submit(|| 2).map(|handle| handle.wait());
+        assert_ne!(Ok(Ok(2)), result);
+    }
+
+    #[test]
+    fn workerpool_default_should_spawn_available_parallelism() {
+        let pool = WorkerPool::default();
+        assert_eq!(default_num_workers(), pool.num_workers());
+        assert!(pool.num_workers() > 0);
+    }
+
+    #[test]
+    fn workerpool_should_count_queued_active_and_completed_jobs() {
+        let pool = WorkerPool::new(1);
+        let (started_tx, started_rx) = mpsc::channel();
+        let (tx, rx) = mpsc::channel::<()>();
+        pool.execute(move || {
+            started_tx.send(()).unwrap();
+            rx.recv().unwrap();
+        })
+        .unwrap();
+        started_rx.recv().unwrap();
+        for _ in 0..3 {
+            pool.execute(|| {}).unwrap();
+        }
+
+        assert_eq!(3, pool.queued_jobs());
+        assert_eq!(1, pool.active_jobs());
+        assert_eq!(0, pool.completed_jobs());
+
+        tx.send(()).unwrap();
+        pool.join();
+        assert_eq!(0, pool.queued_jobs());
+        assert_eq!(0, pool.active_jobs());
+        assert_eq!(4, pool.completed_jobs());
+    }
+
+    #[test]
+    fn job_sender_should_outlive_pool() {
+        let pool = WorkerPool::new(2);
+        let sender = pool.sender();
+        let (tx, rx) = mpsc::channel();
+        sender.execute(move || tx.send(1).unwrap()).unwrap();
+        assert_eq!(Ok(1), rx.recv());
+
+        drop(pool);
+        assert_eq!(Err(ExecuteError::Disconnected), sender.execute(|| {}));
+    }
+
+    #[test]
+    fn scope_should_borrow_local_data() {
+        let pool = WorkerPool::new(2);
+        let mut results = vec![0; 4];
+        pool.scope(|s| {
+            for (i, slot) in results.iter_mut().enumerate() {
+                s.execute(move || *slot = i * 10).unwrap();
+            }
+        });
+        assert_eq!(vec![0, 10, 20, 30], results);
+    }
+
+    #[test]
+    #[should_panic(expected = "a scoped job panicked")]
+    fn scope_should_propagate_job_panics() {
+        let pool = WorkerPool::new(1);
+        pool.scope(|s| {
+            s.execute(|| panic!("job panicked")).unwrap();
+        });
+    }
+
+    #[test]
+    fn keyed_jobs_should_not_overlap() {
+        let pool = WorkerPool::new(4);
+        let running = Arc::new(AtomicUsize::new(0));
+        let overlaps = Arc::new(AtomicUsize::new(0));
+        for _ in 0..20 {
+            let running = running.clone();
+            let overlaps = overlaps.clone();
+            pool.execute_keyed(42, move || {
+                if running.fetch_add(1, Ordering::SeqCst) > 0 {
+                    overlaps.fetch_add(1, Ordering::SeqCst);
+                }
+                thread::sleep(Duration::from_millis(1));
+                running.fetch_sub(1, Ordering::SeqCst);
+            })
+            .unwrap();
+        }
+        pool.join();
+        assert_eq!(0, overlaps.load(Ordering::SeqCst));
+        assert_eq!(20, pool.completed_jobs());
+    }
+
+    #[test]
+    fn keyed_jobs_should_continue_after_panic() {
+        let pool = WorkerPool::new(2);
+        pool.execute_keyed("key", || panic!("job panicked"))
+            .unwrap();
+        let (tx, rx) = mpsc::channel();
+        pool.execute_keyed("key", move || tx.send(1).unwrap())
+            .unwrap();
+        assert_eq!(Ok(1), rx.recv());
+    }
+
+    #[test]
+    fn delayed_jobs_should_be_dropped_on_stop() {
+        let pool = WorkerPool::new(1);
+        let (tx, rx) = mpsc::channel::<()>();
+        pool.execute_after(Duration::from_secs(60), move || tx.send(()).unwrap());
+        drop(pool);
+        assert!(rx.recv().is_err());
+    }
+
+    #[test]
+    fn recurring_job_should_stop_when_cancelled() {
+        let pool = WorkerPool::new(1);
+        let runs = Arc::new(AtomicUsize::new(0));
+        let counter = runs.clone();
+        let handle = pool.execute_every(
+            Duration::from_millis(2),
+            RepeatMode::FixedDelay,
+            move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+            },
+        );
+        while runs.load(Ordering::SeqCst) < 3 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        handle.cancel();
+        thread::sleep(Duration::from_millis(10));
+        pool.join();
+        let stopped_at = runs.load(Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(stopped_at, runs.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn cancelled_job_should_never_start() {
+        let pool = WorkerPool::new(1);
+        let (started_tx, started_rx) = mpsc::channel();
+        let (tx, rx) = mpsc::channel::<()>();
+        pool.execute(move || {
+            started_tx.send(()).unwrap();
+            rx.recv().unwrap();
+        })
+        .unwrap();
+        started_rx.recv().unwrap();
+
+        let started = Arc::new(AtomicUsize::new(0));
+        let counter = started.clone();
+        let handle = pool
+            .execute_cancellable(move |_| {
+                counter.fetch_add(1, Ordering::SeqCst);
+            })
+            .unwrap();
+        handle.cancel();
+        tx.send(()).unwrap();
+
+        assert_eq!(Err(JobError::Cancelled), handle.wait());
+        pool.join();
+        assert_eq!(0, started.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn drain_should_let_running_job_finish() {
+        let pool = WorkerPool::new(1);
+        let (started_tx, started_rx) = mpsc::channel();
+        let (tx, rx) = mpsc::channel::<()>();
+        let (done_tx, done_rx) = mpsc::channel();
+        pool.execute(move || {
+            started_tx.send(()).unwrap();
+            rx.recv().unwrap();
+            done_tx.send(()).unwrap();
+        })
+        .unwrap();
+        let handle = pool.submit(|| 1).unwrap();
+        started_rx.recv().unwrap();
+
+        assert_eq!(1, pool.drain().len());
+        tx.send(()).unwrap();
+        pool.join();
+
+        assert!(done_rx.try_recv().is_ok());
+        assert_eq!(Err(JobError::Disconnected), handle.wait());
+        assert_eq!(0, pool.queued_jobs());
+    }
+
+    #[test]
+    fn map_should_keep_input_order() {
+        let pool = WorkerPool::new(3);
+        let expected: Vec<_> = (0..100).map(|i| i * 2).collect();
+        assert_eq!(expected, pool.map(0..100, |i| i * 2));
+        assert!(pool.map(Vec::<i32>::new(), |i| i).is_empty());
+    }
+
+    #[test]
+    fn chunks_should_cover_every_item() {
+        let pool = WorkerPool::new(2);
+        let chunks = pool.chunks(0..10, Some(4));
+        assert_eq!(vec![vec![0, 1, 2, 3], vec![4, 5, 6, 7], vec![8, 9]], chunks);
+        assert_eq!(8, pool.chunks(0..8, None).len());
+    }
+
+    #[test]
+    fn map_unordered_should_stream_every_result() {
+        let pool = WorkerPool::new(3);
+        let (tx, rx) = mpsc::channel::<()>();
+        let rx = Mutex::new(rx);
+        let mut results = pool.map_unordered(0..10, move |i| {
+            if i == 9 {
+                rx.lock().unwrap().recv().unwrap();
+            }
+            i
+        });
+        let first: Vec<_> = results.by_ref().take(9).collect();
+        tx.send(()).unwrap();
+
+        let mut all: Vec<_> = first.into_iter().chain(results).collect();
+        all.sort();
+        assert_eq!((0..10).collect::<Vec<_>>(), all);
+    }
+
+    #[test]
+    fn for_each_should_visit_every_item_once() {
+        let pool = WorkerPool::new(2);
+        let visits: Vec<_> = (0..50).map(|_| AtomicUsize::new(0)).collect();
+        pool.for_each(0..50, 0, |i| {
+            visits[i].fetch_add(1, Ordering::SeqCst);
+        });
+        assert!(visits.iter().all(|v| v.load(Ordering::SeqCst) == 1));
+    }
+
+    #[test]
+    fn join2_should_run_closures_concurrently() {
+        let pool = WorkerPool::new(1);
+        let (tx, rx) = mpsc::channel();
+        let (a, b) = pool.join2(
+            move || rx.recv().unwrap(),
+            move || {
+                tx.send(5).unwrap();
+                "sent"
+            },
+        );
+        assert_eq!((5, "sent"), (a, b));
+    }
+
+    #[test]
+    fn into_job_should_not_box_a_job_again() {
+        let value = 7;
+        let job: Job = Box::new(move || assert_eq!(7, value));
+        let address = &*job as *const dyn FnOnce() as *const u8;
+        let job = into_job(job);
+        assert_eq!(address, &*job as *const dyn FnOnce() as *const u8);
+        job();
+    }
+
+    #[test]
+    fn bounded_batch_should_queue_every_job() {
+        let pool = WorkerPool::with_queue_capacity(2, 1);
+        let counter = Arc::new(AtomicUsize::new(0));
+        let jobs = (0..10)
+            .map(|_| {
+                let counter = counter.clone();
+                Box::new(move || {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                }) as Job
+            })
+            .collect();
+        pool.execute_batch(jobs).unwrap();
+        pool.join();
+        assert_eq!(10, counter.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn stats_should_average_wait_and_execution() {
+        let pool = WorkerPool::new(1);
+        let (started_tx, started_rx) = mpsc::channel();
+        pool.execute(move || {
+            started_tx.send(()).unwrap();
+            thread::sleep(Duration::from_millis(20));
+        })
+        .unwrap();
+        started_rx.recv().unwrap();
+        pool.execute(|| {}).unwrap();
+        pool.join();
+
+        let stats = pool.stats();
+        assert_eq!(2, stats.submitted);
+        assert_eq!(0, stats.busy_workers);
+        assert!(stats.average_execution >= Duration::from_millis(10));
+        assert!(stats.average_wait >= Duration::from_millis(5));
+        assert!(stats.execution_percentiles.p99 >= Duration::from_millis(20));
+        assert!(stats.wait_percentiles.p50 <= stats.wait_percentiles.p99);
+    }
+
+    #[test]
+    fn observer_should_see_every_event() {
+        #[derive(Default)]
+        struct Events(Mutex<Vec<&'static str>>);
+
+        impl PoolObserver for Events {
+            fn on_submit(&self, _at: Instant) {
+                self.0.lock().unwrap().push("submit");
+            }
+
+            fn on_start(&self, job: &JobInfo) {
+                assert_eq!(0, job.worker);
+                self.0.lock().unwrap().push("start");
+            }
+
+            fn on_complete(&self, _job: &JobInfo, _elapsed: Duration) {
+                self.0.lock().unwrap().push("complete");
+            }
+
+            fn on_panic(&self, _job: &JobInfo, _payload: &(dyn Any + Send)) {
+                self.0.lock().unwrap().push("panic");
+            }
+        }
+
+        let events = Arc::new(Events::default());
+        let pool = WorkerPoolBuilder::new()
+            .num_workers(1)
+            .observer(Arc::clone(&events))
+            .build();
+        pool.execute(|| {}).unwrap();
+        pool.join();
+        pool.execute(|| panic!("job failed")).unwrap();
+        pool.join();
+
+        // A job may start before its submission is reported.
+        let mut events = events.0.lock().unwrap().clone();
+        events.sort_unstable();
+        let expected = ["complete", "panic", "start", "start", "submit", "submit"];
+        assert_eq!(expected, events.as_slice());
+    }
+
+    #[test]
+    fn workerpool_execute_to_should_ignore_a_dropped_receiver() {
+        let pool = WorkerPool::new(1);
+        let (tx, rx) = mpsc::channel();
+        drop(rx);
+        pool.execute_to(tx, || 1).unwrap();
+        pool.join();
+        assert_eq!(0, pool.panic_count());
+    }
+
+    #[test]
+    fn workerpool_execute_ctx_should_report_the_running_worker() {
+        let pool = WorkerPool::new(3);
+        let workers: Vec<_> = (0..12)
+            .map(|_| pool.execute_ctx(|ctx| ctx.worker_id()).unwrap())
+            .collect();
+        for worker in workers {
+            assert!(worker.wait().unwrap() < 3);
+        }
+        let handle = pool.execute_ctx(|ctx| ctx.remaining()).unwrap();
+        assert_eq!(Ok(None), handle.wait());
+    }
+
+    #[test]
+    fn workerpool_status_should_report_panicked_jobs() {
+        let pool = WorkerPool::new(1);
+        let id = pool.submit_tracked(|| panic!("job failed")).unwrap();
+        pool.join();
+        assert_eq!(Some(JobStatus::Panicked), pool.status(id));
+        assert!(!pool.cancel(id));
+        assert_eq!(None, WorkerPool::new(1).status(id));
+    }
+
+    #[test]
+    fn workerpool_set_workers_should_let_retired_workers_finish_their_job() {
+        let pool = WorkerPool::new(2);
+        let (started_tx, started_rx) = mpsc::channel();
+        let (tx, rx) = mpsc::channel::<()>();
+        let rx = Arc::new(Mutex::new(rx));
+        let jobs: Vec<_> = (0..2)
+            .map(|_| {
+                let started_tx = started_tx.clone();
+                let rx = Arc::clone(&rx);
+                pool.execute_ctx(move |ctx| {
+                    started_tx.send(()).unwrap();
+                    rx.lock().unwrap().recv().unwrap();
+                    ctx.worker_id()
+                })
+                .unwrap()
+            })
+            .collect();
+        started_rx.iter().take(2).for_each(drop);
+
+        pool.set_workers(1);
+        assert_eq!(1, pool.num_workers());
+        tx.send(()).unwrap();
+        tx.send(()).unwrap();
+        let mut workers: Vec<_> = jobs.into_iter().map(|job| job.wait().unwrap()).collect();
+        workers.sort_unstable();
+        assert_eq!(vec![0, 1], workers);
+
+        for _ in 0..10 {
+            assert_eq!(
+                Ok(0),
+                pool.execute_ctx(|ctx| ctx.worker_id()).unwrap().wait()
+            );
+        }
+    }
+
+    #[test]
+    fn workerpool_idle_timeout_should_respawn_workers_for_new_jobs() {
+        let pool = WorkerPoolBuilder::new()
+            .num_workers(3)
+            .idle_timeout(Duration::from_millis(1))
+            .build();
+        let counter = Arc::new(AtomicUsize::new(0));
+        for round in 0..5 {
+            while pool.live_workers() > 0 {
+                thread::sleep(Duration::from_millis(1));
+            }
+            for _ in 0..100 {
+                let counter = Arc::clone(&counter);
+                pool.execute(move || {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                })
+                .unwrap();
+            }
+            pool.join();
+            assert_eq!((round + 1) * 100, counter.load(Ordering::SeqCst));
+        }
+    }
+
+    #[test]
+    fn workerpool_lazy_spawn_should_not_exceed_num_workers() {
+        let pool = WorkerPoolBuilder::new()
+            .num_workers(2)
+            .lazy_spawn(true)
+            .build();
+        let (started_tx, started_rx) = mpsc::channel();
+        let (tx, rx) = mpsc::channel::<()>();
+        let rx = Arc::new(Mutex::new(rx));
+        for _ in 0..6 {
+            let started_tx = started_tx.clone();
+            let rx = Arc::clone(&rx);
+            pool.execute(move || {
+                started_tx.send(()).unwrap();
+                let _ = rx.lock().unwrap().recv();
+            })
+            .unwrap();
+        }
+        started_rx.iter().take(2).for_each(drop);
+        assert_eq!(2, pool.live_workers());
+        drop(tx);
+        pool.join();
+        assert_eq!(2, pool.live_workers());
+    }
+
+    #[test]
+    fn workerpool_should_be_reusable_after_wait_idle() {
+        let pool = WorkerPool::new(2);
+        let counter = Arc::new(AtomicUsize::new(0));
+        for phase in 1..=3 {
+            for _ in 0..10 {
+                let counter = Arc::clone(&counter);
+                pool.execute(move || {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                })
+                .unwrap();
+            }
+            pool.wait_idle();
+            assert_eq!(phase * 10, counter.load(Ordering::SeqCst));
+        }
+        assert!(pool.wait_idle_timeout(Duration::ZERO));
+    }
+
+    #[test]
+    fn workerpool_should_stop_when_the_last_clone_drops_in_a_job() {
+        let pool = WorkerPool::new(2);
+        let (tx, rx) = mpsc::channel();
+        let clone = pool.clone();
+        let shared = Arc::clone(&pool.shared);
+        drop(pool);
+        assert!(!shared.queue.is_closed());
+        clone
+            .clone()
+            .execute(move || {
+                drop(clone);
+                tx.send(()).unwrap();
+            })
+            .unwrap();
+        rx.recv().unwrap();
+        while shared.live.load(Ordering::SeqCst) > 0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(shared.queue.is_closed());
+    }
+
+    #[cfg(feature = "futures")]
+    #[test]
+    fn workerpool_should_cancel_a_dropped_future() {
+        let pool = WorkerPool::new(1);
+        let (started_tx, started_rx) = mpsc::channel();
+        let (tx, rx) = mpsc::channel::<()>();
+        pool.execute(move || {
+            started_tx.send(()).unwrap();
+            rx.recv().unwrap();
+        })
+        .unwrap();
+        started_rx.recv().unwrap();
+
+        let future = pool
+            .submit_async(|| unreachable!("the job was cancelled"))
+            .unwrap();
+        drop(future);
+        tx.send(()).unwrap();
+        pool.join();
+        assert_eq!(0, pool.panic_count());
+    }
+
+    #[test]
+    fn workerpool_rate_limit_should_throttle_queued_jobs() {
+        let pool = WorkerPoolBuilder::new()
+            .num_workers(2)
+            .rate_limit(RateLimit {
+                burst: 1,
+                ..RateLimit::per_second(50)
+            })
+            .build();
+        let started = Instant::now();
+        for _ in 0..4 {
+            pool.execute(|| {}).unwrap();
+        }
+        pool.join();
+        assert!(started.elapsed() >= Duration::from_millis(50));
+    }
+
+    #[test]
+    fn workerpool_tagged_jobs_should_not_hold_workers_while_waiting() {
+        let pool = WorkerPoolBuilder::new()
+            .num_workers(2)
+            .tag_limit("io", 1)
+            .build();
+        let (started_tx, started_rx) = mpsc::channel();
+        let (tx, rx) = mpsc::channel::<()>();
+        pool.execute_tagged("io", move || {
+            started_tx.send(()).unwrap();
+            rx.recv().unwrap();
+        })
+        .unwrap();
+        started_rx.recv().unwrap();
+        for _ in 0..3 {
+            pool.execute_tagged("io", || {}).unwrap();
+        }
+        assert_eq!(Ok(1), pool.submit(|| 1).unwrap().wait());
+        assert_eq!(0, pool.queued_jobs());
+        tx.send(()).unwrap();
+        pool.join();
+        assert_eq!(5, pool.completed_jobs());
+    }
+
+    #[test]
+    fn workerpool_deadline_jobs_should_report_misses_and_still_run() {
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let pool = WorkerPoolBuilder::new()
+            .num_workers(1)
+            .queue_order(QueueOrder::EarliestDeadlineFirst)
+            .deadline_miss_handler(move |late| tx.lock().unwrap().send(late).unwrap())
+            .build();
+        let ran = Arc::new(AtomicUsize::new(0));
+        let past = Instant::now();
+        let future = past + Duration::from_secs(60);
+        for deadline in [past, future] {
+            let ran = ran.clone();
+            pool.execute_with_deadline(deadline, move || {
+                ran.fetch_add(1, Ordering::SeqCst);
+            })
+            .unwrap();
+        }
+        pool.join();
+        assert_eq!(2, ran.load(Ordering::SeqCst));
+        assert_eq!(1, rx.try_iter().count());
+    }
+
+    #[test]
+    fn workerpool_retry_should_stop_when_cancelled() {
+        let pool = WorkerPool::new(1);
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let policy = RetryPolicy {
+            backoff: crate::retry::Backoff::Fixed(Duration::from_millis(20)),
+            ..RetryPolicy::new(10)
+        };
+        let handle = pool
+            .submit_retry(policy, move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Err::<(), _>(())
+            })
+            .unwrap();
+        handle.cancel();
+        assert_eq!(Err(JobError::Cancelled), handle.wait());
+        assert!(calls.load(Ordering::SeqCst) <= 1);
+    }
+
+    #[test]
+    fn workerpool_exhausted_retries_should_be_dead_lettered() {
+        let (tx, rx) = mpsc::channel();
+        let pool = WorkerPoolBuilder::new().dead_letter(tx).build();
+        let policy = RetryPolicy {
+            backoff: crate::retry::Backoff::Fixed(Duration::from_millis(1)),
+            ..RetryPolicy::new(2)
+        };
+        let handle = pool.submit_retry(policy, || Err::<(), _>("down")).unwrap();
+        assert!(handle.wait().unwrap().is_err());
+        let letter = rx.recv().unwrap();
+        assert!(letter.job.is_some());
+        assert_eq!(2, letter.attempts);
+        assert!(letter.started <= letter.failed);
+        match letter.failure {
+            crate::deadletter::Failure::Error(error) => assert_eq!("\"down\"", error),
+            crate::deadletter::Failure::Panic(_) => panic!("the job didn't panic"),
+        }
+        pool.execute(|| panic!("boom")).unwrap();
+        let letter = rx.recv().unwrap();
+        assert_eq!(None, letter.job);
+    }
+
+    #[test]
+    fn workerpool_broadcast_should_reach_lazy_workers_once() {
+        let pool = WorkerPoolBuilder::new()
+            .num_workers(4)
+            .lazy_spawn(true)
+            .work_stealing(true)
+            .build();
+        let names = pool
+            .broadcast(|_| format!("{:?}", thread::current().id()))
+            .unwrap();
+        let mut unique = names.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(4, unique.len());
+        assert_eq!(4, pool.live_workers());
+    }
+
+    #[test]
+    fn workerpool_display_should_show_busy_workers() {
+        let pool = WorkerPool::new(1);
+        pool.execute(|| {}).unwrap();
+        pool.join();
+        let (started_tx, started_rx) = mpsc::channel();
+        let (tx, rx) = mpsc::channel::<()>();
+        pool.execute(move || {
+            started_tx.send(()).unwrap();
+            rx.recv().unwrap();
+        })
+        .unwrap();
+        started_rx.recv().unwrap();
+        assert_eq!("workers[] = (id: 0, busy, completed: 1)", pool.to_string());
+        let debug = format!("{:?}", pool);
+        assert!(debug.starts_with("WorkerPool { workers: 1, live_workers: 1"));
+        assert!(debug.contains("active_jobs: 1"));
+        tx.send(()).unwrap();
+    }
+
+    #[test]
+    fn workerpool_labels_should_reach_the_observer_and_dead_letters() {
+        struct Labels(Mutex<Vec<(&'static str, Option<&'static str>)>>);
+
+        impl PoolObserver for Labels {
+            fn on_start(&self, job: &JobInfo) {
+                self.0.lock().unwrap().push(("start", job.label));
+            }
+
+            fn on_complete(&self, job: &JobInfo, _elapsed: Duration) {
+                assert_eq!(job.label, current_label());
+                self.0.lock().unwrap().push(("complete", job.label));
+            }
+
+            fn on_panic(&self, job: &JobInfo, _payload: &(dyn Any + Send)) {
+                self.0.lock().unwrap().push(("panic", job.label));
+            }
+        }
+
+        let labels = Arc::new(Labels(Mutex::new(Vec::new())));
+        let (tx, rx) = mpsc::channel();
+        let pool = WorkerPoolBuilder::new()
+            .num_workers(1)
+            .observer(Arc::clone(&labels))
+            .dead_letter(tx)
+            .build();
+        pool.execute_labeled("resize", || {}).unwrap();
+        pool.execute(|| {}).unwrap();
+        pool.execute_labeled("encode", || panic!("boom")).unwrap();
+        assert_eq!(Some("encode"), rx.recv().unwrap().label);
+        pool.join();
+        let seen = labels.0.lock().unwrap().clone();
+        let expected = vec![
+            ("start", Some("resize")),
+            ("complete", Some("resize")),
+            ("start", None),
+            ("complete", None),
+            ("start", Some("encode")),
+            ("panic", Some("encode")),
+        ];
+        assert_eq!(expected, seen);
+        assert_eq!(1, pool.stats_by_label()["encode"].submitted);
+        assert_eq!(None, current_label());
+    }
+
+    #[test]
+    fn sender_try_execute_should_give_the_job_back_once_disconnected() {
+        let pool = WorkerPool::new(1);
+        let sender = pool.sender();
+        drop(pool);
+        let (tx, rx) = mpsc::channel();
+        let rejected = sender.try_execute(move || tx.send(1).unwrap()).unwrap_err();
+        assert_eq!(ExecuteError::Disconnected, rejected.error());
+        rejected.into_job()();
+        assert_eq!(Ok(1), rx.try_recv());
+    }
+
+    #[test]
+    fn saturation_policies_should_drop_the_oldest_or_newest_job() {
+        for (policy, expected) in [
+            (SaturationPolicy::DropOldest, vec![2]),
+            (SaturationPolicy::DropNewest, vec![1]),
+        ] {
+            let pool = WorkerPoolBuilder::new()
+                .num_workers(1)
+                .queue_capacity(1)
+                .saturation_policy(policy)
+                .build();
+            let (started_tx, started_rx) = mpsc::channel();
+            let (tx, rx) = mpsc::channel::<()>();
+            pool.execute(move || {
+                started_tx.send(()).unwrap();
+                rx.recv().unwrap();
+            })
+            .unwrap();
+            started_rx.recv().unwrap();
+            let (ran_tx, ran_rx) = mpsc::channel();
+            for i in [1, 2] {
+                pool.execute_to(ran_tx.clone(), move || i).unwrap();
+            }
+            tx.send(()).unwrap();
+            pool.join();
+            assert_eq!(expected, ran_rx.try_iter().collect::<Vec<_>>());
+        }
+
+        let pool = WorkerPoolBuilder::new()
+            .num_workers(1)
+            .queue_capacity(1)
+            .saturation_policy(SaturationPolicy::Error)
+            .build();
+        let (started_tx, started_rx) = mpsc::channel();
+        let (tx, rx) = mpsc::channel::<()>();
+        pool.execute(move || {
+            started_tx.send(()).unwrap();
+            rx.recv().unwrap();
+        })
+        .unwrap();
+        started_rx.recv().unwrap();
+        pool.execute(|| {}).unwrap();
+        assert_eq!(Err(ExecuteError::QueueFull), pool.execute(|| {}));
+        tx.send(()).unwrap();
+    }
+
+    #[test]
+    fn caller_runs_should_catch_the_panic_of_the_job_and_count_it() {
+        let pool = WorkerPoolBuilder::new()
+            .num_workers(1)
+            .queue_capacity(1)
+            .saturation_policy(SaturationPolicy::CallerRuns)
+            .build();
+        let (started_tx, started_rx) = mpsc::channel();
+        let (tx, rx) = mpsc::channel::<()>();
+        pool.execute(move || {
+            started_tx.send(()).unwrap();
+            rx.recv().unwrap();
+        })
+        .unwrap();
+        started_rx.recv().unwrap();
+        pool.execute(|| {}).unwrap();
+        assert_eq!(Ok(()), pool.execute(|| panic!("caller runs")));
+        tx.send(()).unwrap();
+        pool.join();
+
+        let stats = pool.stats();
+        assert_eq!(3, stats.submitted);
+        assert_eq!(3, stats.completed);
+        assert_eq!(1, stats.panicked);
+    }
+
+    #[test]
+    fn execute_all_and_wait_should_outlast_the_workers() {
+        let pool = WorkerPool::new(2);
+        let counter = Arc::new(AtomicUsize::new(0));
+        let jobs = (0..64).map(|i| {
+            let counter = counter.clone();
+            Box::new(move || {
+                if i == 7 {
+                    panic!("job panicked");
+                }
+                thread::sleep(Duration::from_micros(100));
+                counter.fetch_add(1, Ordering::SeqCst);
+            }) as Job
+        });
+        assert_eq!(Ok(()), pool.execute_all_and_wait(jobs));
+        assert_eq!(63, counter.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn workerpool_should_replace_the_worker_of_a_panicked_job_on_restart_policy() {
+        let pool = WorkerPoolBuilder::new()
+            .num_workers(1)
+            .panic_policy(PanicPolicy::Restart)
+            .build();
+        let before = pool.submit(|| thread::current().id()).unwrap().wait();
+        pool.execute(|| panic!("job panicked")).unwrap();
+        let after = pool.submit(|| thread::current().id()).unwrap().wait();
+        assert_ne!(before.unwrap(), after.unwrap());
+        assert_eq!(1, pool.panic_count());
+    }
+
+    #[test]
+    fn workerpool_should_call_the_panic_policy_callback() {
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let handler_tx = Mutex::new(tx.lock().unwrap().clone());
+        let pool = WorkerPoolBuilder::new()
+            .num_workers(1)
+            .panic_policy(PanicPolicy::callback(move |payload| {
+                let message = payload.downcast_ref::<&str>().copied();
+                tx.lock().unwrap().send(message).unwrap();
+            }))
+            .panic_handler(move |_| handler_tx.lock().unwrap().send(Some("handler")).unwrap())
+            .build();
+        let before = pool.submit(|| thread::current().id()).unwrap().wait();
+        pool.execute(|| panic!("job panicked")).unwrap();
+        assert_eq!(Some("handler"), rx.recv().unwrap());
+        assert_eq!(Some("job panicked"), rx.recv().unwrap());
+        let after = pool.submit(|| thread::current().id()).unwrap().wait();
+        assert_eq!(before.unwrap(), after.unwrap());
+    }
+
+    #[test]
+    fn workerpool_watchdog_should_mark_the_worker_of_a_stuck_job_as_suspect() {
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let pool = WorkerPoolBuilder::new()
+            .num_workers(2)
+            .watchdog(
+                Watchdog {
+                    mark_suspect: true,
+                    ..Watchdog::new(Duration::from_millis(5))
+                },
+                move |stuck| tx.lock().unwrap().send(stuck).unwrap(),
+            )
+            .build();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let id = pool
+            .submit_tracked(move || {
+                let _ = release_rx.recv();
+            })
+            .unwrap();
+        let stuck = rx.recv().unwrap();
+        assert_eq!(Some(id), stuck.job);
+        assert_eq!(vec![stuck.worker], pool.suspect_workers());
+        drop(release_tx);
+        pool.join();
+        assert!(pool.suspect_workers().is_empty());
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn workerpool_stats_should_tell_a_wedged_worker_from_an_idle_one() {
+        let pool = WorkerPool::new(2);
+        let (started_tx, started_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        pool.execute(move || {
+            started_tx.send(()).unwrap();
+            let _ = release_rx.recv();
+        })
+        .unwrap();
+        started_rx.recv().unwrap();
+        thread::sleep(Duration::from_millis(5));
+        let workers = pool.stats().workers;
+        let wedged: Vec<_> = workers
+            .iter()
+            .filter(|worker| worker.is_wedged(Duration::from_millis(1)))
+            .collect();
+        assert_eq!(1, wedged.len());
+        drop(release_tx);
+        pool.join();
+        let workers = pool.stats().workers;
+        assert!(workers
+            .iter()
+            .all(|worker| !worker.is_wedged(Duration::ZERO)));
+        assert!(workers.iter().any(|worker| worker.last_beat.is_some()));
+    }
+
+    #[test]
+    fn workerpool_should_time_out_a_job_ignoring_its_token() {
+        let pool = WorkerPool::new(1);
+        let (tx, rx) = mpsc::channel();
+        let handle = pool
+            .execute_with_timeout(Duration::from_millis(1), move |token| {
+                thread::sleep(Duration::from_millis(20));
+                tx.send(token.is_cancelled()).unwrap();
+                42
+            })
+            .unwrap();
+        assert_eq!(Err(JobError::Timeout), handle.wait());
+        assert!(rx.recv().unwrap());
+    }
+
+    #[test]
+    fn workerpool_results_should_disconnect_once_the_pool_is_dropped() {
+        let pool = WorkerPool::new(2);
+        let results = pool.results::<usize>();
+        for i in 0..10usize {
+            pool.execute_returning(move || i).unwrap();
+        }
+        pool.execute_returning(|| "other").unwrap();
+        drop(pool);
+        assert_eq!(45, results.iter().sum::<usize>());
+    }
+
+    #[test]
+    fn workerpool_hooks_should_run_on_the_worker_threads() {
+        let (tx, rx) = mpsc::channel();
+        let started = Mutex::new(tx.clone());
+        let stopped = Mutex::new(tx);
+        let pool = WorkerPoolBuilder::new()
+            .num_workers(2)
+            .on_worker_start(move |id| {
+                let name = thread::current().name().map(String::from);
+                started.lock().unwrap().send(("start", id, name)).unwrap();
+            })
+            .on_worker_stop(move |id| {
+                let name = thread::current().name().map(String::from);
+                stopped.lock().unwrap().send(("stop", id, name)).unwrap();
+            })
+            .build();
+        pool.set_workers(1);
+        drop(pool);
+        let mut events: Vec<_> = rx.try_iter().collect();
+        events.sort();
+        let name = |id| Some(format!("workerpool-{}", id));
+        assert_eq!(
+            vec![
+                ("start", 0, name(0)),
+                ("start", 1, name(1)),
+                ("stop", 0, name(0)),
+                ("stop", 1, name(1)),
+            ],
+            events
+        );
+    }
+
+    #[test]
+    fn workerpool_map_reduce_should_reduce_in_input_order() {
+        let pool = WorkerPool::new(3);
+        let digits = pool.map_reduce(
+            0..100,
+            |i| vec![i],
+            Vec::new,
+            |mut left, right| {
+                left.extend(right);
+                left
+            },
+        );
+        assert_eq!((0..100).collect::<Vec<_>>(), digits);
+        assert_eq!(
+            0,
+            pool.map_reduce(Vec::<usize>::new(), |i| i, || 0, |a, b| a + b)
+        );
+    }
+
+    #[test]
+    fn workerpool_try_map_should_stop_on_the_first_error() {
+        let pool = WorkerPool::new(2);
+        let calls = AtomicUsize::new(0);
+        let result = pool.try_map(0..10_000, |i| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            if i % 100 == 7 {
+                Err(i)
+            } else {
+                Ok(i)
+            }
+        });
+        assert!(matches!(result, Err(i) if i % 100 == 7));
+        assert!(calls.load(Ordering::SeqCst) < 10_000);
+        assert_eq!(Ok::<_, ()>(vec![2, 4]), pool.try_map([1, 2], |i| Ok(i * 2)));
+    }
+
+    #[test]
+    fn workerpool_for_each_mut_should_visit_every_chunk_once() {
+        let pool = WorkerPool::new(3);
+        let mut values: Vec<usize> = (0..1000).collect();
+        pool.for_each_mut(&mut values, 0, |chunk| {
+            chunk.iter_mut().for_each(|value| *value *= 2)
+        });
+        assert_eq!((0..1000).map(|i| i * 2).collect::<Vec<_>>(), values);
+
+        let lengths = Mutex::new(Vec::new());
+        pool.for_each_mut(&mut values, 300, |chunk| {
+            lengths.lock().unwrap().push(chunk.len())
+        });
+        let mut lengths = lengths.into_inner().unwrap();
+        lengths.sort();
+        assert_eq!(vec![100, 300, 300, 300], lengths);
+        pool.for_each_mut(&mut Vec::<u8>::new(), 0, |_| panic!("no chunk expected"));
+    }
+
+    #[test]
+    fn workerpool_sort_should_be_stable() {
+        let pool = WorkerPool::new(3);
+        let mut pairs: Vec<(u32, usize)> =
+            (0..20_000).map(|i| ((i * 7919 % 101) as u32, i)).collect();
+        let mut expected = pairs.clone();
+        expected.sort_by_key(|pair| pair.0);
+        pool.sort(&mut pairs);
+        assert_eq!(expected, pairs);
+
+        let mut short = vec![3, 1, 2];
+        pool.sort(&mut short);
+        assert_eq!(vec![1, 2, 3], short);
+    }
+
+    #[test]
+    fn workerpool_nested_jobs_should_not_deadlock_a_single_worker() {
+        let pool = WorkerPool::new(1);
+        let nested = pool.clone();
+        let handle = pool
+            .submit(move || {
+                let squares = nested.map(1..=4, |i| i * i);
+                let (a, b) = nested.join2(|| 1, || 2);
+                let batch: Vec<Job> = vec![Box::new(|| {}), Box::new(|| panic!("nested"))];
+                nested.execute_all_and_wait(batch).unwrap();
+                (squares, a + b)
+            })
+            .unwrap();
+        assert_eq!(Ok((vec![1, 4, 9, 16], 3)), handle.wait());
+        assert_eq!(1, pool.panic_count());
+    }
+
+    #[test]
+    fn workerpool_join_nested_should_wake_when_the_job_fails_elsewhere() {
+        let pool = WorkerPool::new(2);
+        let nested = pool.clone();
+        let (tx, rx) = mpsc::channel::<()>();
+        let handle = pool
+            .submit(move || {
+                let inner = nested
+                    .submit(move || {
+                        rx.recv().unwrap();
+                        panic!("nested job failed");
+                    })
+                    .unwrap();
+                matches!(nested.join_nested(inner), Err(JobError::Panicked(_)))
+            })
+            .unwrap();
+        thread::sleep(Duration::from_millis(20));
+        tx.send(()).unwrap();
+        assert_eq!(Ok(true), handle.wait_timeout(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn workerpool_current_should_return_the_pool_of_the_job() {
+        let pool = WorkerPool::new(1);
+        let other = WorkerPool::new(1);
+        let same = pool
+            .submit(|| WorkerPool::current().map(|current| current.num_workers()))
+            .unwrap()
+            .wait();
+        assert_eq!(Ok(Some(1)), same);
+        let probe = pool.clone();
+        let is_other = other
+            .submit(move || {
+                let current = WorkerPool::current().unwrap();
+                Arc::ptr_eq(&current.shared, &probe.shared)
+            })
+            .unwrap()
+            .wait();
+        assert_eq!(Ok(false), is_other);
+    }
+
+    #[test]
+    fn workerpool_inline_should_run_jobs_on_the_caller() {
+        let pool = WorkerPoolBuilder::new()
+            .execution_mode(ExecutionMode::Inline)
+            .build();
+        let caller = thread::current().id();
+        let handle = pool
+            .submit(move || thread::current().id() == caller)
+            .unwrap();
+        assert!(handle.is_finished());
+        assert_eq!(Ok(true), handle.wait());
+        let panicked = pool.submit(|| panic!("inline job panicked")).unwrap();
+        assert!(matches!(panicked.wait(), Err(JobError::Panicked(_))));
+        let nested = pool
+            .submit(|| WorkerPool::current().unwrap().submit(|| 2).unwrap().wait())
+            .unwrap();
+        assert_eq!(Ok(Ok(2)), nested.wait());
+        assert_eq!(vec![1, 4, 9], pool.map(1..=3u32, |i| i * i));
+        assert_eq!(1, pool.panic_count());
+        let sender = pool.sender();
+        pool.shutdown(ShutdownMode::Immediate);
+        assert_eq!(Err(ExecuteError::Disconnected), sender.execute(|| {}));
+    }
+
+    #[test]
+    fn workerpool_nested_jobs_should_be_reported_as_worker_jobs() {
+        #[derive(Default)]
+        struct Counts(AtomicUsize, AtomicUsize);
+
+        impl PoolObserver for Counts {
+            fn on_start(&self, _job: &JobInfo) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+
+            fn on_panic(&self, _job: &JobInfo, _payload: &(dyn Any + Send)) {
+                self.1.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let counts = Arc::new(Counts::default());
+        let (tx, rx) = mpsc::channel();
+        let pool = WorkerPoolBuilder::new()
+            .num_workers(1)
+            .observer(Arc::clone(&counts))
+            .dead_letter(tx)
+            .panic_policy(PanicPolicy::Restart)
+            .build();
+        let nested = pool.clone();
+        let outer = pool
+            .submit(move || {
+                let inner = nested.submit(|| panic!("nested job panicked")).unwrap();
+                assert!(nested.join_nested(inner).is_err());
+                thread::current().id()
+            })
+            .unwrap()
+            .wait()
+            .unwrap();
+        let next = pool.submit(|| thread::current().id()).unwrap().wait();
+
+        assert_ne!(Ok(outer), next);
+        assert_eq!(3, counts.0.load(Ordering::SeqCst));
+        assert_eq!(1, counts.1.load(Ordering::SeqCst));
+        assert!(rx.try_recv().is_ok());
+    }
+
+    #[test]
+    fn workerpool_inline_should_run_keyed_tagged_and_weighted_jobs() {
+        let pool = WorkerPoolBuilder::new()
+            .execution_mode(ExecutionMode::Inline)
+            .tag_limit("io", 1)
+            .weight_budget(2)
+            .build();
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let (nested, log) = (pool.clone(), Arc::clone(&order));
+        pool.execute_keyed("k", move || {
+            let inner = Arc::clone(&log);
+            nested
+                .execute_keyed("k", move || inner.lock().unwrap().push("keyed after"))
+                .unwrap();
+            log.lock().unwrap().push("keyed");
+        })
+        .unwrap();
+        let log = Arc::clone(&order);
+        pool.execute_tagged("io", move || log.lock().unwrap().push("tagged"))
+            .unwrap();
+        let log = Arc::clone(&order);
+        pool.execute_weighted(3, move || log.lock().unwrap().push("weighted"))
+            .unwrap();
+
+        assert_eq!(0, pool.queued_jobs());
+        pool.join();
+        assert_eq!(
+            vec!["keyed", "keyed after", "tagged", "weighted"],
+            *order.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn workerpool_inline_should_apply_the_observer_and_panic_policy() {
+        #[derive(Default)]
+        struct Counts(AtomicUsize, AtomicUsize, AtomicUsize);
+
+        impl PoolObserver for Counts {
+            fn on_start(&self, _job: &JobInfo) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+
+            fn on_complete(&self, _job: &JobInfo, _elapsed: Duration) {
+                self.1.fetch_add(1, Ordering::SeqCst);
+            }
+
+            fn on_panic(&self, _job: &JobInfo, _payload: &(dyn Any + Send)) {
+                self.2.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let counts = Arc::new(Counts::default());
+        let (tx, rx) = mpsc::channel();
+        let pool = WorkerPoolBuilder::new()
+            .execution_mode(ExecutionMode::Inline)
+            .observer(Arc::clone(&counts))
+            .dead_letter(tx)
+            .panic_policy(PanicPolicy::AbortPool)
+            .build();
+        pool.execute(|| {}).unwrap();
+        pool.execute(|| panic!("inline job panicked")).unwrap();
+
+        assert_eq!(Err(ExecuteError::Disconnected), pool.execute(|| {}));
+        assert_eq!(2, counts.0.load(Ordering::SeqCst));
+        assert_eq!(1, counts.1.load(Ordering::SeqCst));
+        assert_eq!(1, counts.2.load(Ordering::SeqCst));
+        assert!(rx.try_recv().is_ok());
+        let stats = pool.stats();
+        assert_eq!(2, stats.completed);
+        assert_eq!(0, stats.busy_workers);
+    }
+
+    #[test]
+    fn workerpool_nested_submission_should_apply_the_saturation_policy() {
+        let pool = WorkerPoolBuilder::new()
+            .num_workers(1)
+            .queue_capacity(1)
+            .saturation_policy(SaturationPolicy::Error)
+            .build();
+        let nested = pool.clone();
+        let results = pool
+            .submit(move || (nested.execute(|| {}), nested.execute(|| {})))
+            .unwrap()
+            .wait()
+            .unwrap();
+        assert_eq!((Ok(()), Err(ExecuteError::QueueFull)), results);
+
+        let blocking = WorkerPoolBuilder::new()
+            .num_workers(1)
+            .queue_capacity(1)
+            .build();
+        let nested = blocking.clone();
+        let results = blocking
+            .submit(move || (nested.execute(|| {}), nested.execute(|| {})))
+            .unwrap()
+            .wait_timeout(Duration::from_secs(5))
+            .unwrap();
+        assert_eq!((Ok(()), Ok(())), results);
+        blocking.join();
+    }
+
+    #[test]
+    fn workerpool_tracked_statuses_should_stay_bounded() {
+        let pool = WorkerPool::new(2);
+        let ids: Vec<_> = (0..FINISHED_STATUSES + 100)
+            .map(|_| pool.submit_tracked(|| {}).unwrap())
+            .collect();
+        pool.join();
+
+        assert_eq!(None, pool.status(ids[0]));
+        assert_eq!(Some(JobStatus::Completed), pool.status(ids[ids.len() - 1]));
+        let statuses = pool.statuses();
+        assert_eq!(FINISHED_STATUSES, statuses.statuses.len());
+        assert_eq!(FINISHED_STATUSES, statuses.finished.len());
+    }
+
+    #[test]
+    fn workerpool_try_build_should_return_the_spawn_error() {
+        let built = WorkerPoolBuilder::new()
+            .num_workers(2)
+            .stack_size(usize::MAX / 2)
+            .try_build();
+        assert!(matches!(built, Err(Error::Spawn(_))));
+    }
+
+    #[test]
+    #[cfg(feature = "affinity")]
+    fn workerpool_should_report_the_workers_it_cant_pin() {
+        use crate::affinity::Affinity;
+
+        struct Unpinned(Mutex<mpsc::Sender<(usize, Vec<usize>)>>);
+
+        impl PoolObserver for Unpinned {
+            fn on_pin_failed(&self, worker: usize, cores: &[usize]) {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .send((worker, cores.to_vec()))
+                    .unwrap();
+            }
+        }
+
+        let (tx, rx) = mpsc::channel();
+        let _pool = WorkerPoolBuilder::new()
+            .num_workers(1)
+            .affinity(Affinity::Cores(vec![vec![usize::MAX]]))
+            .observer(Unpinned(Mutex::new(tx)))
+            .build();
+        assert_eq!((0, vec![usize::MAX]), rx.recv().unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "tracing")]
+    fn workerpool_should_trace_jobs_under_the_submitting_span() {
+        use std::fmt::Debug;
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata};
+        use tracing_core::span::Current;
+
+        thread_local! {
+            static ENTERED: RefCell<Vec<(Id, &'static Metadata<'static>)>> =
+                const { RefCell::new(Vec::new()) };
+        }
+
+        type Span = (&'static Metadata<'static>, Option<u64>, Vec<&'static str>);
+
+        // Records the metadata, the parent and the field names of every span.
+        #[derive(Default)]
+        struct Spans(Mutex<Vec<Span>>);
+
+        struct Names(Vec<&'static str>);
+
+        impl Visit for Names {
+            fn record_debug(&mut self, field: &Field, _value: &dyn Debug) {
+                self.0.push(field.name());
+            }
+        }
+
+        impl tracing::Subscriber for Spans {
+            fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+                true
+            }
+
+            fn new_span(&self, span: &Attributes<'_>) -> Id {
+                let parent = match span.parent() {
+                    Some(parent) => Some(parent.into_u64()),
+                    None if span.is_contextual() => self.current_span().id().map(Id::into_u64),
+                    None => None,
+                };
+                let mut names = Names(Vec::new());
+                span.record(&mut names);
+                let mut spans = self.0.lock().unwrap();
+                spans.push((span.metadata(), parent, names.0));
+                Id::from_u64(spans.len() as u64)
+            }
+
+            fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+            fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+            fn event(&self, _event: &Event<'_>) {}
+
+            fn enter(&self, span: &Id) {
+                let metadata = self.0.lock().unwrap()[span.into_u64() as usize - 1].0;
+                ENTERED.with(|entered| entered.borrow_mut().push((span.clone(), metadata)));
+            }
+
+            fn exit(&self, _span: &Id) {
+                ENTERED.with(|entered| entered.borrow_mut().pop());
+            }
+
+            fn current_span(&self) -> Current {
+                ENTERED.with(|entered| match entered.borrow().last() {
+                    Some((id, metadata)) => Current::new(id.clone(), metadata),
+                    None => Current::none(),
+                })
+            }
+        }
+
+        let spans = Arc::new(Spans::default());
+        tracing::subscriber::set_global_default(Arc::clone(&spans)).unwrap();
+        let pool = WorkerPool::new(1);
+        tracing::info_span!("request").in_scope(|| pool.execute_labeled("traced", || {}).unwrap());
+        pool.join();
+
+        let spans = spans.0.lock().unwrap();
+        let request = spans.iter().position(|span| span.0.name() == "request");
+        let request = request.map(|index| index as u64 + 1);
+        let job = spans
+            .iter()
+            .find(|span| span.0.name() == "job" && span.1 == request)
+            .unwrap();
+        assert_eq!(vec!["worker", "label", "queue_wait_us"], job.2);
     }
 }
@@ -0,0 +1,79 @@
+//! ## Progress
+//!
+//! This module contains the [`ProgressUpdate`] reported by the jobs
+//! executed with [`WorkerPool::execute_ctx`](crate::pool::WorkerPool::execute_ctx),
+//! through [`JobContext::progress`](crate::context::JobContext::progress)
+//! and [`JobContext::progress_message`](crate::context::JobContext::progress_message).
+//! The updates of every job are sent to the channel set with
+//! [`WorkerPoolBuilder::progress_channel`](crate::pool::WorkerPoolBuilder::progress_channel),
+//! so a single subscriber can draw progress bars or estimate the time
+//! left of a batch.
+
+use crate::context::JobId;
+use std::time::{Duration, Instant};
+
+/// A progress report of a running job.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgressUpdate {
+    /// The id of the job.
+    pub job: JobId,
+    /// The id of the worker running the job.
+    pub worker: usize,
+    /// The last fraction of its work the job reported done, between 0 and
+    /// 1, or None if it only sent messages so far.
+    pub fraction: Option<f64>,
+    /// The message of the update, if it has one.
+    pub message: Option<String>,
+    /// When the job started.
+    pub started: Instant,
+    /// When the update was sent.
+    pub at: Instant,
+}
+
+impl ProgressUpdate {
+    /// Estimates the time the job needs to finish, assuming it keeps its
+    /// pace since it started.
+    ///
+    /// **returns**: the estimated time left, or None before any progress.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPoolBuilder;
+    /// use std::sync::mpsc;
+    ///
+    /// let (tx, rx) = mpsc::channel();
+    /// let pool = WorkerPoolBuilder::new().progress_channel(tx).build();
+    /// pool.execute_ctx(|ctx| ctx.progress(1.0)).unwrap().wait().unwrap();
+    ///
+    /// let update = rx.recv().unwrap();
+    /// assert_eq!(Some(std::time::Duration::ZERO), update.eta());
+    /// ```
+    pub fn eta(&self) -> Option<Duration> {
+        let fraction = self.fraction.filter(|fraction| *fraction > 0.0)?;
+        let elapsed = self.at.saturating_duration_since(self.started);
+        Some(elapsed.mul_f64((1.0 - fraction) / fraction))
+    }
+}
+
+// This sections are the beginning of progress module unit tests.
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    #[test]
+    fn eta_should_extrapolate_the_pace_of_the_job() {
+        let started = Instant::now();
+        let mut update = ProgressUpdate {
+            job: JobId::new(1),
+            worker: 0,
+            fraction: Some(0.25),
+            message: None,
+            started,
+            at: started + Duration::from_secs(10),
+        };
+        assert_eq!(Some(Duration::from_secs(30)), update.eta());
+        update.fraction = Some(0.0);
+        assert_eq!(None, update.eta());
+    }
+}
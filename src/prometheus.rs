@@ -0,0 +1,120 @@
+//! ## Prometheus
+//!
+//! This module, enabled by the `prometheus` feature, renders the counters
+//! of a pool in the Prometheus text exposition format, so they can be
+//! served on the metrics endpoint of a service and scraped with the rest
+//! of its metrics.
+
+use crate::stats::PoolStats;
+use std::fmt::Write;
+
+// The name, type, help text and value of every exported metric.
+fn metrics(stats: &PoolStats) -> [(&'static str, &'static str, &'static str, usize); 5] {
+    [
+        (
+            "workerpool_jobs_submitted_total",
+            "counter",
+            "Jobs accepted by the pool.",
+            stats.submitted,
+        ),
+        (
+            "workerpool_jobs_completed_total",
+            "counter",
+            "Jobs that finished, including the ones that panicked.",
+            stats.completed,
+        ),
+        (
+            "workerpool_jobs_panicked_total",
+            "counter",
+            "Jobs that panicked.",
+            stats.panicked,
+        ),
+        (
+            "workerpool_queue_depth",
+            "gauge",
+            "Jobs waiting in the queue.",
+            stats.queued,
+        ),
+        (
+            "workerpool_busy_workers",
+            "gauge",
+            "Workers executing a job.",
+            stats.busy_workers,
+        ),
+    ]
+}
+
+// Escapes a label value as the text format requires.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Renders the stats in the Prometheus text exposition format, with the
+/// given labels on every sample.
+///
+/// **stats**: &PoolStats - The snapshot to render. \
+/// **labels**: &[(&str, &str)] - The label names and values, as
+/// `[("pool", "io")]`. \
+/// **returns**: the text to serve on a metrics endpoint.
+///
+/// ## Examples
+///
+/// ```
+/// use workerpool_rs::pool::WorkerPool;
+/// use workerpool_rs::prometheus;
+///
+/// let pool = WorkerPool::new(2);
+/// pool.execute(|| {}).unwrap();
+/// pool.join();
+///
+/// let text = prometheus::encode(&pool.stats(), &[("pool", "io")]);
+/// assert!(text.contains("workerpool_jobs_completed_total{pool=\"io\"} 1\n"));
+/// ```
+pub fn encode(stats: &PoolStats, labels: &[(&str, &str)]) -> String {
+    let labels = if labels.is_empty() {
+        String::new()
+    } else {
+        let pairs: Vec<_> = labels
+            .iter()
+            .map(|(name, value)| format!("{}=\"{}\"", name, escape(value)))
+            .collect();
+        format!("{{{}}}", pairs.join(","))
+    };
+
+    let mut text = String::new();
+    for (name, kind, help, value) in metrics(stats).iter() {
+        let _ = writeln!(text, "# HELP {} {}", name, help);
+        let _ = writeln!(text, "# TYPE {} {}", name, kind);
+        let _ = writeln!(text, "{}{} {}", name, labels, value);
+    }
+    text
+}
+
+// This sections are the beginning of prometheus module unit tests.
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    #[test]
+    fn encode_should_render_every_metric() {
+        let stats = PoolStats {
+            submitted: 5,
+            queued: 2,
+            ..PoolStats::default()
+        };
+        let text = encode(&stats, &[]);
+        assert!(text.contains("# TYPE workerpool_jobs_submitted_total counter\n"));
+        assert!(text.contains("workerpool_jobs_submitted_total 5\n"));
+        assert!(text.contains("workerpool_queue_depth 2\n"));
+        assert_eq!(15, text.lines().count());
+    }
+
+    #[test]
+    fn encode_should_escape_label_values() {
+        let text = encode(&PoolStats::default(), &[("pool", "a\"b\\c")]);
+        assert!(text.contains("workerpool_busy_workers{pool=\"a\\\"b\\\\c\"} 0\n"));
+    }
+}
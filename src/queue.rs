@@ -0,0 +1,953 @@
+//! ## Queue
+//!
+//! This module contains the job queue shared by the pool, its senders and
+//! its workers. Unlike a channel, the queue can be closed by the pool even
+//! while senders are still alive. Jobs are exchanged through lock-free
+//! rings, and the lock is only taken to park and wake up threads. A lock
+//! poisoned by a panicking thread is recovered, as the data it guards is
+//! always left consistent, so one bad thread can't disable the pool.
+//!
+//! ### Channel backends
+//!
+//! With the `crossbeam` feature, the rings of the priority levels are
+//! bounded `crossbeam-channel` channels instead of the std-only rings of
+//! the crate. The priorities, LIFO order, work stealing and closing are
+//! kept by the queue around them.
+
+#[cfg(feature = "crossbeam")]
+use crate::channel::Ring;
+#[cfg(not(feature = "crossbeam"))]
+use crate::ring::Ring;
+use crate::{
+    pool::{DispatchOrder, ExecuteError, IdleStrategy, Priority},
+    stats::{Histogram, Percentiles},
+    task::Task,
+};
+use std::{
+    cmp::Ordering as Order,
+    collections::{BinaryHeap, VecDeque},
+    hint,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Condvar, Mutex, MutexGuard, PoisonError,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+// A job with the time it was queued, in nanoseconds since the queue epoch.
+type Stamped = (u64, Task);
+
+// The number of priority levels, one FIFO or LIFO per level.
+const LEVELS: usize = 3;
+
+// The number of jobs each level holds in its ring before spilling into
+// its overflow list.
+const RING_CAPACITY: usize = 1024;
+
+// The number of jobs a worker moves from an overflow list back into the
+// ring when it takes a job from the list, so the next pops don't lock.
+const REFILL_BATCH: usize = 32;
+
+// A FIFO of jobs stamped with the time they were queued. Jobs go to the
+// lock-free ring while it has room and the overflow list is empty, so
+// jobs in the ring are always older than jobs in the overflow list. A
+// LIFO level doesn't use the ring, and its overflow list is a stack.
+//
+// ring: Ring<Task> - the lock-free part of the FIFO.\
+// overflow: Mutex<VecDeque<Stamped>> - the jobs that didn't fit.\
+// overflowed: AtomicUsize - the length of the overflow list.\
+// lifo: bool - whether the newest job is popped first.
+struct Level {
+    ring: Ring<Task>,
+    overflow: Mutex<VecDeque<Stamped>>,
+    overflowed: AtomicUsize,
+    lifo: bool,
+}
+
+impl Level {
+    fn new() -> Level {
+        Level {
+            ring: Ring::new(RING_CAPACITY),
+            overflow: Mutex::new(VecDeque::new()),
+            overflowed: AtomicUsize::new(0),
+            lifo: false,
+        }
+    }
+
+    fn overflow(&self) -> MutexGuard<'_, VecDeque<Stamped>> {
+        self.overflow.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn push<I: IntoIterator<Item = Task>>(&self, stamp: u64, jobs: I) {
+        let mut jobs = jobs.into_iter();
+        let mut spilled = None;
+        if !self.lifo && self.overflowed.load(Ordering::SeqCst) == 0 {
+            spilled = jobs.find_map(|job| self.ring.push(stamp, job).err());
+        }
+        let Some(first) = spilled.or_else(|| jobs.next()) else {
+            return;
+        };
+        let mut overflow = self.overflow();
+        let before = overflow.len();
+        overflow.extend(std::iter::once(first).chain(jobs).map(|job| (stamp, job)));
+        self.overflowed
+            .fetch_add(overflow.len() - before, Ordering::SeqCst);
+    }
+
+    // Pops the oldest job, or the newest one of a LIFO level. A job taken
+    // from the overflow list of a FIFO level brings a batch of the next
+    // ones into the ring.
+    fn pop(&self) -> Option<Stamped> {
+        if let Some(entry) = self.ring.pop() {
+            return Some(entry);
+        }
+        if self.overflowed.load(Ordering::SeqCst) == 0 {
+            return None;
+        }
+        let mut overflow = self.overflow();
+        if self.lifo {
+            let entry = overflow.pop_back()?;
+            self.overflowed.fetch_sub(1, Ordering::SeqCst);
+            return Some(entry);
+        }
+        let entry = overflow.pop_front()?;
+        let mut taken = 1;
+        while taken <= REFILL_BATCH {
+            let Some((stamp, next)) = overflow.pop_front() else {
+                break;
+            };
+            if let Err(next) = self.ring.push(stamp, next) {
+                overflow.push_front((stamp, next));
+                break;
+            }
+            taken += 1;
+        }
+        self.overflowed.fetch_sub(taken, Ordering::SeqCst);
+        Some(entry)
+    }
+
+    // Returns whether the level has no job.
+    fn is_empty(&self) -> bool {
+        self.ring.is_empty() && self.overflowed.load(Ordering::SeqCst) == 0
+    }
+
+    // Returns the stamp of the job pop would return.
+    fn front_stamp(&self) -> Option<u64> {
+        self.ring.front_stamp().or_else(|| {
+            if self.overflowed.load(Ordering::SeqCst) == 0 {
+                return None;
+            }
+            let overflow = self.overflow();
+            let next = if self.lifo {
+                overflow.back()
+            } else {
+                overflow.front()
+            };
+            next.map(|(stamp, _)| *stamp)
+        })
+    }
+}
+
+// A job queued with a deadline. The heap of these jobs is a max-heap, so
+// the earliest deadline is the greatest, and equal deadlines keep their
+// queueing order.
+struct Deadlined {
+    deadline: Instant,
+    seq: u64,
+    stamp: u64,
+    job: Task,
+}
+
+impl PartialEq for Deadlined {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Order::Equal
+    }
+}
+
+impl Eq for Deadlined {}
+
+impl PartialOrd for Deadlined {
+    fn partial_cmp(&self, other: &Self) -> Option<Order> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Deadlined {
+    fn cmp(&self, other: &Self) -> Order {
+        (other.deadline, other.seq).cmp(&(self.deadline, self.seq))
+    }
+}
+
+// A multi-level queue of jobs, optionally bounded. Jobs are popped from
+// the highest priority level, but a queued job gains one level for each
+// aging period it waits, so low priority jobs can't starve.
+//
+// levels: [Level; LEVELS] - one FIFO or LIFO per priority level.\
+// len: AtomicUsize - the number of queued jobs, counting pushes in flight.\
+// closed: AtomicBool - set once the queue rejects new jobs.\
+// sleepers: AtomicUsize - the number of workers parked in pop that no
+// push has woken up yet.\
+// blocked: AtomicUsize - the number of producers parked in push.\
+// wakeups: Mutex<usize> - the wake-ups sent to parked workers and not
+// consumed yet, and the lock taken to park and wake up threads.\
+// available: Condvar - notified when a job is pushed or the queue closes.\
+// space: Condvar - notified when a job is popped or the queue closes.\
+// capacity: Option<usize> - the maximum number of queued jobs.\
+// aging: u64 - the waiting time, in nanoseconds, that promotes a job one
+// level.\
+// epoch: Instant - the origin of the job stamps.\
+// locals: Box<[Mutex<VecDeque<Stamped>>]> - one deque per worker in
+// work-stealing mode, and none otherwise.\
+// spins: u32 - the busy polls of an idle worker before it yields.\
+// yields: u32 - the yielding polls of an idle worker before it parks.\
+// taken: AtomicU64 - the number of jobs popped by workers.\
+// waited: AtomicU64 - the total time, in nanoseconds, those jobs waited.\
+// wait_histogram: Histogram - the distribution of those waiting times.\
+// idle_timeout: Option<Duration> - how long a parked worker waits for a job
+// before pop gives up.\
+// deadlines: Mutex<BinaryHeap<Deadlined>> - the jobs queued with a
+// deadline, taken before the levels.\
+// deadlined: AtomicUsize - the length of the deadline heap.\
+// next_seq: AtomicU64 - the queueing order of the next deadline job.
+pub(crate) struct JobQueue {
+    levels: [Level; LEVELS],
+    len: AtomicUsize,
+    closed: AtomicBool,
+    sleepers: AtomicUsize,
+    blocked: AtomicUsize,
+    wakeups: Mutex<usize>,
+    available: Condvar,
+    space: Condvar,
+    capacity: Option<usize>,
+    aging: u64,
+    epoch: Instant,
+    locals: Box<[Mutex<VecDeque<Stamped>>]>,
+    spins: u32,
+    yields: u32,
+    taken: AtomicU64,
+    waited: AtomicU64,
+    wait_histogram: Histogram,
+    idle_timeout: Option<Duration>,
+    deadlines: Mutex<BinaryHeap<Deadlined>>,
+    deadlined: AtomicUsize,
+    next_seq: AtomicU64,
+}
+
+impl JobQueue {
+    pub(crate) fn new(capacity: Option<usize>, aging: Duration) -> JobQueue {
+        JobQueue {
+            levels: [Level::new(), Level::new(), Level::new()],
+            len: AtomicUsize::new(0),
+            closed: AtomicBool::new(false),
+            sleepers: AtomicUsize::new(0),
+            blocked: AtomicUsize::new(0),
+            wakeups: Mutex::new(0),
+            available: Condvar::new(),
+            space: Condvar::new(),
+            capacity,
+            aging: (aging.as_nanos() as u64).max(1),
+            epoch: Instant::now(),
+            locals: Box::new([]),
+            spins: 0,
+            yields: 0,
+            taken: AtomicU64::new(0),
+            waited: AtomicU64::new(0),
+            wait_histogram: Histogram::new(),
+            idle_timeout: None,
+            deadlines: Mutex::new(BinaryHeap::new()),
+            deadlined: AtomicUsize::new(0),
+            next_seq: AtomicU64::new(0),
+        }
+    }
+
+    // Sets how idle workers poll the queue before they park.
+    pub(crate) fn idle_strategy(mut self, strategy: IdleStrategy) -> JobQueue {
+        (self.spins, self.yields) = match strategy {
+            IdleStrategy::Park => (0, 0),
+            IdleStrategy::SpinThenPark { spins, yields } => (spins, yields),
+        };
+        self
+    }
+
+    // Sets whether the levels hand out their oldest or newest job first.
+    pub(crate) fn dispatch_order(mut self, order: DispatchOrder) -> JobQueue {
+        for level in self.levels.iter_mut() {
+            level.lifo = order == DispatchOrder::Lifo;
+        }
+        self
+    }
+
+    // Makes pop return None when a parked worker got no job for timeout.
+    pub(crate) fn idle_timeout(mut self, timeout: Duration) -> JobQueue {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    pub(crate) fn has_idle_timeout(&self) -> bool {
+        self.idle_timeout.is_some()
+    }
+
+    // Gives each of the workers a local deque. A worker pops the newest
+    // job of its own deque first, then the shared levels, and then steals
+    // the oldest job of another worker's deque.
+    pub(crate) fn work_stealing(mut self, workers: usize) -> JobQueue {
+        self.locals = (0..workers).map(|_| Mutex::new(VecDeque::new())).collect();
+        self
+    }
+
+    fn lock(&self) -> MutexGuard<'_, usize> {
+        self.wakeups.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn now(&self) -> u64 {
+        self.epoch.elapsed().as_nanos() as u64
+    }
+
+    // A bounded queue accepts one extra job per parked worker, so a
+    // capacity of zero hands jobs off directly.
+    fn is_full(&self, len: usize) -> bool {
+        match self.capacity {
+            Some(capacity) => len >= capacity + self.sleepers.load(Ordering::SeqCst),
+            None => false,
+        }
+    }
+
+    // Reserves room for a job. The reservation is counted in len before the
+    // closed flag is checked, so a worker that saw the queue closed and
+    // empty can't miss a job pushed concurrently.
+    pub(crate) fn reserve(&self) -> Result<(), ExecuteError> {
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(ExecuteError::Disconnected);
+        }
+        let mut len = self.len.load(Ordering::SeqCst);
+        loop {
+            if self.is_full(len) {
+                return Err(ExecuteError::QueueFull);
+            }
+            match self
+                .len
+                .compare_exchange_weak(len, len + 1, Ordering::SeqCst, Ordering::SeqCst)
+            {
+                Ok(_) => break,
+                Err(current) => len = current,
+            }
+        }
+        if self.closed.load(Ordering::SeqCst) {
+            self.release();
+            return Err(ExecuteError::Disconnected);
+        }
+        Ok(())
+    }
+
+    // Gives back a reservation or the room of a popped job.
+    fn release(&self) {
+        self.len.fetch_sub(1, Ordering::SeqCst);
+        if self.blocked.load(Ordering::SeqCst) > 0 {
+            let _lock = self.lock();
+            self.space.notify_one();
+        }
+    }
+
+    // Inserts a job into its level, after room was reserved for it.
+    pub(crate) fn insert(&self, job: Task, priority: Priority) {
+        self.levels[priority as usize].push(self.now(), Some(job));
+        self.wake(1);
+    }
+
+    // Wakes up to count parked workers that no other push woke up already.
+    fn wake(&self, count: usize) {
+        if self.sleepers.load(Ordering::SeqCst) > 0 {
+            let mut wakeups = self.lock();
+            let woken = count.min(self.sleepers.load(Ordering::SeqCst));
+            self.sleepers.fetch_sub(woken, Ordering::SeqCst);
+            *wakeups += woken;
+            for _ in 0..woken {
+                self.available.notify_one();
+            }
+        }
+    }
+
+    fn deadlines(&self) -> MutexGuard<'_, BinaryHeap<Deadlined>> {
+        self.deadlines
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Pushes a job, blocking while the queue is full.
+    pub(crate) fn push(&self, job: Task, priority: Priority) -> Result<(), ExecuteError> {
+        self.wait_room(None)?;
+        self.insert(job, priority);
+        Ok(())
+    }
+
+    // Pushes a job taken before every job with a later deadline and every
+    // job without one, blocking while the queue is full.
+    pub(crate) fn push_deadline(&self, job: Task, deadline: Instant) -> Result<(), ExecuteError> {
+        self.wait_room(None)?;
+        let entry = Deadlined {
+            deadline,
+            seq: self.next_seq.fetch_add(1, Ordering::SeqCst),
+            stamp: self.now(),
+            job,
+        };
+        self.deadlines().push(entry);
+        self.deadlined.fetch_add(1, Ordering::SeqCst);
+        self.wake(1);
+        Ok(())
+    }
+
+    // Reserves room for a job, blocking while the queue is full, or until
+    // the deadline, if any, when it fails with ExecuteError::Timeout. The
+    // room must then be filled with insert.
+    pub(crate) fn wait_room(&self, deadline: Option<Instant>) -> Result<(), ExecuteError> {
+        loop {
+            match self.reserve() {
+                Ok(()) => break,
+                Err(ExecuteError::QueueFull) => {
+                    let now = Instant::now();
+                    if deadline.is_some_and(|deadline| now >= deadline) {
+                        return Err(ExecuteError::Timeout);
+                    }
+                    let lock = self.lock();
+                    self.blocked.fetch_add(1, Ordering::SeqCst);
+                    let full = self.is_full(self.len.load(Ordering::SeqCst));
+                    if full && !self.closed.load(Ordering::SeqCst) {
+                        match deadline {
+                            Some(deadline) => drop(
+                                self.space
+                                    .wait_timeout(lock, deadline - now)
+                                    .unwrap_or_else(PoisonError::into_inner),
+                            ),
+                            None => drop(
+                                self.space
+                                    .wait(lock)
+                                    .unwrap_or_else(PoisonError::into_inner),
+                            ),
+                        }
+                    }
+                    self.blocked.fetch_sub(1, Ordering::SeqCst);
+                }
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(())
+    }
+
+    // Pushes every job of an unbounded queue at once, or none of them if
+    // the queue is closed.
+    pub(crate) fn push_batch(
+        &self,
+        jobs: Vec<Task>,
+        priority: Priority,
+    ) -> Result<(), ExecuteError> {
+        debug_assert!(self.capacity.is_none());
+        let count = jobs.len();
+        self.len.fetch_add(count, Ordering::SeqCst);
+        if self.closed.load(Ordering::SeqCst) {
+            self.len.fetch_sub(count, Ordering::SeqCst);
+            return Err(ExecuteError::Disconnected);
+        }
+        self.levels[priority as usize].push(self.now(), jobs);
+        self.wake(count);
+        Ok(())
+    }
+
+    pub(crate) fn is_bounded(&self) -> bool {
+        self.capacity.is_some()
+    }
+
+    // Pushes a job to the local deque of a worker, or to the normal level
+    // when the queue has no deque for it. Local jobs ignore the capacity,
+    // as blocking a worker on its own queue could never finish.
+    pub(crate) fn push_local(&self, worker: usize, job: Task) -> Result<(), ExecuteError> {
+        self.len.fetch_add(1, Ordering::SeqCst);
+        if self.closed.load(Ordering::SeqCst) {
+            self.release();
+            return Err(ExecuteError::Disconnected);
+        }
+        self.insert_local(worker, job);
+        Ok(())
+    }
+
+    // Inserts a job into the local deque of a worker, or into the normal
+    // level when the queue has no deque for it, after room was reserved
+    // for it.
+    pub(crate) fn insert_local(&self, worker: usize, job: Task) {
+        let Some(local) = self.locals.get(worker) else {
+            return self.insert(job, Priority::Normal);
+        };
+        let stamp = self.now();
+        local
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push_back((stamp, job));
+        self.wake(1);
+    }
+
+    // Swaps the next job of a full queue for a new one, keeping the queue
+    // length, and returns the job taken out.
+    pub(crate) fn replace_next(&self, job: Task, priority: Priority) -> Option<Task> {
+        let (_, next) = self.take()?;
+        self.insert(job, priority);
+        Some(next)
+    }
+
+    // Pushes a job that continues work the pool already accepted, so it
+    // ignores both the capacity and the closed flag.
+    pub(crate) fn force_push(&self, job: Task, priority: Priority) {
+        self.len.fetch_add(1, Ordering::SeqCst);
+        self.insert(job, priority);
+    }
+
+    // Takes the job with the earliest deadline, without blocking.
+    fn take_deadline(&self) -> Option<Stamped> {
+        if self.deadlined.load(Ordering::SeqCst) == 0 {
+            return None;
+        }
+        let entry = self.deadlines().pop()?;
+        self.deadlined.fetch_sub(1, Ordering::SeqCst);
+        Some((entry.stamp, entry.job))
+    }
+
+    // Takes the job with the earliest deadline, or else the job at the
+    // front of the level with the best aged priority, without blocking.
+    // Ties are won by the higher level. The stamps are only compared when
+    // several levels hold jobs.
+    fn take(&self) -> Option<Stamped> {
+        if let Some(entry) = self.take_deadline() {
+            return Some(entry);
+        }
+        loop {
+            let mut filled = (0..LEVELS).filter(|&level| !self.levels[level].is_empty());
+            match (filled.next(), filled.next()) {
+                (None, _) => return None,
+                (Some(level), None) => match self.levels[level].pop() {
+                    Some(entry) => return Some(entry),
+                    None => continue,
+                },
+                _ => {}
+            }
+            let now = self.now();
+            let mut best: Option<(u64, usize)> = None;
+            for level in (0..LEVELS).rev() {
+                if let Some(stamp) = self.levels[level].front_stamp() {
+                    let score = level as u64 + now.saturating_sub(stamp) / self.aging;
+                    if best.is_none_or(|(best_score, _)| score > best_score) {
+                        best = Some((score, level));
+                    }
+                }
+            }
+            let (_, level) = best?;
+            if let Some(entry) = self.levels[level].pop() {
+                return Some(entry);
+            }
+        }
+    }
+
+    // Takes the next job for a worker, without blocking.
+    fn take_for(&self, worker: usize) -> Option<Stamped> {
+        if let Some(local) = self.locals.get(worker) {
+            if let Some(entry) = local
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .pop_back()
+            {
+                return Some(entry);
+            }
+        }
+        self.take().or_else(|| self.steal(worker))
+    }
+
+    // Takes the oldest job of another worker's deque, starting with the
+    // next worker.
+    fn steal(&self, worker: usize) -> Option<Stamped> {
+        let count = self.locals.len();
+        (1..count).find_map(|offset| {
+            let victim = &self.locals[(worker + offset) % count];
+            victim
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .pop_front()
+        })
+    }
+
+    // Pops the next job for a worker, if there is one, without blocking.
+    pub(crate) fn try_pop(&self, worker: usize) -> Option<Task> {
+        let (stamp, job) = self.take_for(worker)?;
+        self.release();
+        let waited = self.now().saturating_sub(stamp);
+        self.waited.fetch_add(waited, Ordering::Relaxed);
+        self.taken.fetch_add(1, Ordering::Relaxed);
+        self.wait_histogram.record(Duration::from_nanos(waited));
+        Some(job)
+    }
+
+    // Pops the next job for a worker, blocking while the queue is empty.
+    // Returns None once the queue is closed and empty, as soon as the
+    // worker is retired, or when it stayed parked for the idle timeout.
+    pub(crate) fn pop(&self, worker: usize, retired: &AtomicBool) -> Option<Task> {
+        let mut polls = 0;
+        loop {
+            if retired.load(Ordering::SeqCst) {
+                // The wake-up this worker consumed may belong to a job.
+                if self.len.load(Ordering::SeqCst) > 0 {
+                    self.wake(1);
+                }
+                return None;
+            }
+            if let Some(job) = self.try_pop(worker) {
+                return Some(job);
+            }
+            if self.len.load(Ordering::SeqCst) > 0 {
+                // A push is in flight or another worker took the job.
+                thread::yield_now();
+                continue;
+            }
+            if polls < self.spins {
+                polls += 1;
+                hint::spin_loop();
+                continue;
+            }
+            if polls < self.spins.saturating_add(self.yields) {
+                polls += 1;
+                thread::yield_now();
+                continue;
+            }
+            polls = 0;
+            let mut wakeups = self.lock();
+            self.sleepers.fetch_add(1, Ordering::SeqCst);
+            if self.blocked.load(Ordering::SeqCst) > 0 {
+                self.space.notify_one();
+            }
+            let empty = self.len.load(Ordering::SeqCst) == 0;
+            let closed = self.closed.load(Ordering::SeqCst);
+            if !empty || closed {
+                self.sleepers.fetch_sub(1, Ordering::SeqCst);
+                if closed && empty {
+                    return None;
+                }
+                continue;
+            }
+            let deadline = self.idle_timeout.map(|timeout| Instant::now() + timeout);
+            while *wakeups == 0
+                && !self.closed.load(Ordering::SeqCst)
+                && !retired.load(Ordering::SeqCst)
+            {
+                let Some(deadline) = deadline else {
+                    wakeups = self
+                        .available
+                        .wait(wakeups)
+                        .unwrap_or_else(PoisonError::into_inner);
+                    continue;
+                };
+                let now = Instant::now();
+                if now >= deadline {
+                    self.sleepers.fetch_sub(1, Ordering::SeqCst);
+                    return None;
+                }
+                wakeups = self
+                    .available
+                    .wait_timeout(wakeups, deadline - now)
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .0;
+            }
+            if *wakeups > 0 {
+                *wakeups -= 1;
+            } else {
+                self.sleepers.fetch_sub(1, Ordering::SeqCst);
+            }
+        }
+    }
+
+    // Wakes up every parked worker, so the retired ones can leave.
+    pub(crate) fn wake_all(&self) {
+        let _lock = self.lock();
+        self.available.notify_all();
+    }
+
+    // Rejects every later push and wakes up every parked thread. Jobs
+    // already queued can still be popped.
+    pub(crate) fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        let _lock = self.lock();
+        self.available.notify_all();
+        self.space.notify_all();
+    }
+
+    // Removes and returns every queued job, by deadline, then from the
+    // highest level down, and then the jobs of the local deques.
+    pub(crate) fn drain(&self) -> Vec<Task> {
+        let mut jobs: Vec<_> = std::iter::from_fn(|| self.take_deadline())
+            .chain(
+                self.levels
+                    .iter()
+                    .rev()
+                    .flat_map(|level| std::iter::from_fn(move || level.pop())),
+            )
+            .map(|(_, job)| job)
+            .collect();
+        for local in self.locals.iter() {
+            let mut local = local.lock().unwrap_or_else(PoisonError::into_inner);
+            jobs.extend(local.drain(..).map(|(_, job)| job));
+        }
+        self.len.fetch_sub(jobs.len(), Ordering::SeqCst);
+        let _lock = self.lock();
+        self.space.notify_all();
+        jobs
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len.load(Ordering::SeqCst)
+    }
+
+    // Returns the number of jobs popped by workers, the total time they
+    // waited in the queue and the percentiles of that time.
+    pub(crate) fn waited(&self) -> (u64, Duration, Percentiles) {
+        let taken = self.taken.load(Ordering::Relaxed);
+        let waited = Duration::from_nanos(self.waited.load(Ordering::Relaxed));
+        (taken, waited, self.wait_histogram.percentiles())
+    }
+}
+
+// This sections are the beginning of queue module unit tests.
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use std::sync::{mpsc, Arc};
+
+    const AGING: Duration = Duration::from_secs(60);
+    static WORKING: AtomicBool = AtomicBool::new(false);
+
+    // Returns a job that sends id through tx.
+    fn send_job(tx: &mpsc::Sender<i32>, id: i32) -> Task {
+        let tx = tx.clone();
+        Task::new(move || tx.send(id).unwrap())
+    }
+
+    #[test]
+    fn queue_should_pop_in_fifo_order() {
+        let queue = JobQueue::new(None, AGING);
+        let (tx, rx) = mpsc::channel();
+        for i in 0..3 {
+            queue.push(send_job(&tx, i), Priority::Normal).unwrap();
+        }
+        while queue.len() > 0 {
+            queue.pop(0, &WORKING).unwrap().run();
+        }
+        assert_eq!(vec![0, 1, 2], rx.try_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn bounded_queue_should_reject_when_full() {
+        let queue = JobQueue::new(Some(1), AGING);
+        queue.reserve().unwrap();
+        queue.insert(Task::new(|| {}), Priority::Low);
+        assert_eq!(Err(ExecuteError::QueueFull), queue.reserve());
+    }
+
+    #[test]
+    fn closed_queue_should_reject_and_drain() {
+        let queue = JobQueue::new(None, AGING);
+        queue.push(Task::new(|| {}), Priority::Normal).unwrap();
+        queue.close();
+        assert_eq!(
+            Err(ExecuteError::Disconnected),
+            queue.push(Task::new(|| {}), Priority::Normal)
+        );
+        assert!(queue.pop(0, &WORKING).is_some());
+        assert!(queue.pop(0, &WORKING).is_none());
+    }
+
+    #[test]
+    fn queue_should_keep_working_after_its_lock_is_poisoned() {
+        let queue = Arc::new(JobQueue::new(None, AGING));
+        let poisoner = Arc::clone(&queue);
+        let _ = std::thread::spawn(move || {
+            let _lock = poisoner.lock();
+            panic!("poison the queue lock");
+        })
+        .join();
+        assert!(queue.wakeups.is_poisoned());
+        let (tx, rx) = mpsc::channel();
+        queue.push(send_job(&tx, 1), Priority::Normal).unwrap();
+        queue.pop(0, &WORKING).unwrap().run();
+        queue.close();
+        assert!(queue.pop(0, &WORKING).is_none());
+        assert_eq!(Ok(1), rx.try_recv());
+    }
+
+    #[test]
+    fn queue_should_pop_higher_priority_first() {
+        let queue = JobQueue::new(None, AGING);
+        let (tx, rx) = mpsc::channel();
+        queue.push(send_job(&tx, 0), Priority::Low).unwrap();
+        queue.push(send_job(&tx, 1), Priority::Normal).unwrap();
+        queue.push(send_job(&tx, 2), Priority::High).unwrap();
+        while queue.len() > 0 {
+            queue.pop(0, &WORKING).unwrap().run();
+        }
+        assert_eq!(vec![2, 1, 0], rx.try_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn queue_should_pop_earliest_deadline_first() {
+        let queue = JobQueue::new(None, AGING);
+        let (tx, rx) = mpsc::channel();
+        let now = Instant::now();
+        queue.push(send_job(&tx, 0), Priority::High).unwrap();
+        for (id, ms) in [(1, 30), (2, 10), (3, 20), (4, 10)] {
+            let deadline = now + Duration::from_millis(ms);
+            queue.push_deadline(send_job(&tx, id), deadline).unwrap();
+        }
+        while queue.len() > 0 {
+            queue.pop(0, &WORKING).unwrap().run();
+        }
+        assert_eq!(vec![2, 4, 3, 1, 0], rx.try_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn queue_should_promote_aged_jobs() {
+        let queue = JobQueue::new(None, Duration::from_millis(5));
+        let (tx, rx) = mpsc::channel();
+        queue.push(send_job(&tx, 0), Priority::Low).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        queue.push(send_job(&tx, 1), Priority::High).unwrap();
+        queue.pop(0, &WORKING).unwrap().run();
+        assert_eq!(Ok(0), rx.try_recv());
+    }
+
+    #[test]
+    fn queue_should_spill_into_overflow_in_order() {
+        let queue = JobQueue::new(None, AGING);
+        let (tx, rx) = mpsc::channel();
+        let count = RING_CAPACITY as i32 + 10;
+        for i in 0..count {
+            queue.push(send_job(&tx, i), Priority::Normal).unwrap();
+        }
+        assert_eq!(count as usize, queue.len());
+        while queue.len() > 0 {
+            queue.pop(0, &WORKING).unwrap().run();
+        }
+        assert_eq!(
+            (0..count).collect::<Vec<_>>(),
+            rx.try_iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn worker_should_pop_local_jobs_newest_first_and_steal_oldest() {
+        let queue = JobQueue::new(None, AGING).work_stealing(2);
+        let (tx, rx) = mpsc::channel();
+        for i in 0..3 {
+            queue.push_local(0, send_job(&tx, i)).unwrap();
+        }
+        queue.pop(0, &WORKING).unwrap().run();
+        queue.pop(1, &WORKING).unwrap().run();
+        assert_eq!(vec![2, 0], rx.try_iter().collect::<Vec<_>>());
+        assert_eq!(1, queue.len());
+        assert_eq!(1, queue.drain().len());
+    }
+
+    #[test]
+    fn batch_should_keep_order_across_overflow() {
+        let queue = JobQueue::new(None, AGING);
+        let (tx, rx) = mpsc::channel();
+        let count = (RING_CAPACITY + REFILL_BATCH * 2) as i32;
+        queue.push(send_job(&tx, -1), Priority::Normal).unwrap();
+        let jobs = (0..count).map(|i| send_job(&tx, i)).collect();
+        queue.push_batch(jobs, Priority::Normal).unwrap();
+        assert_eq!(count as usize + 1, queue.len());
+        while queue.len() > 0 {
+            queue.pop(0, &WORKING).unwrap().run();
+        }
+        assert_eq!(
+            (-1..count).collect::<Vec<_>>(),
+            rx.try_iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn spinning_worker_should_pop_and_see_close() {
+        let strategy = IdleStrategy::SpinThenPark {
+            spins: 100,
+            yields: 10,
+        };
+        let queue = Arc::new(JobQueue::new(None, AGING).idle_strategy(strategy));
+        let worker = Arc::clone(&queue);
+        let handle = std::thread::spawn(move || {
+            let mut popped = 0;
+            while let Some(job) = worker.pop(0, &WORKING) {
+                job.run();
+                popped += 1;
+            }
+            popped
+        });
+        for _ in 0..3 {
+            queue.push(Task::new(|| {}), Priority::Normal).unwrap();
+        }
+        while queue.len() > 0 {
+            std::thread::yield_now();
+        }
+        queue.close();
+        assert_eq!(3, handle.join().unwrap());
+    }
+
+    #[test]
+    fn retired_worker_should_leave_a_parked_pop() {
+        let queue = Arc::new(JobQueue::new(None, AGING));
+        let retired = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let queue = Arc::clone(&queue);
+            let retired = Arc::clone(&retired);
+            std::thread::spawn(move || queue.pop(0, &retired).is_none())
+        };
+        std::thread::sleep(Duration::from_millis(10));
+        retired.store(true, Ordering::SeqCst);
+        queue.wake_all();
+        assert!(handle.join().unwrap());
+    }
+
+    #[test]
+    fn idle_pop_should_give_up_after_the_timeout() {
+        let queue = JobQueue::new(None, AGING).idle_timeout(Duration::from_millis(10));
+        assert!(queue.pop(0, &WORKING).is_none());
+        assert!(!queue.is_closed());
+        queue.push(Task::new(|| {}), Priority::Normal).unwrap();
+        assert!(queue.pop(0, &WORKING).is_some());
+    }
+
+    #[test]
+    fn full_queue_should_time_out_waiting_for_room() {
+        let queue = JobQueue::new(Some(1), AGING);
+        queue.push(Task::new(|| {}), Priority::Normal).unwrap();
+        let deadline = Instant::now() + Duration::from_millis(10);
+        assert_eq!(Err(ExecuteError::Timeout), queue.wait_room(Some(deadline)));
+        assert!(Instant::now() >= deadline);
+        queue.pop(0, &WORKING).unwrap().run();
+        assert_eq!(Ok(()), queue.wait_room(Some(deadline)));
+        queue.insert(Task::new(|| {}), Priority::Normal);
+        assert_eq!(1, queue.len());
+    }
+
+    #[test]
+    fn lifo_queue_should_pop_newest_first_within_a_level() {
+        let queue = JobQueue::new(None, AGING).dispatch_order(DispatchOrder::Lifo);
+        let (tx, rx) = mpsc::channel();
+        for i in 0..3 {
+            queue.push(send_job(&tx, i), Priority::Normal).unwrap();
+        }
+        queue.push(send_job(&tx, 3), Priority::Low).unwrap();
+        queue
+            .push_batch(vec![send_job(&tx, 4), send_job(&tx, 5)], Priority::High)
+            .unwrap();
+        while queue.len() > 0 {
+            queue.pop(0, &WORKING).unwrap().run();
+        }
+        assert_eq!(vec![5, 4, 2, 1, 0, 3], rx.try_iter().collect::<Vec<_>>());
+    }
+}
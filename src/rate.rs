@@ -0,0 +1,176 @@
+//! ## Rate
+//!
+//! This module contains the rate limiter enabled with
+//! [`WorkerPoolBuilder::rate_limit`](crate::pool::WorkerPoolBuilder::rate_limit).
+//! It is a token bucket: a job needs a token to go on, tokens are refilled
+//! at a steady rate, and up to a burst of them can be saved while the pool
+//! is quiet.
+
+use std::{
+    sync::{Mutex, PoisonError},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Defines where a job waits when the rate limit is reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RatePolicy {
+    /// Jobs are queued right away, and workers wait before starting them.
+    #[default]
+    Queue,
+    /// Submitting a job blocks the caller until the job may start, while
+    /// `try_execute` fails with `ExecuteError::QueueFull`.
+    Block,
+}
+
+/// The settings of the rate limiter. The fields not given to
+/// [`per_second`](RateLimit::per_second) can be overridden with the struct
+/// update syntax.
+///
+/// # Examples
+///
+/// ```
+/// use workerpool_rs::pool::WorkerPoolBuilder;
+/// use workerpool_rs::rate::{RateLimit, RatePolicy};
+/// use std::time::{Duration, Instant};
+///
+/// let pool = WorkerPoolBuilder::new()
+///     .num_workers(4)
+///     .rate_limit(RateLimit {
+///         burst: 1,
+///         policy: RatePolicy::Block,
+///         ..RateLimit::per_second(100)
+///     })
+///     .build();
+///
+/// let started = Instant::now();
+/// for _ in 0..6 {
+///     pool.execute(|| {}).unwrap();
+/// }
+/// pool.join();
+/// assert!(started.elapsed() >= Duration::from_millis(40));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    /// The number of jobs started per period.
+    pub jobs: u32,
+    /// The period over which jobs are counted.
+    pub per: Duration,
+    /// The number of jobs that can start at once after a quiet period.
+    pub burst: u32,
+    /// Where jobs wait once the limit is reached.
+    pub policy: RatePolicy,
+}
+
+impl RateLimit {
+    /// Constructs the settings of a limiter starting at most jobs per
+    /// second, with a burst of the same size, that queues the jobs over
+    /// the limit.
+    ///
+    /// **jobs**: u32 - The number of jobs started per second, at least one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if jobs is zero.
+    pub fn per_second(jobs: u32) -> RateLimit {
+        assert!(jobs > 0, "the rate limit must allow some jobs");
+        RateLimit {
+            jobs,
+            per: Duration::from_secs(1),
+            burst: jobs,
+            policy: RatePolicy::Queue,
+        }
+    }
+}
+
+// The tokens left and the last time they were refilled.
+struct Bucket {
+    tokens: f64,
+    refilled: Instant,
+}
+
+// A token bucket shared by the submitters or the workers of a pool.
+pub(crate) struct RateLimiter {
+    bucket: Mutex<Bucket>,
+    // tokens added per second.
+    rate: f64,
+    burst: f64,
+    pub(crate) policy: RatePolicy,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limit: RateLimit) -> RateLimiter {
+        let burst = f64::from(limit.burst.max(1));
+        RateLimiter {
+            bucket: Mutex::new(Bucket {
+                tokens: burst,
+                refilled: Instant::now(),
+            }),
+            rate: f64::from(limit.jobs.max(1)) / limit.per.as_secs_f64().max(f64::EPSILON),
+            burst,
+            policy: limit.policy,
+        }
+    }
+
+    // Takes a token if one is left, or returns how long to wait for one.
+    fn take(&self) -> Result<(), Duration> {
+        let mut bucket = self.bucket.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.refilled).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+        bucket.refilled = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate))
+        }
+    }
+
+    // Blocks until a token is taken.
+    pub(crate) fn acquire(&self) {
+        while let Err(wait) = self.take() {
+            thread::sleep(wait);
+        }
+    }
+
+    // Blocks until a token is taken or the deadline is reached.
+    //
+    // **returns**: true if a token was taken.
+    pub(crate) fn acquire_until(&self, deadline: Instant) -> bool {
+        while let Err(wait) = self.take() {
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            thread::sleep(wait.min(deadline - now));
+        }
+        true
+    }
+
+    // Takes a token without blocking.
+    pub(crate) fn try_acquire(&self) -> bool {
+        self.take().is_ok()
+    }
+}
+
+// This sections are the beginning of rate module unit tests.
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    #[test]
+    fn limiter_should_allow_a_burst_and_then_throttle() {
+        let limiter = RateLimiter::new(RateLimit {
+            burst: 2,
+            ..RateLimit::per_second(10)
+        });
+        assert!(limiter.try_acquire());
+        assert!(limiter.try_acquire());
+        assert!(!limiter.try_acquire());
+
+        let started = Instant::now();
+        limiter.acquire();
+        assert!(started.elapsed() >= Duration::from_millis(90));
+    }
+}
@@ -0,0 +1,159 @@
+//! ## Results
+//!
+//! This module contains [`Results`], the receiving end of the result
+//! channels built into a pool. Each pool has one channel per result type:
+//! [`WorkerPool::execute_returning`](crate::pool::WorkerPool::execute_returning)
+//! sends the value of a job to the channel of its type, and
+//! [`WorkerPool::results`](crate::pool::WorkerPool::results) returns its
+//! receiver. Receivers can be cloned, and each value goes to one of them.
+
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    sync::{mpsc, Arc, Mutex, PoisonError},
+    time::Duration,
+};
+
+/// A cloneable receiver of the values returned by the jobs executed with
+/// [`WorkerPool::execute_returning`](crate::pool::WorkerPool::execute_returning).
+/// The clones share the same channel, so every value is received once, by
+/// one of them. The channel is disconnected once every clone of the pool
+/// is dropped and its jobs returned.
+///
+/// ## Examples
+///
+/// ```
+/// use workerpool_rs::pool::WorkerPool;
+///
+/// let pool = WorkerPool::new(4);
+/// for i in 0..8 {
+///     pool.execute_returning(move || i * 2).unwrap();
+/// }
+///
+/// let results = pool.results::<i32>();
+/// assert_eq!(56, results.iter().take(8).sum::<i32>());
+/// ```
+pub struct Results<T> {
+    receiver: Arc<Mutex<mpsc::Receiver<T>>>,
+}
+
+impl<T> Results<T> {
+    /// Blocks until a value is received.
+    ///
+    /// **returns**: `Err(RecvError)` once the channel is disconnected.
+    pub fn recv(&self) -> Result<T, mpsc::RecvError> {
+        self.lock().recv()
+    }
+
+    /// Receives a value if one is waiting, without blocking.
+    ///
+    /// **returns**: `Err(TryRecvError)` if no value is waiting.
+    pub fn try_recv(&self) -> Result<T, mpsc::TryRecvError> {
+        self.lock().try_recv()
+    }
+
+    /// Blocks until a value is received, or the timeout expires.
+    ///
+    /// **timeout**: Duration - The maximum time to wait. \
+    /// **returns**: `Err(RecvTimeoutError)` if no value was received.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, mpsc::RecvTimeoutError> {
+        self.lock().recv_timeout(timeout)
+    }
+
+    /// Returns an iterator blocking for every next value, which ends once
+    /// the channel is disconnected.
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        std::iter::from_fn(move || self.recv().ok())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, mpsc::Receiver<T>> {
+        self.receiver.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T> Clone for Results<T> {
+    fn clone(&self) -> Results<T> {
+        Results {
+            receiver: Arc::clone(&self.receiver),
+        }
+    }
+}
+
+// Both ends of the channel of a result type.
+type Channel<T> = (mpsc::Sender<T>, Results<T>);
+
+// The result channels of a pool, created on first use, by result type.
+pub(crate) struct ResultChannels {
+    channels: Mutex<HashMap<TypeId, Box<dyn Any + Send>>>,
+}
+
+impl ResultChannels {
+    pub(crate) fn new() -> ResultChannels {
+        ResultChannels {
+            channels: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Returns a sender to the channel of T.
+    pub(crate) fn sender<T: Send + 'static>(&self) -> mpsc::Sender<T> {
+        self.with(|(sender, _)| sender.clone())
+    }
+
+    // Returns a receiver of the channel of T.
+    pub(crate) fn receiver<T: Send + 'static>(&self) -> Results<T> {
+        self.with(|(_, receiver)| receiver.clone())
+    }
+
+    // Calls f with the channel of T, creating it if needed.
+    fn with<T: Send + 'static, R>(&self, f: impl FnOnce(&Channel<T>) -> R) -> R {
+        let mut channels = self.channels.lock().unwrap_or_else(PoisonError::into_inner);
+        let channel = channels.entry(TypeId::of::<T>()).or_insert_with(|| {
+            let (sender, receiver) = mpsc::channel::<T>();
+            let receiver = Results {
+                receiver: Arc::new(Mutex::new(receiver)),
+            };
+            Box::new((sender, receiver))
+        });
+        f(channel
+            .downcast_ref::<Channel<T>>()
+            .expect("result channel of another type"))
+    }
+}
+
+// This sections are the beginning of results module unit tests.
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn channels_should_be_separate_by_type() {
+        let channels = ResultChannels::new();
+        channels.sender::<u8>().send(1).unwrap();
+        channels.sender::<&str>().send("one").unwrap();
+        assert_eq!(Ok("one"), channels.receiver::<&str>().try_recv());
+        assert_eq!(Ok(1), channels.receiver::<u8>().try_recv());
+        assert!(channels.receiver::<u8>().try_recv().is_err());
+    }
+
+    #[test]
+    fn results_should_deliver_each_value_once_across_clones() {
+        let channels = ResultChannels::new();
+        let results = channels.receiver::<usize>();
+        let sender = channels.sender::<usize>();
+        (0..100).for_each(|i| sender.send(i).unwrap());
+        drop((sender, channels));
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let results = results.clone();
+                thread::spawn(move || results.iter().collect::<Vec<_>>())
+            })
+            .collect();
+        let mut received: Vec<_> = handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap())
+            .collect();
+        received.sort();
+        assert_eq!((0..100).collect::<Vec<_>>(), received);
+    }
+}
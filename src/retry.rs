@@ -0,0 +1,145 @@
+//! ## Retry
+//!
+//! This module contains the policy of
+//! [`WorkerPool::submit_retry`](crate::pool::WorkerPool::submit_retry).
+//! A failed attempt is rescheduled through the pool timer after a backoff
+//! delay, so no worker sleeps between attempts.
+
+use std::{
+    collections::hash_map::RandomState,
+    fmt::Display,
+    hash::{BuildHasher, Hasher},
+    time::Duration,
+};
+
+/// Defines how long to wait before retrying a failed attempt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backoff {
+    /// Every retry waits the same delay.
+    Fixed(Duration),
+    /// The first retry waits `initial`, and each later one waits twice the
+    /// previous delay, up to `max`.
+    Exponential { initial: Duration, max: Duration },
+}
+
+/// The settings of a retried job. The fields not given to
+/// [`new`](RetryPolicy::new) can be overridden with the struct update
+/// syntax.
+///
+/// # Examples
+///
+/// ```
+/// use workerpool_rs::retry::{Backoff, RetryPolicy};
+/// use std::time::Duration;
+///
+/// let policy = RetryPolicy {
+///     backoff: Backoff::Exponential {
+///         initial: Duration::from_millis(10),
+///         max: Duration::from_secs(1),
+///     },
+///     jitter: true,
+///     ..RetryPolicy::new(5)
+/// };
+/// assert_eq!(5, policy.max_attempts);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The number of attempts, the first one included.
+    pub max_attempts: u32,
+    /// The delay before each retry.
+    pub backoff: Backoff,
+    /// Whether each delay is shortened by a random amount, up to half of
+    /// it, so jobs failing together don't retry together.
+    pub jitter: bool,
+}
+
+impl RetryPolicy {
+    /// Constructs a policy making up to max_attempts attempts, 100
+    /// milliseconds apart, without jitter.
+    ///
+    /// **max_attempts**: u32 - The number of attempts, at least one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if max_attempts is zero.
+    pub fn new(max_attempts: u32) -> RetryPolicy {
+        assert!(max_attempts > 0, "a retried job needs at least one attempt");
+        RetryPolicy {
+            max_attempts,
+            backoff: Backoff::Fixed(Duration::from_millis(100)),
+            jitter: false,
+        }
+    }
+
+    // Returns the delay before the retry following the given failed
+    // attempt, counted from one.
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        let delay = match self.backoff {
+            Backoff::Fixed(delay) => delay,
+            Backoff::Exponential { initial, max } => {
+                let factor = 1u32
+                    .checked_shl(attempt.saturating_sub(1))
+                    .unwrap_or(u32::MAX);
+                initial.saturating_mul(factor).min(max)
+            }
+        };
+        if !self.jitter {
+            return delay;
+        }
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u32(attempt);
+        let cut = hasher.finish() % (delay.as_nanos() as u64 / 2 + 1);
+        delay - Duration::from_nanos(cut)
+    }
+}
+
+/// The error delivered by a retried job whose every attempt failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exhausted<E> {
+    /// The number of attempts made.
+    pub attempts: u32,
+    /// The error of the last attempt.
+    pub error: E,
+}
+
+impl<E: Display> Display for Exhausted<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the job failed {} times: {}", self.attempts, self.error)
+    }
+}
+
+impl<E: std::fmt::Debug + Display> std::error::Error for Exhausted<E> {}
+
+// This sections are the beginning of retry module unit tests.
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    #[test]
+    fn exponential_backoff_should_double_up_to_max() {
+        let policy = RetryPolicy {
+            backoff: Backoff::Exponential {
+                initial: Duration::from_millis(10),
+                max: Duration::from_millis(50),
+            },
+            ..RetryPolicy::new(10)
+        };
+        let delays: Vec<_> = (1..6).map(|attempt| policy.delay(attempt)).collect();
+        let millis: Vec<_> = delays.iter().map(Duration::as_millis).collect();
+        assert_eq!(vec![10, 20, 40, 50, 50], millis);
+        assert_eq!(Duration::from_millis(50), policy.delay(u32::MAX));
+    }
+
+    #[test]
+    fn jitter_should_cut_at_most_half_of_the_delay() {
+        let policy = RetryPolicy {
+            backoff: Backoff::Fixed(Duration::from_millis(10)),
+            jitter: true,
+            ..RetryPolicy::new(3)
+        };
+        for attempt in 1..20 {
+            let delay = policy.delay(attempt);
+            assert!(delay >= Duration::from_millis(5) && delay <= Duration::from_millis(10));
+        }
+    }
+}
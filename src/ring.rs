@@ -0,0 +1,204 @@
+//! ## Ring
+//!
+//! This module contains a bounded lock-free MPMC ring buffer, after
+//! Dmitry Vyukov's design. Every slot carries a sequence number that
+//! tells producers and consumers whether it is free or filled, so both
+//! ends only contend on a single atomic position.
+
+use std::{
+    cell::UnsafeCell,
+    mem::MaybeUninit,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+};
+
+// seq: AtomicUsize - equal to the position when free and to position + 1
+// when filled.\
+// stamp: AtomicU64 - a value stored with the item that can be read
+// without taking it.\
+// value: UnsafeCell<MaybeUninit<T>> - the item, initialized when filled.
+struct Slot<T> {
+    seq: AtomicUsize,
+    stamp: AtomicU64,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+// slots: Box<[Slot<T>]> - the buffer, whose length is a power of two.\
+// mask: usize - the length minus one, to map positions to slots.\
+// head: AtomicUsize - the position of the next item to pop.\
+// tail: AtomicUsize - the position of the next item to push.
+pub(crate) struct Ring<T> {
+    slots: Box<[Slot<T>]>,
+    mask: usize,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+}
+
+// SAFETY: an item is written by the single producer that claimed its slot
+// and read by the single consumer that claimed it afterwards, with the
+// slot sequence publishing the write, so items only move between threads.
+unsafe impl<T: Send> Send for Ring<T> {}
+unsafe impl<T: Send> Sync for Ring<T> {}
+
+impl<T> Ring<T> {
+    // Constructs a ring holding at least capacity items.
+    pub(crate) fn new(capacity: usize) -> Ring<T> {
+        let capacity = capacity.max(2).next_power_of_two();
+        let slots = (0..capacity)
+            .map(|i| Slot {
+                seq: AtomicUsize::new(i),
+                stamp: AtomicU64::new(0),
+                value: UnsafeCell::new(MaybeUninit::uninit()),
+            })
+            .collect();
+        Ring {
+            slots,
+            mask: capacity - 1,
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    // Pushes an item with its stamp, giving it back if the ring is full.
+    pub(crate) fn push(&self, stamp: u64, value: T) -> Result<(), T> {
+        let mut pos = self.tail.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[pos & self.mask];
+            let seq = slot.seq.load(Ordering::Acquire);
+            let diff = seq.wrapping_sub(pos) as isize;
+            if diff == 0 {
+                let claimed = self.tail.compare_exchange_weak(
+                    pos,
+                    pos.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                );
+                match claimed {
+                    Ok(_) => {
+                        // SAFETY: the slot is free and this producer is the
+                        // only one that claimed it.
+                        unsafe { (*slot.value.get()).write(value) };
+                        slot.stamp.store(stamp, Ordering::Relaxed);
+                        slot.seq.store(pos.wrapping_add(1), Ordering::Release);
+                        return Ok(());
+                    }
+                    Err(current) => pos = current,
+                }
+            } else if diff < 0 {
+                return Err(value);
+            } else {
+                pos = self.tail.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    // Pops the oldest item with its stamp, or returns None if the ring is
+    // empty.
+    pub(crate) fn pop(&self) -> Option<(u64, T)> {
+        let mut pos = self.head.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[pos & self.mask];
+            let seq = slot.seq.load(Ordering::Acquire);
+            let diff = seq.wrapping_sub(pos.wrapping_add(1)) as isize;
+            if diff == 0 {
+                let claimed = self.head.compare_exchange_weak(
+                    pos,
+                    pos.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                );
+                match claimed {
+                    Ok(_) => {
+                        // SAFETY: the slot is filled and this consumer is the
+                        // only one that claimed it.
+                        let value = unsafe { (*slot.value.get()).assume_init_read() };
+                        let stamp = slot.stamp.load(Ordering::Relaxed);
+                        let next = pos.wrapping_add(self.mask).wrapping_add(1);
+                        slot.seq.store(next, Ordering::Release);
+                        return Some((stamp, value));
+                    }
+                    Err(current) => pos = current,
+                }
+            } else if diff < 0 {
+                return None;
+            } else {
+                pos = self.head.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    // Returns whether the ring is empty. Items may be pushed or taken
+    // concurrently, so it is only a hint.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.front_stamp().is_none()
+    }
+
+    // Returns the stamp of the oldest item without popping it. The item may
+    // be taken concurrently, so the stamp is only a hint.
+    pub(crate) fn front_stamp(&self) -> Option<u64> {
+        let pos = self.head.load(Ordering::Acquire);
+        let slot = &self.slots[pos & self.mask];
+        if slot.seq.load(Ordering::Acquire) == pos.wrapping_add(1) {
+            Some(slot.stamp.load(Ordering::Relaxed))
+        } else {
+            None
+        }
+    }
+}
+
+impl<T> Drop for Ring<T> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+// This sections are the beginning of ring module unit tests.
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use std::{sync::Arc, thread};
+
+    #[test]
+    fn ring_should_pop_in_fifo_order_until_empty() {
+        let ring = Ring::new(4);
+        for i in 0..4 {
+            ring.push(i, i).unwrap();
+        }
+        assert_eq!(Err(4), ring.push(4, 4));
+        assert_eq!(Some(0), ring.front_stamp());
+        assert_eq!(
+            vec![(0, 0), (1, 1), (2, 2), (3, 3)],
+            (0..4).filter_map(|_| ring.pop()).collect::<Vec<_>>()
+        );
+        assert_eq!(None, ring.pop());
+        assert_eq!(None, ring.front_stamp());
+    }
+
+    #[test]
+    fn ring_should_deliver_every_item_across_threads() {
+        let ring = Arc::new(Ring::new(8));
+        let producers: Vec<_> = (0..4u64)
+            .map(|p| {
+                let ring = Arc::clone(&ring);
+                thread::spawn(move || {
+                    for i in 0..1000 {
+                        let mut item = p * 1000 + i;
+                        while let Err(back) = ring.push(0, item) {
+                            item = back;
+                            thread::yield_now();
+                        }
+                    }
+                })
+            })
+            .collect();
+        let mut seen = Vec::new();
+        while seen.len() < 4000 {
+            match ring.pop() {
+                Some((_, item)) => seen.push(item),
+                None => thread::yield_now(),
+            }
+        }
+        producers.into_iter().for_each(|p| p.join().unwrap());
+        seen.sort_unstable();
+        assert_eq!((0..4000).collect::<Vec<_>>(), seen);
+    }
+}
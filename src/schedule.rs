@@ -0,0 +1,355 @@
+//! ## Schedule
+//!
+//! This module contains the timer used by
+//! [`WorkerPool::execute_after`](crate::pool::WorkerPool::execute_after),
+//! [`WorkerPool::execute_at`](crate::pool::WorkerPool::execute_at) and
+//! [`WorkerPool::execute_every`](crate::pool::WorkerPool::execute_every).
+//! Delayed jobs wait in a min-heap watched by a dedicated timer thread,
+//! which submits them to the pool when they are due.
+
+use crate::pool::Job;
+use std::{
+    cmp::Ordering as CmpOrdering,
+    collections::BinaryHeap,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc, Condvar, Mutex, PoisonError,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Defines when a recurring job runs again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepeatMode {
+    /// Runs are queued every period, measured from the first run, whether
+    /// or not the previous run finished.
+    FixedRate,
+    /// Each run is queued one period after the previous run finished.
+    FixedDelay,
+}
+
+// A recurring job shared by all its runs.
+type RepeatedJob = Arc<dyn Fn() + Send + Sync + 'static>;
+
+// What a timer entry submits when it is due.
+enum Task {
+    Once(Job),
+    Every {
+        job: RepeatedJob,
+        period: Duration,
+        mode: RepeatMode,
+    },
+}
+
+// The states of a scheduled job.
+const SCHEDULED: u8 = 0;
+const CANCELLED: u8 = 1;
+const FIRED: u8 = 2;
+
+/// A handle to a delayed or recurring job, used to cancel it. Cancelling a
+/// recurring job stops its later runs.
+#[derive(Clone)]
+pub struct ScheduledHandle {
+    state: Arc<AtomicU8>,
+}
+
+impl ScheduledHandle {
+    /// Cancels the job, unless a one-off job was already submitted to the
+    /// pool.
+    ///
+    /// **returns**: true if the job was cancelled by this call.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use workerpool_rs::pool::WorkerPool;
+    /// use std::time::Duration;
+    ///
+    /// let pool = WorkerPool::new(1);
+    /// let handle = pool.execute_after(Duration::from_secs(60), || {
+    ///     unreachable!("the job was cancelled");
+    /// });
+    ///
+    /// assert!(handle.cancel());
+    /// assert!(handle.is_cancelled());
+    /// assert!(!handle.cancel());
+    /// ```
+    pub fn cancel(&self) -> bool {
+        self.state
+            .compare_exchange(SCHEDULED, CANCELLED, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+    }
+
+    /// Checks whether the job was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.state.load(Ordering::SeqCst) == CANCELLED
+    }
+}
+
+// A task waiting in the heap. Entries are ordered by due time and then by
+// insertion order, reversed, so the heap pops the earliest one first.
+struct Entry {
+    at: Instant,
+    seq: u64,
+    task: Task,
+    state: Arc<AtomicU8>,
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Entry) -> bool {
+        self.at == other.at && self.seq == other.seq
+    }
+}
+
+impl Eq for Entry {}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Entry) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Entry {
+    fn cmp(&self, other: &Entry) -> CmpOrdering {
+        (other.at, other.seq).cmp(&(self.at, self.seq))
+    }
+}
+
+struct TimerState {
+    entries: BinaryHeap<Entry>,
+    next_seq: u64,
+    stopped: bool,
+}
+
+// state: Mutex<TimerState> - the delayed jobs and the stopped flag.\
+// wakeup: Condvar - notified when a job is scheduled or the timer stops.
+struct Timer {
+    state: Mutex<TimerState>,
+    wakeup: Condvar,
+}
+
+impl Timer {
+    fn insert(&self, at: Instant, task: Task, state: Arc<AtomicU8>) {
+        let mut timer = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let seq = timer.next_seq;
+        timer.next_seq += 1;
+        timer.entries.push(Entry {
+            at,
+            seq,
+            task,
+            state,
+        });
+        self.wakeup.notify_one();
+    }
+}
+
+// Owns the timer thread, which hands due jobs to the submit callback.
+pub(crate) struct Scheduler {
+    timer: Arc<Timer>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Scheduler {
+    pub(crate) fn new<S>(submit: S) -> Scheduler
+    where
+        S: Fn(Job) + Send + 'static,
+    {
+        let timer = Arc::new(Timer {
+            state: Mutex::new(TimerState {
+                entries: BinaryHeap::new(),
+                next_seq: 0,
+                stopped: false,
+            }),
+            wakeup: Condvar::new(),
+        });
+
+        let thread_timer = Arc::clone(&timer);
+        let thread = thread::Builder::new()
+            .name(String::from("workerpool-timer"))
+            .spawn(move || run(thread_timer, submit))
+            .expect("Cant spawn timer");
+
+        Scheduler {
+            timer,
+            thread: Some(thread),
+        }
+    }
+
+    // Schedules a job to be submitted at the given instant.
+    pub(crate) fn schedule(&self, at: Instant, job: Job) -> ScheduledHandle {
+        let state = Arc::new(AtomicU8::new(SCHEDULED));
+        self.timer.insert(at, Task::Once(job), Arc::clone(&state));
+        ScheduledHandle { state }
+    }
+
+    // Schedules a job to be submitted every period, starting at the given
+    // instant.
+    pub(crate) fn schedule_every(
+        &self,
+        at: Instant,
+        period: Duration,
+        mode: RepeatMode,
+        job: RepeatedJob,
+    ) -> ScheduledHandle {
+        let state = Arc::new(AtomicU8::new(SCHEDULED));
+        let task = Task::Every { job, period, mode };
+        self.timer.insert(at, task, Arc::clone(&state));
+        ScheduledHandle { state }
+    }
+
+    // Stops the timer thread. Jobs that are not due yet are dropped.
+    pub(crate) fn stop(&mut self) {
+        self.timer
+            .state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .stopped = true;
+        self.timer.wakeup.notify_one();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+// Waits for the earliest job to be due and submits it, until stopped.
+fn run<S: Fn(Job)>(timer: Arc<Timer>, submit: S) {
+    loop {
+        let mut state = timer.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let entry = loop {
+            if state.stopped {
+                return;
+            }
+            let now = Instant::now();
+            match state.entries.peek() {
+                Some(entry) if entry.at <= now => break state.entries.pop(),
+                Some(entry) => {
+                    let timeout = entry.at - now;
+                    state = timer
+                        .wakeup
+                        .wait_timeout(state, timeout)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0;
+                }
+                None => {
+                    state = timer
+                        .wakeup
+                        .wait(state)
+                        .unwrap_or_else(PoisonError::into_inner)
+                }
+            }
+        };
+        drop(state);
+
+        if let Some(entry) = entry {
+            fire(&timer, entry, &submit);
+        }
+    }
+}
+
+// Submits a due entry, unless it was cancelled, and reschedules it if it
+// is recurring.
+fn fire<S: Fn(Job)>(timer: &Arc<Timer>, entry: Entry, submit: &S) {
+    let Entry {
+        at, task, state, ..
+    } = entry;
+    match task {
+        Task::Once(job) => {
+            let fired =
+                state.compare_exchange(SCHEDULED, FIRED, Ordering::SeqCst, Ordering::SeqCst);
+            if fired.is_ok() {
+                submit(job);
+            }
+        }
+        Task::Every { job, period, mode } => {
+            if state.load(Ordering::SeqCst) != SCHEDULED {
+                return;
+            }
+            match mode {
+                RepeatMode::FixedRate => {
+                    let task = Task::Every {
+                        job: Arc::clone(&job),
+                        period,
+                        mode,
+                    };
+                    timer.insert(at + period, task, state);
+                    submit(Box::new(move || job()));
+                }
+                RepeatMode::FixedDelay => {
+                    let timer = Arc::clone(timer);
+                    submit(Box::new(move || {
+                        job();
+                        let task = Task::Every { job, period, mode };
+                        timer.insert(Instant::now() + period, task, state);
+                    }));
+                }
+            }
+        }
+    }
+}
+
+// This sections are the beginning of schedule module unit tests.
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use std::{sync::mpsc, time::Duration};
+
+    #[test]
+    fn scheduler_should_submit_jobs_in_due_order() {
+        let (tx, rx) = mpsc::channel::<Job>();
+        let tx = Mutex::new(tx);
+        let scheduler = Scheduler::new(move |job| tx.lock().unwrap().send(job).unwrap());
+        let (order_tx, order_rx) = mpsc::channel();
+        let now = Instant::now();
+        for (id, delay) in [(0, 30), (1, 10), (2, 20)] {
+            let order_tx = order_tx.clone();
+            let job: Job = Box::new(move || order_tx.send(id).unwrap());
+            scheduler.schedule(now + Duration::from_millis(delay), job);
+        }
+        for job in rx.iter().take(3) {
+            job();
+        }
+        assert_eq!(vec![1, 2, 0], order_rx.try_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn scheduler_should_not_submit_cancelled_jobs() {
+        let (tx, rx) = mpsc::channel::<Job>();
+        let tx = Mutex::new(tx);
+        let mut scheduler = Scheduler::new(move |job| tx.lock().unwrap().send(job).unwrap());
+        let handle =
+            scheduler.schedule(Instant::now() + Duration::from_millis(10), Box::new(|| {}));
+        assert!(handle.cancel());
+        thread::sleep(Duration::from_millis(30));
+        scheduler.stop();
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn scheduler_should_repeat_until_cancelled() {
+        let (tx, rx) = mpsc::channel::<Job>();
+        let tx = Mutex::new(tx);
+        let scheduler = Scheduler::new(move |job| tx.lock().unwrap().send(job).unwrap());
+        let period = Duration::from_millis(1);
+        let handle = scheduler.schedule_every(
+            Instant::now(),
+            period,
+            RepeatMode::FixedDelay,
+            Arc::new(|| {}),
+        );
+        for job in rx.iter().take(3) {
+            job();
+        }
+        handle.cancel();
+        while let Ok(job) = rx.recv_timeout(Duration::from_millis(20)) {
+            job();
+        }
+        assert!(rx.recv_timeout(Duration::from_millis(20)).is_err());
+    }
+}
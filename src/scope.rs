@@ -0,0 +1,103 @@
+//! ## Scope
+//!
+//! This module contains the scope created by
+//! [`WorkerPool::scope`](crate::pool::WorkerPool::scope). Jobs executed in
+//! a scope may borrow non-`'static` data, as the scope waits for all of
+//! them before returning.
+
+use crate::pool::{ExecuteError, Job, PendingJobs, WorkerPool};
+use std::{
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+/// A scope to execute jobs that borrow data living longer than `'env`.
+pub struct Scope<'scope, 'env: 'scope> {
+    pool: &'scope WorkerPool,
+    state: Arc<ScopeState>,
+    scope: PhantomData<&'scope mut &'scope ()>,
+    env: PhantomData<&'env mut &'env ()>,
+}
+
+// pending: PendingJobs - the scoped jobs that are not finished yet.\
+// panicked: AtomicBool - set when a scoped job panics.
+struct ScopeState {
+    pending: PendingJobs,
+    panicked: AtomicBool,
+}
+
+impl<'scope, 'env> Scope<'scope, 'env> {
+    pub(crate) fn new(pool: &'scope WorkerPool) -> Scope<'scope, 'env> {
+        Scope {
+            pool,
+            state: Arc::new(ScopeState {
+                pending: PendingJobs::new(),
+                panicked: AtomicBool::new(false),
+            }),
+            scope: PhantomData,
+            env: PhantomData,
+        }
+    }
+
+    /// Executes a job that may borrow data from the scope environment.
+    ///
+    /// **f**: A FnOnce closure. \
+    /// **returns**: `Err(ExecuteError)` if the job can't be queued.
+    pub fn execute<F>(&'scope self, f: F) -> Result<(), ExecuteError>
+    where
+        F: FnOnce() + Send + 'scope,
+    {
+        self.state.pending.increment();
+        let job = ScopedJob {
+            f,
+            _guard: ScopeGuard(Arc::clone(&self.state)),
+        };
+        let job: Box<dyn FnOnce() + Send + 'scope> = Box::new(move || {
+            let ScopedJob { f, _guard } = job;
+            f();
+        });
+
+        // SAFETY: WorkerPool::scope doesn't return before every guard is
+        // dropped, and a guard is only dropped after its job ran or was
+        // dropped, so the borrowed data outlives the job.
+        let job: Job = unsafe { std::mem::transmute(job) };
+        self.pool.execute_boxed(job)
+    }
+
+    // Blocks until every scoped job is finished, running queued jobs
+    // meanwhile on a worker of the pool. Returns true if any of them
+    // panicked.
+    pub(crate) fn wait(&self) -> bool {
+        let pending = &self.state.pending;
+        if !self
+            .pool
+            .help_until(|timeout| pending.wait_zero_timeout(timeout))
+        {
+            pending.wait_zero();
+        }
+        self.state.panicked.load(Ordering::SeqCst)
+    }
+}
+
+// A scoped job with its guard. Fields are dropped in declaration order, so
+// the closure and its borrows are gone before the guard is released.
+struct ScopedJob<F> {
+    f: F,
+    _guard: ScopeGuard,
+}
+
+// Marks a scoped job as finished when dropped.
+struct ScopeGuard(Arc<ScopeState>);
+
+impl Drop for ScopeGuard {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.0.panicked.store(true, Ordering::SeqCst);
+        }
+        self.0.pending.decrement();
+    }
+}
@@ -0,0 +1,190 @@
+//! ## Scoped
+//!
+//! This module contains [`ScopedWorkerPool`], a pool whose workers are
+//! threads of a `std::thread::scope`. Its jobs may borrow anything that
+//! outlives the scope, and as the scope joins its threads before
+//! returning, every job is finished by then, without any unsafe code.
+//! Unlike [`WorkerPool::scope`](crate::pool::WorkerPool::scope), the pool
+//! can be handed to the threads of the scope, and outlive a single call.
+//!
+//! ## Examples
+//!
+//! ```
+//! use workerpool_rs::scoped::ScopedWorkerPool;
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//! use std::thread;
+//!
+//! let words = vec!["scoped", "jobs", "borrow"];
+//! let letters = AtomicUsize::new(0);
+//!
+//! thread::scope(|scope| {
+//!     let pool = ScopedWorkerPool::new(scope, 2);
+//!     for word in &words {
+//!         let letters = &letters;
+//!         pool.execute(move || {
+//!             letters.fetch_add(word.len(), Ordering::SeqCst);
+//!         })
+//!         .unwrap();
+//!     }
+//! });
+//!
+//! assert_eq!(16, letters.load(Ordering::SeqCst));
+//! ```
+
+use crate::{error::Error, pool::ExecuteError};
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc, Mutex, PoisonError,
+    },
+    thread::{self, Scope, ScopedJoinHandle},
+};
+
+// A job that may borrow data living for 'scope.
+type ScopedJob<'scope> = Box<dyn FnOnce() + Send + 'scope>;
+
+/// A pool of scoped threads running jobs that borrow data living for
+/// `'scope`. It can only be built from a `std::thread::Scope`, which joins
+/// the workers before it ends, so every job is finished before the
+/// borrowed data goes away. Dropping the pool lets the workers finish the
+/// queued jobs and exit.
+pub struct ScopedWorkerPool<'scope> {
+    sender: Option<mpsc::Sender<ScopedJob<'scope>>>,
+    workers: Vec<ScopedJoinHandle<'scope, ()>>,
+    panics: Arc<AtomicUsize>,
+}
+
+impl<'scope> ScopedWorkerPool<'scope> {
+    /// Constructs a new ScopedWorkerPool, spawning its workers in the
+    /// given scope.
+    ///
+    /// **scope**: &Scope - The scope the workers belong to. \
+    /// **size**: usize - The number of workers. \
+    /// **returns**: a ScopedWorkerPool object.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a worker thread can't be spawned. See
+    /// [`try_new`](ScopedWorkerPool::try_new).
+    pub fn new<'env>(scope: &'scope Scope<'scope, 'env>, size: usize) -> ScopedWorkerPool<'scope> {
+        ScopedWorkerPool::try_new(scope, size).expect("Cant spawn worker")
+    }
+
+    /// Constructs a new ScopedWorkerPool as [`new`](ScopedWorkerPool::new),
+    /// but returns an error when a worker thread can't be spawned. The
+    /// workers spawned before the failure exit right away.
+    ///
+    /// **scope**: &Scope - The scope the workers belong to. \
+    /// **size**: usize - The number of workers. \
+    /// **returns**: `Err(Error::Spawn)` if a worker thread couldn't be spawned.
+    pub fn try_new<'env>(
+        scope: &'scope Scope<'scope, 'env>,
+        size: usize,
+    ) -> Result<ScopedWorkerPool<'scope>, Error> {
+        let (sender, receiver) = mpsc::channel::<ScopedJob<'scope>>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let panics = Arc::new(AtomicUsize::new(0));
+        let workers = (0..size)
+            .map(|id| {
+                let receiver = Arc::clone(&receiver);
+                let panics = Arc::clone(&panics);
+                thread::Builder::new()
+                    .name(format!("workerpool-scoped-{}", id))
+                    .spawn_scoped(scope, move || loop {
+                        let job = receiver
+                            .lock()
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .recv();
+                        let Ok(job) = job else {
+                            break;
+                        };
+                        if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                            panics.fetch_add(1, Ordering::SeqCst);
+                        }
+                    })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(ScopedWorkerPool {
+            sender: Some(sender),
+            workers,
+            panics,
+        })
+    }
+
+    /// Executes a job that may borrow data living for `'scope`.
+    ///
+    /// **f**: A FnOnce closure. \
+    /// **returns**: `Err(ExecuteError)` if the pool has no worker.
+    pub fn execute<F>(&self, f: F) -> Result<(), ExecuteError>
+    where
+        F: FnOnce() + Send + 'scope,
+    {
+        if self.workers.is_empty() {
+            return Err(ExecuteError::Disconnected);
+        }
+        self.sender
+            .as_ref()
+            .expect("the sender is only taken on drop")
+            .send(Box::new(f))
+            .map_err(|_| ExecuteError::Disconnected)
+    }
+
+    /// Returns the number of workers of the pool.
+    pub fn num_workers(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Returns the number of jobs that panicked so far. A panicking job
+    /// doesn't stop its worker.
+    pub fn panic_count(&self) -> usize {
+        self.panics.load(Ordering::SeqCst)
+    }
+
+    /// Blocks until every queued job is finished and the workers exited,
+    /// before the end of the scope.
+    ///
+    /// **returns**: the number of jobs that panicked.
+    pub fn join(mut self) -> usize {
+        drop(self.sender.take());
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+        self.panic_count()
+    }
+}
+
+impl Drop for ScopedWorkerPool<'_> {
+    fn drop(&mut self) {
+        drop(self.sender.take());
+    }
+}
+
+// This sections are the beginning of scoped module unit tests.
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    #[test]
+    fn scoped_pool_should_run_jobs_borrowing_the_environment() {
+        let mut totals = vec![0; 8];
+        thread::scope(|scope| {
+            let pool = ScopedWorkerPool::new(scope, 3);
+            for (i, total) in totals.iter_mut().enumerate() {
+                pool.execute(move || *total = i * 10).unwrap();
+            }
+            pool.execute(|| panic!("scoped job panicked")).unwrap();
+            assert_eq!(1, pool.join());
+        });
+        assert_eq!((0..8).map(|i| i * 10).collect::<Vec<_>>(), totals);
+    }
+
+    #[test]
+    fn scoped_pool_without_workers_should_reject_jobs() {
+        thread::scope(|scope| {
+            let pool = ScopedWorkerPool::try_new(scope, 0).unwrap();
+            assert_eq!(Err(ExecuteError::Disconnected), pool.execute(|| {}));
+        });
+    }
+}
@@ -0,0 +1,155 @@
+//! ## State
+//!
+//! This module contains [`StatefulPool`], a pool whose workers each own a
+//! state built once by an init closure, such as a database connection or
+//! a buffer. Jobs borrow the state of the worker running them, so the
+//! resource is reused instead of being recreated per job.
+
+use crate::pool::{ExecuteError, WorkerPool};
+use std::{
+    any::Any,
+    cell::RefCell,
+    collections::HashMap,
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+// The source of the ids that tell apart the states of different pools
+// living on the same worker thread.
+static NEXT_POOL_ID: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    // The states of the stateful pools this thread worked for, by pool id.
+    static STATES: RefCell<HashMap<usize, Box<dyn Any>>> = RefCell::new(HashMap::new());
+}
+
+/// A pool whose workers each own a state of type `S`. The state of a
+/// worker is built by the init closure before its first stateful job and
+/// dropped when the worker exits.
+///
+/// # Examples
+///
+/// ```
+/// use workerpool_rs::pool::WorkerPool;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+///
+/// let inits = Arc::new(AtomicUsize::new(0));
+/// let counter = Arc::clone(&inits);
+/// let pool = WorkerPool::with_state(2, move || {
+///     counter.fetch_add(1, Ordering::SeqCst);
+///     Vec::<u8>::with_capacity(1024)
+/// });
+///
+/// for i in 0..100 {
+///     pool.execute_with_state(move |buffer| {
+///         buffer.clear();
+///         buffer.push(i);
+///     })
+///     .unwrap();
+/// }
+///
+/// pool.join();
+/// assert!(inits.load(Ordering::SeqCst) <= 2);
+/// ```
+pub struct StatefulPool<S> {
+    pool: WorkerPool,
+    init: Arc<dyn Fn() -> S + Send + Sync>,
+    id: usize,
+    state: PhantomData<fn() -> S>,
+}
+
+impl<S: 'static> StatefulPool<S> {
+    /// Constructs a new StatefulPool that runs its jobs on the given pool.
+    ///
+    /// **pool**: WorkerPool - The pool running the jobs. \
+    /// **init**: A Fn closure that builds the state of a worker. \
+    /// **returns**: a StatefulPool object.
+    pub fn new<I>(pool: WorkerPool, init: I) -> StatefulPool<S>
+    where
+        I: Fn() -> S + Send + Sync + 'static,
+    {
+        StatefulPool {
+            pool,
+            init: Arc::new(init),
+            id: NEXT_POOL_ID.fetch_add(1, Ordering::SeqCst),
+            state: PhantomData,
+        }
+    }
+
+    /// Executes a job that borrows the state of the worker running it. If
+    /// the job panics, the state is dropped and the worker builds a new one
+    /// for its next job.
+    ///
+    /// **f**: A FnOnce closure that receives `&mut S`. \
+    /// **returns**: `Err(ExecuteError)` if the job can't be queued.
+    pub fn execute_with_state<F>(&self, f: F) -> Result<(), ExecuteError>
+    where
+        F: FnOnce(&mut S) + Send + 'static,
+    {
+        let init = Arc::clone(&self.init);
+        let id = self.id;
+        self.pool.execute(move || {
+            // The state is taken out of the map while the job runs, so the
+            // job may use other stateful pools on the same thread.
+            let state = STATES.with(|states| states.borrow_mut().remove(&id));
+            let mut state = match state.map(|state| state.downcast::<S>()) {
+                Some(Ok(state)) => state,
+                _ => Box::new(init()),
+            };
+            f(&mut state);
+            STATES.with(|states| states.borrow_mut().insert(id, state));
+        })
+    }
+
+    /// Returns the pool running the jobs, to execute stateless jobs or
+    /// read its counters.
+    pub fn pool(&self) -> &WorkerPool {
+        &self.pool
+    }
+
+    /// Blocks the current thread until every job is finished, as
+    /// [`WorkerPool::join`].
+    pub fn join(&self) {
+        self.pool.join();
+    }
+}
+
+// This sections are the beginning of state module unit tests.
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn state_should_be_reused_by_the_worker() {
+        let pool = WorkerPool::with_state(1, || 0);
+        let (tx, rx) = mpsc::channel();
+        for _ in 0..3 {
+            let tx = tx.clone();
+            pool.execute_with_state(move |count| {
+                *count += 1;
+                tx.send(*count).unwrap();
+            })
+            .unwrap();
+        }
+        assert_eq!(vec![1, 2, 3], rx.iter().take(3).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn state_should_be_rebuilt_after_a_panic() {
+        let pool = WorkerPool::with_state(1, || 10);
+        pool.execute_with_state(|count| {
+            *count += 1;
+            panic!("job failed");
+        })
+        .unwrap();
+        let (tx, rx) = mpsc::channel();
+        pool.execute_with_state(move |count| tx.send(*count).unwrap())
+            .unwrap();
+        assert_eq!(Ok(10), rx.recv());
+    }
+}
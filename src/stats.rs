@@ -0,0 +1,322 @@
+//! ## Stats
+//!
+//! This module contains [`PoolStats`], a snapshot of the counters of a
+//! pool returned by [`WorkerPool::stats`](crate::pool::WorkerPool::stats).
+//! It can be polled periodically and exported to a monitoring system.
+//! [`LabelStats`] breaks the same counters down per job label, and
+//! [`WorkerHeartbeat`] tells an idle worker from a wedged one.
+//! Latencies are recorded in lock-free log-scale histograms, cheap enough
+//! to be always on.
+
+use std::{
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+// The number of sub-buckets per power of two, as a power of two. Three bits
+// keep the error of a percentile under 12.5%.
+const SUB_BITS: u32 = 3;
+const SUB_BUCKETS: usize = 1 << SUB_BITS;
+const BUCKETS: usize = (64 - SUB_BITS as usize + 1) * SUB_BUCKETS;
+
+/// The 50th, 95th and 99th percentiles of a latency. Each one is the upper
+/// bound of the histogram bucket holding it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Percentiles {
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+}
+
+/// A snapshot of the pool counters. Counters are read one by one while the
+/// pool is running, so they may be slightly out of sync with each other.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PoolStats {
+    /// The number of jobs accepted by the pool.
+    pub submitted: usize,
+    /// The number of jobs that finished, including the ones that panicked.
+    pub completed: usize,
+    /// The number of jobs that panicked.
+    pub panicked: usize,
+    /// The number of jobs waiting in the queue.
+    pub queued: usize,
+    /// The number of workers executing a job.
+    pub busy_workers: usize,
+    /// The average time a job waited in the queue before it started.
+    pub average_wait: Duration,
+    /// The average time a job took to execute.
+    pub average_execution: Duration,
+    /// The percentiles of the time jobs waited in the queue.
+    pub wait_percentiles: Percentiles,
+    /// The percentiles of the time jobs took to execute.
+    pub execution_percentiles: Percentiles,
+    /// The heartbeat of every worker, in id order.
+    pub workers: Vec<WorkerHeartbeat>,
+}
+
+/// The heartbeat of a worker in a [`PoolStats`] snapshot. A worker beats
+/// when it picks up a job and when it finishes one, so a busy worker whose
+/// last beat is old is stuck in a job, while an idle one just has no work.
+///
+/// # Examples
+///
+/// ```
+/// use workerpool_rs::pool::WorkerPool;
+/// use std::time::Duration;
+///
+/// let pool = WorkerPool::new(2);
+/// pool.execute(|| {}).unwrap();
+/// pool.join();
+///
+/// let workers = pool.stats().workers;
+/// assert_eq!(2, workers.len());
+/// assert!(workers.iter().any(|worker| worker.last_beat.is_some()));
+/// assert!(workers.iter().all(|worker| !worker.is_wedged(Duration::ZERO)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkerHeartbeat {
+    /// The id of the worker.
+    pub id: usize,
+    /// Whether the worker is running a job.
+    pub busy: bool,
+    /// The last time the worker picked up or finished a job, or None if it
+    /// didn't run any job yet.
+    pub last_beat: Option<Instant>,
+}
+
+impl WorkerHeartbeat {
+    /// Checks whether the worker has been running the same job for longer
+    /// than max_execution.
+    ///
+    /// **max_execution**: Duration - The longest time a job may run.
+    pub fn is_wedged(&self, max_execution: Duration) -> bool {
+        self.busy
+            && self
+                .last_beat
+                .is_some_and(|beat| beat.elapsed() > max_execution)
+    }
+}
+
+/// A snapshot of the counters of the jobs with one label, returned by
+/// [`WorkerPool::stats_by_label`](crate::pool::WorkerPool::stats_by_label).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LabelStats {
+    /// The number of jobs with the label accepted by the pool.
+    pub submitted: usize,
+    /// The number of jobs with the label that finished, including the ones
+    /// that panicked.
+    pub completed: usize,
+    /// The number of jobs with the label that panicked.
+    pub panicked: usize,
+    /// The average time a job waited before it started.
+    pub average_wait: Duration,
+    /// The average time a job took to execute.
+    pub average_execution: Duration,
+    /// The percentiles of the time jobs waited before they started.
+    pub wait_percentiles: Percentiles,
+    /// The percentiles of the time jobs took to execute.
+    pub execution_percentiles: Percentiles,
+}
+
+// The counters of the jobs with one label.
+#[derive(Default)]
+pub(crate) struct LabelCounters {
+    submitted: AtomicUsize,
+    completed: AtomicUsize,
+    panicked: AtomicUsize,
+    waited: AtomicU64,
+    execution: AtomicU64,
+    wait_histogram: Histogram,
+    execution_histogram: Histogram,
+}
+
+impl LabelCounters {
+    pub(crate) fn submit(&self) {
+        self.submitted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn reject(&self) {
+        self.submitted.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn start(&self, waited: Duration) {
+        self.waited
+            .fetch_add(waited.as_nanos() as u64, Ordering::Relaxed);
+        self.wait_histogram.record(waited);
+    }
+
+    pub(crate) fn finish(&self, elapsed: Duration, panicked: bool) {
+        self.execution
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+        self.execution_histogram.record(elapsed);
+        if panicked {
+            self.panicked.fetch_add(1, Ordering::Relaxed);
+        }
+        self.completed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> LabelStats {
+        let completed = self.completed.load(Ordering::Relaxed);
+        let waited = Duration::from_nanos(self.waited.load(Ordering::Relaxed));
+        let execution = Duration::from_nanos(self.execution.load(Ordering::Relaxed));
+        let started = self.wait_histogram.count();
+        LabelStats {
+            submitted: self.submitted.load(Ordering::Relaxed),
+            completed,
+            panicked: self.panicked.load(Ordering::Relaxed),
+            average_wait: average(waited, started),
+            average_execution: average(execution, completed as u64),
+            wait_percentiles: self.wait_histogram.percentiles(),
+            execution_percentiles: self.execution_histogram.percentiles(),
+        }
+    }
+}
+
+// A histogram of durations in nanoseconds, with buckets growing
+// exponentially. Values below SUB_BUCKETS get a bucket each, and every
+// power of two above is split into SUB_BUCKETS buckets.
+pub(crate) struct Histogram {
+    buckets: Box<[AtomicU64]>,
+}
+
+impl Histogram {
+    pub(crate) fn new() -> Histogram {
+        Histogram {
+            buckets: (0..BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+
+    fn index(value: u64) -> usize {
+        if value < SUB_BUCKETS as u64 {
+            return value as usize;
+        }
+        let exponent = 63 - value.leading_zeros();
+        let sub = (value >> (exponent - SUB_BITS)) as usize & (SUB_BUCKETS - 1);
+        (exponent - SUB_BITS + 1) as usize * SUB_BUCKETS + sub
+    }
+
+    // Returns the largest value that falls in the bucket.
+    fn upper_bound(index: usize) -> u64 {
+        if index < SUB_BUCKETS {
+            return index as u64;
+        }
+        let exponent = (index / SUB_BUCKETS) as u32 + SUB_BITS - 1;
+        let sub = (index % SUB_BUCKETS) as u64;
+        let lower = (SUB_BUCKETS as u64 + sub) << (exponent - SUB_BITS);
+        lower + ((1 << (exponent - SUB_BITS)) - 1)
+    }
+
+    pub(crate) fn record(&self, duration: Duration) {
+        let nanos = duration.as_nanos().min(u64::MAX as u128) as u64;
+        self.buckets[Self::index(nanos)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Returns the upper bound of the bucket holding the given quantile, or
+    // zero if nothing was recorded.
+    fn quantile(counts: &[u64], total: u64, quantile: f64) -> Duration {
+        let rank = ((quantile * total as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (index, count) in counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Duration::from_nanos(Self::upper_bound(index));
+            }
+        }
+        Duration::ZERO
+    }
+
+    // Returns the number of recorded values.
+    pub(crate) fn count(&self) -> u64 {
+        self.buckets
+            .iter()
+            .map(|bucket| bucket.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    pub(crate) fn percentiles(&self) -> Percentiles {
+        let counts: Vec<u64> = self
+            .buckets
+            .iter()
+            .map(|bucket| bucket.load(Ordering::Relaxed))
+            .collect();
+        let total = counts.iter().sum();
+        Percentiles {
+            p50: Self::quantile(&counts, total, 0.50),
+            p95: Self::quantile(&counts, total, 0.95),
+            p99: Self::quantile(&counts, total, 0.99),
+        }
+    }
+}
+
+impl Default for Histogram {
+    fn default() -> Histogram {
+        Histogram::new()
+    }
+}
+
+// Returns total divided by count, or zero if count is zero.
+pub(crate) fn average(total: Duration, count: u64) -> Duration {
+    match count {
+        0 => Duration::ZERO,
+        count => Duration::from_nanos((total.as_nanos() / count as u128) as u64),
+    }
+}
+
+// This sections are the beginning of stats module unit tests.
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    #[test]
+    fn average_should_be_zero_without_samples() {
+        assert_eq!(Duration::ZERO, average(Duration::from_secs(3), 0));
+        assert_eq!(
+            Duration::from_millis(1500),
+            average(Duration::from_secs(3), 2)
+        );
+    }
+
+    #[test]
+    fn histogram_buckets_should_contain_their_values() {
+        for value in (0..10_000).chain([u64::MAX / 3, u64::MAX]) {
+            let index = Histogram::index(value);
+            assert!(value <= Histogram::upper_bound(index));
+            assert!(index == 0 || value > Histogram::upper_bound(index - 1));
+        }
+    }
+
+    #[test]
+    fn histogram_should_report_percentiles() {
+        let histogram = Histogram::new();
+        assert_eq!(Percentiles::default(), histogram.percentiles());
+        for micros in 1..=100 {
+            histogram.record(Duration::from_micros(micros));
+        }
+        let percentiles = histogram.percentiles();
+        let within = |expected: u64, actual: Duration| {
+            let expected = Duration::from_micros(expected);
+            actual >= expected && actual <= expected + expected / 8
+        };
+        assert!(within(50, percentiles.p50));
+        assert!(within(95, percentiles.p95));
+        assert!(within(99, percentiles.p99));
+    }
+
+    #[test]
+    fn label_counters_should_average_their_samples() {
+        let counters = LabelCounters::default();
+        counters.submit();
+        counters.submit();
+        counters.start(Duration::from_millis(2));
+        counters.start(Duration::from_millis(4));
+        counters.finish(Duration::from_millis(10), false);
+        counters.finish(Duration::from_millis(20), true);
+        let stats = counters.snapshot();
+        assert_eq!(
+            (2, 2, 1),
+            (stats.submitted, stats.completed, stats.panicked)
+        );
+        assert_eq!(Duration::from_millis(3), stats.average_wait);
+        assert_eq!(Duration::from_millis(15), stats.average_execution);
+    }
+}
@@ -0,0 +1,107 @@
+//! ## Tagged
+//!
+//! This module contains the concurrency groups used by
+//! [`WorkerPool::execute_tagged`](crate::pool::WorkerPool::execute_tagged).
+//! A tag with a limit lets at most that many of its jobs be in the pool
+//! queue or running, and keeps the others waiting in a FIFO.
+
+use crate::pool::Job;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Mutex, PoisonError},
+};
+
+// A tag limit, the number of its jobs queued or running, and the jobs
+// waiting for one of them to finish.
+struct Group {
+    limit: usize,
+    running: usize,
+    waiting: VecDeque<Job>,
+}
+
+// The concurrency groups of the tags that have a limit.
+pub(crate) struct TagGroups {
+    groups: Mutex<HashMap<String, Group>>,
+}
+
+impl TagGroups {
+    pub(crate) fn new<I: IntoIterator<Item = (String, usize)>>(limits: I) -> TagGroups {
+        let groups = limits
+            .into_iter()
+            .map(|(tag, limit)| {
+                let group = Group {
+                    limit: limit.max(1),
+                    running: 0,
+                    waiting: VecDeque::new(),
+                };
+                (tag, group)
+            })
+            .collect();
+        TagGroups {
+            groups: Mutex::new(groups),
+        }
+    }
+
+    // Checks whether the tag has a limit.
+    pub(crate) fn is_limited(&self, tag: &str) -> bool {
+        let groups = self.groups.lock().unwrap_or_else(PoisonError::into_inner);
+        groups.contains_key(tag)
+    }
+
+    // Appends a job to its group. If the group is under its limit, the job
+    // is given back, as the caller must queue it in the pool.
+    pub(crate) fn enqueue(&self, tag: &str, job: Job) -> Option<Job> {
+        let mut groups = self.groups.lock().unwrap_or_else(PoisonError::into_inner);
+        let group = groups.get_mut(tag)?;
+        if group.running < group.limit {
+            group.running += 1;
+            Some(job)
+        } else {
+            group.waiting.push_back(job);
+            None
+        }
+    }
+
+    // Frees the slot of a finished job and returns the next waiting job,
+    // which takes the slot.
+    pub(crate) fn next(&self, tag: &str) -> Option<Job> {
+        let mut groups = self.groups.lock().unwrap_or_else(PoisonError::into_inner);
+        let group = groups.get_mut(tag)?;
+        let next = group.waiting.pop_front();
+        if next.is_none() {
+            group.running -= 1;
+        }
+        next
+    }
+
+    // Frees the slot of a job that won't run and returns the waiting jobs.
+    pub(crate) fn remove(&self, tag: &str) -> Vec<Job> {
+        let mut groups = self.groups.lock().unwrap_or_else(PoisonError::into_inner);
+        match groups.get_mut(tag) {
+            Some(group) => {
+                group.running -= 1;
+                group.waiting.drain(..).collect()
+            }
+            None => Vec::new(),
+        }
+    }
+}
+
+// This sections are the beginning of tagged module unit tests.
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    #[test]
+    fn groups_should_give_back_jobs_up_to_the_limit() {
+        let groups = TagGroups::new(vec![(String::from("io"), 2)]);
+        assert!(groups.enqueue("io", Box::new(|| {})).is_some());
+        assert!(groups.enqueue("io", Box::new(|| {})).is_some());
+        assert!(groups.enqueue("io", Box::new(|| {})).is_none());
+        assert!(groups.next("io").is_some());
+        assert!(groups.next("io").is_none());
+        assert!(groups.next("io").is_none());
+        assert!(groups.enqueue("io", Box::new(|| {})).is_some());
+        assert!(!groups.is_limited("cpu"));
+    }
+}
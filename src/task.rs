@@ -0,0 +1,191 @@
+//! ## Task
+//!
+//! This module contains the job representation used by the queue. A
+//! closure small enough is stored inline in the task, so queueing it
+//! doesn't allocate, and a larger one is boxed. A boxed [`Job`] fits
+//! inline, so it isn't boxed again.
+
+use crate::pool::Job;
+use std::mem::{self, ManuallyDrop, MaybeUninit};
+#[cfg(feature = "tracing")]
+use std::time::{Duration, Instant};
+
+// The number of words a closure stored inline can take. Four words hold a
+// closure capturing a few handles, or a boxed Job.
+const INLINE_WORDS: usize = 4;
+
+// The storage of a task.
+type Inline = MaybeUninit<[usize; INLINE_WORDS]>;
+
+// The operations on the closure stored in a task.
+//
+// call: unsafe fn(*mut u8) - moves the closure out and calls it.\
+// drop: unsafe fn(*mut u8) - drops the closure without calling it.
+struct VTable {
+    call: unsafe fn(*mut u8),
+    drop: unsafe fn(*mut u8),
+}
+
+// Builds the vtable of a closure type.
+struct Operations<F>(F);
+
+impl<F: FnOnce()> Operations<F> {
+    const VTABLE: VTable = VTable {
+        call: Operations::<F>::call,
+        drop: Operations::<F>::drop,
+    };
+
+    // SAFETY: data must point to an initialized F, which is moved out.
+    unsafe fn call(data: *mut u8) {
+        (data as *mut F).read()()
+    }
+
+    // SAFETY: data must point to an initialized F, which is dropped.
+    unsafe fn drop(data: *mut u8) {
+        (data as *mut F).drop_in_place()
+    }
+}
+
+// The span current when a task was created, and when it was, to trace its
+// job as a child of the submitting span.
+#[cfg(feature = "tracing")]
+struct Trace {
+    parent: tracing::Span,
+    queued: Instant,
+}
+
+// A closure queued in the pool, stored inline when it fits, with the label
+// of its job if it has one.
+pub(crate) struct Task {
+    data: Inline,
+    vtable: &'static VTable,
+    label: Option<&'static str>,
+    #[cfg(feature = "tracing")]
+    trace: Option<Trace>,
+}
+
+// SAFETY: a task only holds a closure that is Send.
+unsafe impl Send for Task {}
+
+impl Task {
+    // Stores f inline when its size and alignment allow it, and boxes it
+    // otherwise.
+    pub(crate) fn new<F>(f: F) -> Task
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        if mem::size_of::<F>() <= mem::size_of::<Inline>()
+            && mem::align_of::<F>() <= mem::align_of::<Inline>()
+        {
+            Task::inline(f)
+        } else {
+            Task::inline(Box::new(f))
+        }
+    }
+
+    // Stores f inline. The caller checked that it fits.
+    fn inline<F>(f: F) -> Task
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let mut data = Inline::uninit();
+        // SAFETY: F fits in data, with a compatible alignment.
+        unsafe { (data.as_mut_ptr() as *mut F).write(f) };
+        Task {
+            data,
+            vtable: &Operations::<F>::VTABLE,
+            label: None,
+            #[cfg(feature = "tracing")]
+            trace: Some(Trace {
+                parent: tracing::Span::current(),
+                queued: Instant::now(),
+            }),
+        }
+    }
+
+    // Labels the task.
+    pub(crate) fn labeled(mut self, label: &'static str) -> Task {
+        self.label = Some(label);
+        self
+    }
+
+    // Returns the label of the task, if it has one.
+    pub(crate) fn label(&self) -> Option<&'static str> {
+        self.label
+    }
+
+    // Returns the span of the job of the task, run by the given worker from
+    // started on. Its panicked field is recorded if the job panics.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn span(&self, worker: usize, started: Instant) -> tracing::Span {
+        let (parent, waited) = match &self.trace {
+            Some(trace) => (
+                trace.parent.id(),
+                started.saturating_duration_since(trace.queued),
+            ),
+            None => (None, Duration::ZERO),
+        };
+        tracing::info_span!(
+            parent: parent,
+            "job",
+            worker,
+            label = self.label,
+            queue_wait_us = waited.as_micros() as u64,
+            panicked = tracing::field::Empty,
+        )
+    }
+
+    // Calls the closure.
+    pub(crate) fn run(self) {
+        let mut task = ManuallyDrop::new(self);
+        // The task isn't dropped, so its span is released here.
+        #[cfg(feature = "tracing")]
+        drop(task.trace.take());
+        // SAFETY: the closure is initialized and, as the task is not
+        // dropped, it is moved out only once.
+        unsafe { (task.vtable.call)(task.data.as_mut_ptr() as *mut u8) }
+    }
+
+    // Boxes the task into a Job, as handed out of the pool.
+    pub(crate) fn into_job(self) -> Job {
+        Box::new(move || self.run())
+    }
+}
+
+impl Drop for Task {
+    fn drop(&mut self) {
+        // SAFETY: the closure is initialized, as a task that ran is not
+        // dropped.
+        unsafe { (self.vtable.drop)(self.data.as_mut_ptr() as *mut u8) }
+    }
+}
+
+impl From<Job> for Task {
+    fn from(job: Job) -> Task {
+        Task::new(job)
+    }
+}
+
+// This sections are the beginning of task module unit tests.
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use std::sync::{mpsc, Arc};
+
+    #[test]
+    fn task_should_run_or_drop_its_closure_once() {
+        let (tx, rx) = mpsc::channel();
+        let small = tx.clone();
+        Task::new(move || small.send(1).unwrap()).run();
+        let large = [tx.clone(), tx.clone(), tx.clone(), tx.clone(), tx.clone()];
+        Task::new(move || large[4].send(2).unwrap()).run();
+        let boxed: Job = Box::new(move || tx.send(3).unwrap());
+        Task::from(boxed).run();
+        assert_eq!(vec![1, 2, 3], rx.iter().collect::<Vec<_>>());
+
+        let counter = Arc::new(());
+        let held = counter.clone();
+        drop(Task::new(move || drop(held)));
+        assert_eq!(1, Arc::strong_count(&counter));
+    }
+}
@@ -0,0 +1,86 @@
+//! ## Thread priority
+//!
+//! This module contains the OS priority of the worker threads, set with
+//! [`WorkerPoolBuilder::thread_priority`](crate::pool::WorkerPoolBuilder::thread_priority).
+//! A pool of background work can run at a lower priority, so it doesn't
+//! compete with latency critical threads.
+//!
+//! The priority is the niceness of the thread, set with `setpriority` on
+//! Linux. On other platforms the priority is ignored.
+
+/// The OS priority of the worker threads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ThreadPriority {
+    /// The priority the threads inherit from the process.
+    #[default]
+    Inherit,
+    /// The lowest priority, for work that only runs when the machine has
+    /// nothing else to do.
+    Background,
+    /// A niceness from -20, the highest priority, to 19, the lowest.
+    /// Raising the priority above the inherited one needs privileges.
+    Nice(i8),
+}
+
+impl ThreadPriority {
+    // Returns the niceness to set, if any.
+    fn niceness(self) -> Option<i32> {
+        match self {
+            ThreadPriority::Inherit => None,
+            ThreadPriority::Background => Some(19),
+            ThreadPriority::Nice(nice) => Some(i32::from(nice).clamp(-20, 19)),
+        }
+    }
+}
+
+// Sets the priority of the calling thread.
+//
+// **returns**: true if the priority was set.
+#[cfg(target_os = "linux")]
+pub(crate) fn apply(priority: ThreadPriority) -> bool {
+    // Selects the thread id as the target of setpriority.
+    const PRIO_PROCESS: i32 = 0;
+    // Selects the calling thread as the target of setpriority.
+    const CALLING_THREAD: u32 = 0;
+
+    extern "C" {
+        fn setpriority(which: i32, who: u32, prio: i32) -> i32;
+    }
+
+    let Some(nice) = priority.niceness() else {
+        return false;
+    };
+    // On Linux the niceness belongs to a thread, and an id of 0 stands for
+    // the calling one.
+    unsafe { setpriority(PRIO_PROCESS, CALLING_THREAD, nice) == 0 }
+}
+
+// Sets the priority of the calling thread, which is not supported on this
+// platform.
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn apply(priority: ThreadPriority) -> bool {
+    let _ = priority.niceness();
+    false
+}
+
+// This sections are the beginning of thread priority module unit tests.
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn priority_should_clamp_niceness() {
+        assert_eq!(None, ThreadPriority::Inherit.niceness());
+        assert_eq!(Some(19), ThreadPriority::Background.niceness());
+        assert_eq!(Some(-20), ThreadPriority::Nice(-100).niceness());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn background_priority_should_apply_to_a_thread() {
+        assert!(thread::spawn(|| apply(ThreadPriority::Background))
+            .join()
+            .unwrap());
+    }
+}
@@ -0,0 +1,144 @@
+//! ## Tokio
+//!
+//! This module contains [`BlockingPool`], which runs the blocking work of
+//! tokio tasks on a [`WorkerPool`] in place of `tokio::task::spawn_blocking`,
+//! whose threads grow without bound. A job is awaited through a
+//! [`JobFuture`](crate::future::JobFuture), which wakes the awaiting task
+//! once the job finishes, and cancels the job when dropped before it
+//! starts, as when the task is aborted or loses a `select!`. The jobs
+//! queued or running are bounded by permits, awaited without blocking the
+//! runtime. Submitting still blocks the executor thread when the bounded
+//! queue of the pool is full under `SaturationPolicy::Block`, which the
+//! permits only rule out if the limit is at most the workers plus the
+//! queue capacity and no other code submits to the pool.
+//!
+//! ## Examples
+//!
+//! ```
+//! use workerpool_rs::pool::WorkerPool;
+//! use workerpool_rs::tokio::BlockingPool;
+//!
+//! let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+//! let pool = BlockingPool::new(WorkerPool::new(2), 16);
+//! let value = runtime.block_on(async { pool.spawn_blocking(|| 6 * 7).await });
+//! assert_eq!(Ok(42), value);
+//! ```
+
+use crate::{error::Error, pool::WorkerPool};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+/// An adapter running the blocking jobs of tokio tasks on a [`WorkerPool`]
+/// and awaiting their values. At most `limit` jobs are queued or running
+/// at once, and later ones wait for a permit. Clones share the pool and
+/// the permits.
+#[derive(Clone)]
+pub struct BlockingPool {
+    pool: WorkerPool,
+    permits: Arc<Semaphore>,
+}
+
+impl BlockingPool {
+    /// Constructs a new BlockingPool.
+    ///
+    /// **pool**: WorkerPool - The pool running the jobs. \
+    /// **limit**: usize - The most jobs queued or running at once. With a
+    /// bounded queue, it shouldn't exceed the workers plus the queue
+    /// capacity, so a submission never blocks. \
+    /// **returns**: a BlockingPool object.
+    pub fn new(pool: WorkerPool, limit: usize) -> BlockingPool {
+        BlockingPool {
+            pool,
+            permits: Arc::new(Semaphore::new(limit)),
+        }
+    }
+
+    /// Returns the pool running the jobs, to read its stats.
+    ///
+    /// **returns**: a reference to the WorkerPool.
+    pub fn pool(&self) -> &WorkerPool {
+        &self.pool
+    }
+
+    /// Runs f on the pool, as `tokio::task::spawn_blocking`, once a permit
+    /// is available, and resolves to the value it returns. Dropping the
+    /// future before the job starts cancels it.
+    ///
+    /// **f**: A FnOnce closure that returns a value. \
+    /// **returns**: the value of f, `Err(Error::Disconnected)` if the job
+    /// panicked or was discarded, or the error of the submission.
+    pub async fn spawn_blocking<F, R>(&self, f: F) -> Result<R, Error>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let permit = Arc::clone(&self.permits)
+            .acquire_owned()
+            .await
+            .map_err(|_| Error::Disconnected)?;
+        let future = self.pool.submit_async(move || {
+            let _permit = permit;
+            f()
+        })?;
+        Ok(future.await?)
+    }
+}
+
+// This sections are the beginning of tokio module unit tests.
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc;
+    use tokio::runtime::{Builder, Runtime};
+
+    fn runtime() -> Runtime {
+        Builder::new_current_thread().build().unwrap()
+    }
+
+    #[test]
+    fn blocking_pool_should_bound_the_jobs_in_flight() {
+        let runtime = runtime();
+        let pool = BlockingPool::new(WorkerPool::new(2), 1);
+        let (tx, rx) = mpsc::channel::<()>();
+        let first = pool.clone();
+        let first = runtime.spawn(async move { first.spawn_blocking(move || rx.recv()).await });
+        let second = pool.clone();
+        let second = runtime.spawn(async move { second.spawn_blocking(|| 42).await });
+        while pool.pool().stats().busy_workers == 0 {
+            runtime.block_on(tokio::task::yield_now());
+        }
+        assert_eq!(1, pool.pool().stats().submitted);
+
+        tx.send(()).unwrap();
+        assert_eq!(Ok(Ok(())), runtime.block_on(first).unwrap());
+        assert_eq!(Ok(42), runtime.block_on(second).unwrap());
+    }
+
+    #[test]
+    fn blocking_pool_should_cancel_the_job_of_an_aborted_task() {
+        let runtime = runtime();
+        let pool = BlockingPool::new(WorkerPool::new(1), 4);
+        let (tx, rx) = mpsc::channel::<()>();
+        pool.pool().execute(move || rx.recv().unwrap()).unwrap();
+
+        let ran = Arc::new(AtomicBool::new(false));
+        let job_ran = Arc::clone(&ran);
+        let aborted = pool.clone();
+        let task = runtime.spawn(async move {
+            aborted
+                .spawn_blocking(move || job_ran.store(true, Ordering::SeqCst))
+                .await
+        });
+        while pool.pool().stats().queued == 0 {
+            runtime.block_on(tokio::task::yield_now());
+        }
+        task.abort();
+        assert!(runtime.block_on(task).unwrap_err().is_cancelled());
+
+        tx.send(()).unwrap();
+        pool.pool().join();
+        assert!(!ran.load(Ordering::SeqCst));
+        assert_eq!(4, pool.permits.available_permits());
+    }
+}
@@ -0,0 +1,184 @@
+//! ## Wait Group
+//!
+//! This module contains a [`WaitGroup`], a counter of unfinished work that
+//! threads can wait on. Jobs executed with
+//! [`WorkerPool::execute_in_group`](crate::pool::WorkerPool::execute_in_group)
+//! are added to a group and marked done when they finish, so waiting for a
+//! batch of jobs works whatever the number of workers.
+
+use std::{
+    sync::{Arc, Condvar, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+// count: Mutex<usize> - the number of unfinished tasks.\
+// zero: Condvar - notified when the count gets back to zero.
+struct Counter {
+    count: Mutex<usize>,
+    zero: Condvar,
+}
+
+/// A counter of unfinished tasks. Clones share the same counter.
+///
+/// ## Examples
+///
+/// ```
+/// use workerpool_rs::pool::WorkerPool;
+/// use workerpool_rs::wait_group::WaitGroup;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+///
+/// let pool = WorkerPool::new(2);
+/// let group = WaitGroup::new();
+/// let counter = Arc::new(AtomicUsize::new(0));
+///
+/// for _ in 0..16 {
+///     let counter = counter.clone();
+///     pool.execute_in_group(&group, move || {
+///         counter.fetch_add(1, Ordering::SeqCst);
+///     }).unwrap();
+/// }
+///
+/// group.wait();
+/// assert_eq!(16, counter.load(Ordering::SeqCst));
+/// ```
+#[derive(Clone)]
+pub struct WaitGroup {
+    counter: Arc<Counter>,
+}
+
+impl WaitGroup {
+    /// Constructs a new WaitGroup with no unfinished task.
+    pub fn new() -> WaitGroup {
+        WaitGroup {
+            counter: Arc::new(Counter {
+                count: Mutex::new(0),
+                zero: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Adds unfinished tasks to the group.
+    ///
+    /// **count**: usize - The number of tasks to add.
+    pub fn add(&self, count: usize) {
+        *self.lock() += count;
+    }
+
+    /// Marks one task of the group as done, waking up the waiting threads
+    /// if it was the last one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the group has no unfinished task.
+    pub fn done(&self) {
+        let mut count = self.lock();
+        *count = count
+            .checked_sub(1)
+            .expect("done called on a WaitGroup without unfinished tasks");
+        if *count == 0 {
+            self.counter.zero.notify_all();
+        }
+    }
+
+    /// Returns the number of unfinished tasks.
+    pub fn count(&self) -> usize {
+        *self.lock()
+    }
+
+    /// Blocks the current thread until every task of the group is done.
+    pub fn wait(&self) {
+        let count = self.lock();
+        drop(
+            self.counter
+                .zero
+                .wait_while(count, |count| *count > 0)
+                .unwrap_or_else(PoisonError::into_inner),
+        );
+    }
+
+    /// Blocks the current thread until every task of the group is done, or
+    /// the timeout expires.
+    ///
+    /// **timeout**: Duration - The maximum time to wait. \
+    /// **returns**: false if some tasks were still unfinished at the
+    /// timeout.
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut count = self.lock();
+        while *count > 0 {
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            count = self
+                .counter
+                .zero
+                .wait_timeout(count, deadline - now)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+        }
+        true
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, usize> {
+        self.counter
+            .count
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Default for WaitGroup {
+    fn default() -> WaitGroup {
+        WaitGroup::new()
+    }
+}
+
+// Marks a task of a group as done when dropped, so a task that panics or is
+// discarded is done too.
+pub(crate) struct GroupGuard(pub(crate) WaitGroup);
+
+impl Drop for GroupGuard {
+    fn drop(&mut self) {
+        self.0.done();
+    }
+}
+
+// This sections are the beginning of wait_group module unit tests.
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn group_should_wait_for_every_task() {
+        let group = WaitGroup::new();
+        group.add(3);
+        let handles: Vec<_> = (0..3)
+            .map(|_| {
+                let group = group.clone();
+                thread::spawn(move || group.done())
+            })
+            .collect();
+        group.wait();
+        assert_eq!(0, group.count());
+        handles.into_iter().for_each(|h| h.join().unwrap());
+    }
+
+    #[test]
+    fn group_should_time_out_with_unfinished_tasks() {
+        let group = WaitGroup::new();
+        assert!(group.wait_timeout(Duration::ZERO));
+        group.add(1);
+        assert!(!group.wait_timeout(Duration::from_millis(5)));
+        drop(GroupGuard(group.clone()));
+        assert!(group.wait_timeout(Duration::ZERO));
+    }
+
+    #[test]
+    #[should_panic(expected = "without unfinished tasks")]
+    fn done_should_panic_without_unfinished_tasks() {
+        WaitGroup::new().done();
+    }
+}
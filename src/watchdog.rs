@@ -0,0 +1,161 @@
+//! ## Watchdog
+//!
+//! This module contains the watchdog enabled with
+//! [`WorkerPoolBuilder::watchdog`](crate::pool::WorkerPoolBuilder::watchdog).
+//! A supervisor thread checks the running jobs at a fixed interval and
+//! reports, once, every job running for longer than a threshold, so hung
+//! jobs are noticed. A reported job keeps running, as a thread can't be
+//! stopped from the outside.
+
+use crate::context::JobId;
+use std::{
+    io,
+    sync::{Arc, Condvar, Mutex, PoisonError},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// The settings of the watchdog. The fields not given to
+/// [`new`](Watchdog::new) can be overridden with the struct update syntax.
+///
+/// # Examples
+///
+/// ```
+/// use workerpool_rs::pool::WorkerPoolBuilder;
+/// use workerpool_rs::watchdog::Watchdog;
+/// use std::time::Duration;
+///
+/// let pool = WorkerPoolBuilder::new()
+///     .watchdog(
+///         Watchdog {
+///             mark_suspect: true,
+///             ..Watchdog::new(Duration::from_secs(30))
+///         },
+///         |stuck| eprintln!("job {:?} stuck for {:?}", stuck.label, stuck.elapsed),
+///     )
+///     .build();
+///
+/// assert_eq!(Ok(2), pool.submit(|| 1 + 1).unwrap().wait());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watchdog {
+    /// A job running for longer than this is reported.
+    pub max_execution: Duration,
+    /// How often the running jobs are checked.
+    pub interval: Duration,
+    /// Whether the worker of a reported job is marked as suspect until the
+    /// job returns.
+    pub mark_suspect: bool,
+}
+
+impl Watchdog {
+    /// Constructs the settings of a watchdog reporting the jobs running for
+    /// longer than max_execution. The jobs are checked four times per
+    /// max_execution, and workers are not marked as suspect.
+    ///
+    /// **max_execution**: Duration - The longest time a job may run.
+    pub fn new(max_execution: Duration) -> Watchdog {
+        Watchdog {
+            max_execution,
+            interval: (max_execution / 4).max(Duration::from_millis(1)),
+            mark_suspect: false,
+        }
+    }
+}
+
+/// A job reported by the watchdog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StuckJob {
+    /// The id of the worker running the job.
+    pub worker: usize,
+    /// The id of the job, for the jobs that have one.
+    pub job: Option<JobId>,
+    /// The label of the job, for the jobs that have one.
+    pub label: Option<&'static str>,
+    /// How long the job had been running when it was reported.
+    pub elapsed: Duration,
+}
+
+// Owns the watchdog thread, which calls check at every interval.
+pub(crate) struct Watcher {
+    stopped: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Watcher {
+    pub(crate) fn new<C>(interval: Duration, check: C) -> io::Result<Watcher>
+    where
+        C: Fn(Instant) + Send + 'static,
+    {
+        let stopped = Arc::new((Mutex::new(false), Condvar::new()));
+        let thread_stopped = Arc::clone(&stopped);
+        let thread = thread::Builder::new()
+            .name(String::from("workerpool-watchdog"))
+            .spawn(move || {
+                let (lock, wakeup) = &*thread_stopped;
+                let mut stopped = lock.lock().unwrap_or_else(PoisonError::into_inner);
+                while !*stopped {
+                    stopped = wakeup
+                        .wait_timeout(stopped, interval)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0;
+                    if !*stopped {
+                        check(Instant::now());
+                    }
+                }
+            })?;
+
+        Ok(Watcher {
+            stopped,
+            thread: Some(thread),
+        })
+    }
+
+    // Stops the watchdog thread.
+    pub(crate) fn stop(&mut self) {
+        let (lock, wakeup) = &*self.stopped;
+        *lock.lock().unwrap_or_else(PoisonError::into_inner) = true;
+        wakeup.notify_one();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+// This sections are the beginning of watchdog module unit tests.
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn watcher_should_check_until_stopped() {
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = Watcher::new(Duration::from_millis(1), move |now| {
+            let _ = tx.send(now);
+        })
+        .unwrap();
+        let first = rx.recv().unwrap();
+        assert!(rx.recv().unwrap() >= first);
+        watcher.stop();
+        while rx.recv().is_ok() {}
+    }
+
+    #[test]
+    fn watchdog_should_check_four_times_per_threshold() {
+        assert_eq!(
+            Duration::from_millis(250),
+            Watchdog::new(Duration::from_secs(1)).interval
+        );
+        assert_eq!(
+            Duration::from_millis(1),
+            Watchdog::new(Duration::ZERO).interval
+        );
+    }
+}
@@ -0,0 +1,99 @@
+//! ## Weighted
+//!
+//! This module contains the weight budget used by
+//! [`WorkerPool::execute_weighted`](crate::pool::WorkerPool::execute_weighted).
+//! A job takes its weight from the budget while it is in the pool queue or
+//! running, and the jobs that don't fit wait, in order, until enough weight
+//! is given back.
+
+use crate::pool::Job;
+use std::{
+    collections::VecDeque,
+    sync::{Mutex, PoisonError},
+};
+
+// The budget capacity, the weight taken by the jobs queued or running, and
+// the jobs waiting for room with their weight.
+struct Budget {
+    capacity: usize,
+    used: usize,
+    waiting: VecDeque<(usize, Job)>,
+}
+
+impl Budget {
+    // Moves the waiting jobs that fit, in order, into the budget.
+    fn admit(&mut self) -> Vec<(usize, Job)> {
+        let mut admitted = Vec::new();
+        while let Some((weight, _)) = self.waiting.front() {
+            if self.used + weight > self.capacity {
+                break;
+            }
+            self.used += weight;
+            admitted.extend(self.waiting.pop_front());
+        }
+        admitted
+    }
+}
+
+// The weight budget of a pool.
+pub(crate) struct WeightBudget {
+    budget: Mutex<Budget>,
+}
+
+impl WeightBudget {
+    pub(crate) fn new(capacity: usize) -> WeightBudget {
+        WeightBudget {
+            budget: Mutex::new(Budget {
+                capacity: capacity.max(1),
+                used: 0,
+                waiting: VecDeque::new(),
+            }),
+        }
+    }
+
+    // Appends a job to the waiting jobs. A weight above the capacity is cut
+    // to it, so the job runs alone. If the job fits, it is given back with
+    // its weight, as the caller must queue it in the pool.
+    pub(crate) fn enqueue(&self, weight: usize, job: Job) -> Option<(usize, Job)> {
+        let mut budget = self.budget.lock().unwrap_or_else(PoisonError::into_inner);
+        let weight = weight.min(budget.capacity);
+        budget.waiting.push_back((weight, job));
+        budget.admit().pop()
+    }
+
+    // Gives back the weight of a finished job and returns the waiting jobs
+    // that fit now, which take their weight.
+    pub(crate) fn release(&self, weight: usize) -> Vec<(usize, Job)> {
+        let mut budget = self.budget.lock().unwrap_or_else(PoisonError::into_inner);
+        budget.used -= weight;
+        budget.admit()
+    }
+
+    // Gives back the weight of a job that won't run and returns every
+    // waiting job.
+    pub(crate) fn remove(&self, weight: usize) -> Vec<Job> {
+        let mut budget = self.budget.lock().unwrap_or_else(PoisonError::into_inner);
+        budget.used -= weight;
+        budget.waiting.drain(..).map(|(_, job)| job).collect()
+    }
+}
+
+// This sections are the beginning of weighted module unit tests.
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    #[test]
+    fn budget_should_admit_jobs_in_order_as_weight_frees_up() {
+        let budget = WeightBudget::new(4);
+        assert_eq!(3, budget.enqueue(3, Box::new(|| {})).unwrap().0);
+        assert!(budget.enqueue(2, Box::new(|| {})).is_none());
+        assert!(budget.enqueue(1, Box::new(|| {})).is_none());
+        let admitted: Vec<_> = budget.release(3).into_iter().map(|(w, _)| w).collect();
+        assert_eq!(vec![2, 1], admitted);
+        assert!(budget.enqueue(9, Box::new(|| {})).is_none());
+        assert_eq!(1, budget.remove(2).len());
+        assert!(budget.release(1).is_empty());
+        assert_eq!(4, budget.enqueue(9, Box::new(|| {})).unwrap().0);
+    }
+}
@@ -24,7 +24,8 @@ fn pool_should_sum_atomic_variable() {
         pool.execute(move || {
             atomic.fetch_add(1, Ordering::Relaxed);
             b.wait();
-        });
+        })
+        .unwrap();
     }
     barrier.wait();
     assert_eq!(atomic.load(Ordering::SeqCst), njobs);
@@ -44,8 +45,9 @@ fn pool_should_synchronize_sender_and_receiver_and_fold_results() {
         pool.execute(move || {
             let tx = atx.lock().unwrap();
             tx.send(1).expect("channel waiting for pool");
-        });
+        })
+        .unwrap();
     }
 
-    assert_eq!(rx.iter().take(njobs).fold(0, |a, b| a + b), njobs);
+    assert_eq!(rx.iter().take(njobs).sum::<usize>(), njobs);
 }